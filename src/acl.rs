@@ -0,0 +1,166 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::RwLock,
+};
+
+use crate::sha256;
+
+/// An ACL user. Only the enabled flag and passwords are enforced; key, channel and
+/// command rules are accepted by SETUSER but every user can run everything.
+#[derive(Debug, Clone, Default)]
+pub struct User {
+    pub enabled: bool,
+    pub nopass: bool,
+    /// Hex SHA-256 digests, the form ACL LIST and GETUSER report.
+    pub passwords: BTreeSet<String>,
+}
+
+impl User {
+    fn accepts(&self, password: &str) -> bool {
+        self.enabled && (self.nopass || self.passwords.contains(&sha256::hex_digest(password.as_bytes())))
+    }
+
+    fn apply(&mut self, rule: &str) -> Result<(), String> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "reset" => *self = User::default(),
+            "allkeys" | "resetkeys" | "allchannels" | "resetchannels" | "allcommands"
+            | "nocommands" => {}
+            // A rule that is empty or starts with a multi-byte character has no prefix to
+            // split off, and falls through to the syntax error.
+            _ => match rule.split_at_checked(1).unwrap_or_default() {
+                (">", password) => {
+                    self.nopass = false;
+                    self.passwords.insert(sha256::hex_digest(password.as_bytes()));
+                }
+                ("<", password) => {
+                    let digest = sha256::hex_digest(password.as_bytes());
+                    if !self.passwords.remove(&digest) {
+                        return Err(format!(
+                            "ERR Error in ACL SETUSER modifier '{rule}': no such password"
+                        ));
+                    }
+                }
+                ("#", digest) if digest.len() == 64 && hex::decode(digest).is_ok() => {
+                    self.nopass = false;
+                    self.passwords.insert(digest.to_ascii_lowercase());
+                }
+                ("!", digest) => {
+                    if !self.passwords.remove(&digest.to_ascii_lowercase()) {
+                        return Err(format!(
+                            "ERR Error in ACL SETUSER modifier '{rule}': no such password"
+                        ));
+                    }
+                }
+                ("~" | "%" | "&" | "+" | "-", _) => {}
+                _ => {
+                    return Err(format!(
+                        "ERR Error in ACL SETUSER modifier '{rule}': Syntax error"
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// The user as an ACL LIST line.
+    pub fn describe(&self, name: &str) -> String {
+        let mut parts = vec![
+            "user".to_string(),
+            name.to_string(),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+        if self.nopass {
+            parts.push("nopass".into());
+        }
+        parts.extend(self.passwords.iter().map(|digest| format!("#{digest}")));
+        parts.extend(["~*", "&*", "+@all"].map(String::from));
+        parts.join(" ")
+    }
+}
+
+#[derive(Debug)]
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl Acl {
+    /// Starts with the `default` user, protected by `requirepass` when one is configured.
+    pub fn new(requirepass: Option<&str>) -> Self {
+        let mut default = User {
+            enabled: true,
+            ..User::default()
+        };
+        match requirepass {
+            Some(password) => {
+                default.passwords.insert(sha256::hex_digest(password.as_bytes()));
+            }
+            None => default.nopass = true,
+        }
+        Self {
+            users: RwLock::new(BTreeMap::from([("default".to_string(), default)])),
+        }
+    }
+
+    /// Whether new connections start out authenticated as `default`.
+    pub fn default_is_open(&self) -> bool {
+        self.get_user("default")
+            .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    pub fn authenticate(&self, username: &str, password: &str) -> bool {
+        self.get_user(username)
+            .is_some_and(|user| user.accepts(password))
+    }
+
+    /// Creates the user if needed and applies `rules` in order. Nothing changes if any
+    /// rule is rejected.
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), String> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(name).cloned().unwrap_or_default();
+        for rule in rules {
+            user.apply(rule)?;
+        }
+        users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    pub fn get_user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, user)| user.describe(name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_without_a_prefix_are_syntax_errors() {
+        let acl = Acl::new(None);
+        for rule in ["", "é>pw", "?x"] {
+            let err = acl.set_user("u", &[rule.to_string()]).unwrap_err();
+            assert!(err.ends_with("Syntax error"), "{rule:?}: {err}");
+        }
+        // The rejected rules leave the ACL usable.
+        acl.set_user("u", &["on".into(), ">pw".into()]).unwrap();
+        assert!(acl.authenticate("u", "pw"));
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+    sync::{mpsc, Mutex, RwLock, RwLockReadGuard},
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    log,
+    acl::Acl,
+    command::{self, ExpireCondition, FieldExpiry, RespCommand},
+    handlers::master::replay_command,
+    latency::LatencyMonitor,
+    rdb_parser::config::RdbConfig,
+    replication_manager::manager::ReplicationManager,
+    resp::{RespCodec, RespValue},
+    scripting::ScriptCache,
+    server_context::ServerContext,
+    server_info::ServerInfo,
+    shared_store::{keyspace::unix_now_ms, shared_store::Store},
+};
+
+enum AofMessage {
+    Append(Vec<u8>),
+    Rewrite(Vec<u8>),
+}
+
+/// Handle to the append-only file. Writes are queued to a background task so the
+/// command path never waits on disk I/O.
+#[derive(Debug)]
+pub struct Aof {
+    tx: mpsc::UnboundedSender<AofMessage>,
+    /// Writes hold this shared from running until they are logged, and a rewrite
+    /// holds it exclusively while it snapshots. Every write then lands either in the
+    /// snapshot or after it in the new file, never in both or neither.
+    order: RwLock<()>,
+}
+
+/// What a write command leaves in the AOF once it has run. Replay happens later, so
+/// relative TTLs are pinned to Unix milliseconds up front.
+#[derive(Debug)]
+pub enum AofEntry {
+    Bytes(Vec<u8>),
+    /// Reads that consume what they return, logged only when they returned something.
+    UnlessNil(Vec<u8>),
+    /// BLPOP is logged as the LPOP it turned into, read from its reply.
+    PopFromReply,
+}
+
+impl AofEntry {
+    pub fn for_command(command: &RespCommand, bytes: &[u8]) -> Self {
+        let parts = match command {
+            RespCommand::Set {
+                key,
+                value,
+                px: Some(px),
+            } => vec![
+                b"SET".to_vec(),
+                key.as_bytes().to_vec(),
+                value.clone(),
+                b"PXAT".to_vec(),
+                (unix_now_ms() as u64).saturating_add(*px).to_string().into_bytes(),
+            ],
+            RespCommand::ExpireAt {
+                key,
+                unix_ms,
+                condition,
+            } => {
+                let mut parts = vec![
+                    b"PEXPIREAT".to_vec(),
+                    key.as_bytes().to_vec(),
+                    unix_ms.to_string().into_bytes(),
+                ];
+                parts.extend(condition_flags(condition));
+                parts
+            }
+            RespCommand::GetEx {
+                key,
+                expiry: Some(FieldExpiry::At(unix_ms)),
+            } => vec![
+                b"GETEX".to_vec(),
+                key.as_bytes().to_vec(),
+                b"PXAT".to_vec(),
+                unix_ms.to_string().into_bytes(),
+            ],
+            RespCommand::HgetEx {
+                key,
+                expiry: Some(FieldExpiry::At(unix_ms)),
+                fields,
+            } => {
+                let mut parts = vec![
+                    b"HGETEX".to_vec(),
+                    key.as_bytes().to_vec(),
+                    b"PXAT".to_vec(),
+                    unix_ms.to_string().into_bytes(),
+                ];
+                parts.extend(fields_block(fields));
+                parts
+            }
+            RespCommand::HExpire {
+                key,
+                unix_ms,
+                condition,
+                fields,
+            } => {
+                let mut parts = vec![
+                    b"HPEXPIREAT".to_vec(),
+                    key.as_bytes().to_vec(),
+                    unix_ms.to_string().into_bytes(),
+                ];
+                parts.extend(condition_flags(condition));
+                parts.extend(fields_block(fields));
+                parts
+            }
+            // Replay must not wait, so BLOCK is left out.
+            RespCommand::XReadGroup {
+                group,
+                consumer,
+                count,
+                noack,
+                keys,
+                ids,
+                ..
+            } => {
+                let mut parts = vec![
+                    b"XREADGROUP".to_vec(),
+                    b"GROUP".to_vec(),
+                    group.as_bytes().to_vec(),
+                    consumer.as_bytes().to_vec(),
+                ];
+                if let Some(count) = count {
+                    parts.push(b"COUNT".to_vec());
+                    parts.push(count.to_string().into_bytes());
+                }
+                if *noack {
+                    parts.push(b"NOACK".to_vec());
+                }
+                parts.push(b"STREAMS".to_vec());
+                parts.extend(keys.iter().chain(ids).map(|arg| arg.as_bytes().to_vec()));
+                return AofEntry::UnlessNil(encode_command(parts));
+            }
+            RespCommand::BLPop(..) => return AofEntry::PopFromReply,
+            _ => return AofEntry::Bytes(bytes.to_vec()),
+        };
+        AofEntry::Bytes(encode_command(parts))
+    }
+}
+
+fn condition_flags(condition: &ExpireCondition) -> Vec<Vec<u8>> {
+    [
+        (condition.nx, "NX"),
+        (condition.xx, "XX"),
+        (condition.gt, "GT"),
+        (condition.lt, "LT"),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, flag)| flag.as_bytes().to_vec())
+    .collect()
+}
+
+fn fields_block(fields: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut parts = vec![b"FIELDS".to_vec(), fields.len().to_string().into_bytes()];
+    parts.extend(fields.iter().cloned());
+    parts
+}
+
+fn encode_command(parts: Vec<Vec<u8>>) -> Vec<u8> {
+    let command = RespValue::Array(
+        parts
+            .into_iter()
+            .map(|part| RespValue::BulkString(Some(part)))
+            .collect(),
+    );
+    let mut buffer = BytesMut::new();
+    // Encoding an array of bulk strings into memory can't fail.
+    let _ = RespCodec.encode(command, &mut buffer);
+    buffer.to_vec()
+}
+
+impl std::fmt::Debug for AofMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AofMessage::Append(bytes) => write!(f, "Append({} bytes)", bytes.len()),
+            AofMessage::Rewrite(bytes) => write!(f, "Rewrite({} bytes)", bytes.len()),
+        }
+    }
+}
+
+impl Aof {
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let file = open_for_append(path).await?;
+        let (tx, mut rx) = mpsc::unbounded_channel::<AofMessage>();
+        let path = path.to_path_buf();
+
+        tokio::spawn(async move {
+            let mut writer = BufWriter::new(file);
+            while let Some(message) = rx.recv().await {
+                handle_message(&path, &mut writer, message).await;
+                // Batch whatever else is already queued before paying for a flush.
+                while let Ok(message) = rx.try_recv() {
+                    handle_message(&path, &mut writer, message).await;
+                }
+                if let Err(e) = writer.flush().await {
+                    log::warning!("AOF flush failed: {e}");
+                }
+            }
+        });
+
+        Ok(Self {
+            tx,
+            order: RwLock::new(()),
+        })
+    }
+
+    pub fn append(&self, bytes: Vec<u8>) {
+        // The writer task only stops when the server shuts down.
+        let _ = self.tx.send(AofMessage::Append(bytes));
+    }
+
+    /// Held by a write from before it runs until `record`, so a rewrite can't snapshot
+    /// in between.
+    pub async fn order_write(&self) -> RwLockReadGuard<'_, ()> {
+        self.order.read().await
+    }
+
+    /// Logs a write once it has run. Rejected commands change nothing, so they are
+    /// left out.
+    pub fn record(&self, entry: AofEntry, reply: &Option<RespValue>) {
+        if let Some(RespValue::Error(_)) = reply {
+            return;
+        }
+        match entry {
+            AofEntry::Bytes(bytes) => self.append(bytes),
+            AofEntry::UnlessNil(bytes) => {
+                if !matches!(
+                    reply,
+                    None | Some(RespValue::NullArray | RespValue::BulkString(None))
+                ) {
+                    self.append(bytes);
+                }
+            }
+            AofEntry::PopFromReply => {
+                if let Some(RespValue::Array(items)) = reply {
+                    if let Some(RespValue::BulkString(Some(key))) = items.first() {
+                        self.append(encode_command(vec![b"LPOP".to_vec(), key.clone()]));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the file with a compacted dataset. The swap happens on the writer task,
+    /// so appends queued before the rewrite land in the old file and later ones in the new.
+    pub async fn rewrite(&self, store: &Store) -> io::Result<()> {
+        // No write may run between the snapshot and queueing it.
+        let _order = self.order.write().await;
+        let commands = store.rewrite_commands().await;
+        let mut buffer = BytesMut::new();
+        let mut codec = RespCodec;
+        for command in commands {
+            codec.encode(command, &mut buffer)?;
+        }
+        let _ = self.tx.send(AofMessage::Rewrite(buffer.to_vec()));
+        Ok(())
+    }
+}
+
+async fn open_for_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+}
+
+async fn handle_message(path: &PathBuf, writer: &mut BufWriter<File>, message: AofMessage) {
+    match message {
+        AofMessage::Append(bytes) => {
+            if let Err(e) = writer.write_all(&bytes).await {
+                log::warning!("AOF write failed: {e}");
+            }
+        }
+        AofMessage::Rewrite(bytes) => match rewrite_file(path, writer, &bytes).await {
+            Ok(()) => match reopen(path).await {
+                Ok(file) => *writer = BufWriter::new(file),
+                Err(e) => {
+                    // The old handle points at the unlinked file, so every later write
+                    // would be lost.
+                    log::warning!("Can't reopen the AOF after a rewrite, exiting: {e}");
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => log::warning!("AOF rewrite failed: {e}"),
+        },
+    }
+}
+
+/// Swaps the compacted file into place. On error the old file is untouched and the
+/// writer can keep appending to it.
+async fn rewrite_file(path: &PathBuf, writer: &mut BufWriter<File>, bytes: &[u8]) -> io::Result<()> {
+    writer.flush().await?;
+    let temp_path = path.with_extension("aof.rewrite");
+    let mut temp = File::create(&temp_path).await?;
+    temp.write_all(bytes).await?;
+    temp.sync_all().await?;
+    tokio::fs::rename(&temp_path, path).await
+}
+
+/// Opens the freshly renamed file, retrying briefly in case the failure is transient.
+async fn reopen(path: &Path) -> io::Result<File> {
+    let mut attempts = 0;
+    loop {
+        match open_for_append(path).await {
+            Err(e) if attempts < 3 => {
+                attempts += 1;
+                log::warning!("Reopening the AOF failed, retrying: {e}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Rebuilds the keyspace by running every command recorded in the AOF.
+/// Returns false when there is no file to replay.
+pub async fn replay(
+    rdb: &Arc<RdbConfig>,
+    store: &Arc<Store>,
+    info: &Arc<ServerInfo>,
+) -> anyhow::Result<bool> {
+    let path = rdb.aof_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let raw = tokio::fs::read(&path).await?;
+    let mut buffer = BytesMut::from(raw.as_slice());
+    let context = ServerContext::new(
+        store.clone(),
+        rdb.clone(),
+        Arc::new(Mutex::new(ReplicationManager::new(info.clone()))),
+        info.clone(),
+        None,
+        Arc::new(Acl::new(rdb.requirepass.as_deref())),
+        Arc::new(ScriptCache::new()),
+        Arc::new(LatencyMonitor::new(0)),
+    );
+
+    let mut codec = RespCodec;
+    while let Some((resp_value, bytes)) = codec.decode(&mut buffer)? {
+        let command = command::Command::try_from_resp(resp_value)?;
+        replay_command(&context, command, bytes)
+            .await
+            .map_err(|e| anyhow::anyhow!("AOF replay failed: {e}"))?;
+    }
+    if !buffer.is_empty() {
+        log::warning!("AOF ends with a truncated command, ignoring {} bytes", buffer.len());
+    }
+
+    // Replayed writes are not part of the replication stream.
+    store.clear_log().await;
+    info.set_repl_offset(0);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `args` the way a client's request would arrive, keeping the raw bytes.
+    fn request(args: &[&str]) -> (RespCommand, Vec<u8>) {
+        let value = || {
+            RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(Some(arg.as_bytes().to_vec())))
+                    .collect(),
+            )
+        };
+        let mut bytes = BytesMut::new();
+        RespCodec.encode(value(), &mut bytes).unwrap();
+        (command::Command::try_from_resp(value()).unwrap(), bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn writes_survive_a_restart_via_replay() {
+        let dir = std::env::temp_dir().join(format!("aof-replay-{}", std::process::id()));
+        let rdb = Arc::new(RdbConfig {
+            dir: dir.to_string_lossy().into_owned(),
+            ..RdbConfig::new()
+        });
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+
+        let aof = Aof::open(&rdb.aof_path()).await.unwrap();
+        for args in [
+            &["SET", "plain", "v"][..],
+            &["SET", "ttl", "v", "PX", "100000"],
+            &["RPUSH", "list", "a", "b"],
+            &["SET", "rejected", "v"],
+        ] {
+            let (command, bytes) = request(args);
+            let reply = match args[1] {
+                "rejected" => Some(RespValue::Error("ERR nope".into())),
+                _ => Some(RespValue::SimpleString("OK".into())),
+            };
+            aof.record(AofEntry::for_command(&command, &bytes), &reply);
+        }
+        // The writer task flushes in the background.
+        let logged = loop {
+            let logged = tokio::fs::read(rdb.aof_path()).await.unwrap();
+            if logged.windows(4).any(|w| w == b"list") {
+                break logged;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert!(logged.windows(4).any(|w| w == b"PXAT"));
+
+        let store = Arc::new(Store::new());
+        let info = Arc::new(ServerInfo::new().unwrap());
+        assert!(replay(&rdb, &store, &info).await.unwrap());
+
+        assert!(matches!(store.get("plain").await.unwrap(), RespValue::BulkString(Some(v)) if v == b"v"));
+        let ttl = store.pttl("ttl").await;
+        assert!(ttl > 90_000 && ttl <= 100_000, "ttl was {ttl}");
+        assert_eq!(store.llen("list".into()).await.unwrap(), 2);
+        assert_eq!(store.pttl("rejected").await, -2);
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
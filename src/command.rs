@@ -1,11 +1,100 @@
 use futures::io;
+use std::time::Duration;
 
+use tokio::time::Instant;
+
+use crate::command_table;
 use crate::resp::RespValue;
+use crate::shared_store::keyspace::unix_now_ms;
+use crate::shared_store::stream_id::StreamID;
+
+#[derive(Debug, Clone)]
+pub enum AclCommand {
+    WhoAmI,
+    List,
+    GetUser(String),
+    SetUser(String, Vec<String>),
+}
 
 #[derive(Debug, Clone)]
 pub enum ConfigCommand {
     Get(String),
-    _Set(String, String),
+    Set(String, String),
+}
+#[derive(Debug, Clone)]
+pub enum FunctionCommand {
+    List,
+    Dump,
+    Stats,
+}
+#[derive(Debug, Clone)]
+pub enum LatencyCommand {
+    Latest,
+    History(String),
+    Reset(Vec<String>),
+}
+#[derive(Debug, Clone)]
+pub enum ScriptCommand {
+    Load(String),
+    Exists(Vec<String>),
+}
+#[derive(Debug, Clone)]
+pub enum CommandCommand {
+    GetKeys(Vec<String>),
+    /// Command names to describe; empty describes every command.
+    Info(Vec<String>),
+}
+#[derive(Debug, Clone)]
+pub enum ObjectCommand {
+    Encoding(String),
+}
+#[derive(Debug, Clone)]
+pub enum MemoryCommand {
+    /// Key and how many elements of a collection to sample; 0 sizes every element.
+    Usage(String, usize),
+    Doctor,
+}
+#[derive(Debug, Clone)]
+pub enum XGroupCommand {
+    Create {
+        key: String,
+        group: String,
+        id: String,
+        mkstream: bool,
+    },
+}
+/// The extended XPENDING form: `[IDLE ms] start end count [consumer]`.
+#[derive(Debug, Clone)]
+pub struct PendingRange {
+    pub min_idle_ms: Option<u64>,
+    pub start: Option<StreamID>,
+    pub end: Option<StreamID>,
+    pub count: usize,
+    pub consumer: Option<String>,
+}
+#[derive(Debug, Clone, Default)]
+pub struct ClaimOptions {
+    pub idle_ms: Option<u64>,
+    pub time_ms: Option<i64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub justid: bool,
+    pub last_id: Option<StreamID>,
+}
+#[derive(Debug, Clone)]
+pub enum PubSubCommand {
+    Channels(Option<String>),
+}
+#[derive(Debug, Clone)]
+pub enum DebugCommand {
+    ChangeReplId,
+    QuicklistPackedThreshold(usize),
+    StringMatchLen(String, String),
+    SetActiveExpire(bool),
+    Object(String),
+    Sleep(Duration),
+    /// Replies with the message as an error, verbatim.
+    Error(String),
 }
 #[derive(Debug, Clone)]
 pub enum ReplconfCommand {
@@ -15,6 +104,71 @@ pub enum ReplconfCommand {
     Getack(String),
     Ack(String),
 }
+#[derive(Debug, Clone, Default)]
+pub struct ZaddOptions {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+    pub ch: bool,
+    pub incr: bool,
+}
+
+/// The NX/XX/GT/LT guards shared by the EXPIRE family.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExpireCondition {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+}
+
+impl ExpireCondition {
+    /// Whether moving an expiry from `current` to `deadline` passes the guards. `None`
+    /// means no TTL for `current` and an already-passed deadline for `deadline`.
+    pub fn allows(&self, current: Option<Instant>, deadline: Option<Instant>) -> bool {
+        match current {
+            // A key without a TTL counts as never expiring for GT/LT.
+            None => !self.xx && !self.gt,
+            Some(current) => {
+                !self.nx
+                    && (!self.gt || deadline.is_some_and(|deadline| deadline > current))
+                    && (!self.lt || deadline.is_none_or(|deadline| deadline < current))
+            }
+        }
+    }
+}
+
+/// What HGETEX does to the TTL of the fields it returns.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldExpiry {
+    At(i64),
+    Persist,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LcsOptions {
+    pub len: bool,
+    pub idx: bool,
+    pub min_match_len: usize,
+    pub with_match_len: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub alpha: bool,
+    pub desc: bool,
+    pub limit: Option<(i64, i64)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LexBound {
+    NegativeInfinity,
+    PositiveInfinity,
+    Inclusive(String),
+    Exclusive(String),
+}
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 enum PushDirection {
@@ -27,18 +181,119 @@ enum PushDirection {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum RespCommand {
+    Acl(AclCommand),
+    Auth {
+        username: Option<String>,
+        password: String,
+    },
+    Append {
+        key: String,
+        value: Vec<u8>,
+    },
+    BgRewriteAof,
+    Command(CommandCommand),
     ConfigCommand(ConfigCommand),
+    Function(FunctionCommand),
+    FCall,
+    Copy {
+        source: String,
+        destination: String,
+        db: Option<i64>,
+        replace: bool,
+    },
+    Debug(DebugCommand),
+    Del(Vec<String>),
     Echo(String),
+    Eval {
+        script: String,
+        keys: Vec<String>,
+        args: Vec<String>,
+    },
+    EvalSha {
+        sha: String,
+        keys: Vec<String>,
+        args: Vec<String>,
+    },
+    Exists(Vec<String>),
+    ExpireAt {
+        key: String,
+        unix_ms: i64,
+        condition: ExpireCondition,
+    },
+    FlushAll,
     Get(String),
+    GetDel(String),
+    GetEx {
+        key: String,
+        expiry: Option<FieldExpiry>,
+    },
+    Help(String),
+    Hset {
+        key: String,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    Hello {
+        protover: Option<i64>,
+        auth: Option<(String, String)>,
+    },
+    HgetDel {
+        key: String,
+        fields: Vec<Vec<u8>>,
+    },
+    HgetEx {
+        key: String,
+        expiry: Option<FieldExpiry>,
+        fields: Vec<Vec<u8>>,
+    },
+    HExpire {
+        key: String,
+        unix_ms: i64,
+        condition: ExpireCondition,
+        fields: Vec<Vec<u8>>,
+    },
+    HTtl {
+        key: String,
+        unit_ms: u128,
+        fields: Vec<Vec<u8>>,
+    },
+    HsetNx {
+        key: String,
+        field: Vec<u8>,
+        value: Vec<u8>,
+    },
+    HrandField {
+        key: String,
+        count: Option<i64>,
+        with_values: bool,
+    },
+    HGetAll(String),
+    HKeys(String),
+    HVals(String),
     Incr(String),
     Info(String),
     Keys(String),
+    Latency(LatencyCommand),
+    Lcs {
+        key1: String,
+        key2: String,
+        options: LcsOptions,
+    },
+    Lolwut(Option<i64>),
+    Move(String, i64),
+    Persist(String),
+    Rename(String, String),
     Multi,
+    Object(ObjectCommand),
+    Memory(MemoryCommand),
+    PubSub(PubSubCommand),
+    Script(ScriptCommand),
+    Sort(String, SortOptions),
     Exec,
     Discard,
     Ping,
-    Publish(String, String),
+    Publish(Vec<u8>, Vec<u8>),
     PSYNC(String, i64),
     #[allow(dead_code)]
     RDB(Option<Vec<u8>>),
@@ -48,7 +303,32 @@ pub enum RespCommand {
         value: Vec<u8>,
         px: Option<u64>,
     },
-    Subscribe(String),
+    SetRange {
+        key: String,
+        offset: usize,
+        value: Vec<u8>,
+    },
+    Sadd {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    SRandMember {
+        key: String,
+        count: Option<i64>,
+    },
+    SmIsMember {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    SinterCard {
+        keys: Vec<String>,
+        limit: usize,
+    },
+    Subscribe(Vec<Vec<u8>>),
+    Ttl {
+        key: String,
+        unit_ms: i64,
+    },
     Type(String),
     Wait(String, String),
     Xadd {
@@ -61,13 +341,40 @@ pub enum RespCommand {
         start: Option<String>,
         end: Option<String>,
     },
-    #[allow(dead_code)]
     Xread {
         count: Option<u64>,
         block: Option<u64>,
         keys: Vec<String>,
         ids: Vec<String>,
     },
+    XGroup(XGroupCommand),
+    XReadGroup {
+        group: String,
+        consumer: String,
+        count: Option<usize>,
+        block: Option<u64>,
+        noack: bool,
+        keys: Vec<String>,
+        ids: Vec<String>,
+    },
+    XAck {
+        key: String,
+        group: String,
+        ids: Vec<StreamID>,
+    },
+    XPending {
+        key: String,
+        group: String,
+        range: Option<PendingRange>,
+    },
+    XClaim {
+        key: String,
+        group: String,
+        consumer: String,
+        min_idle_ms: u64,
+        ids: Vec<StreamID>,
+        options: ClaimOptions,
+    },
     Rpush {
         key: String,
         values: Vec<Vec<u8>>,
@@ -85,11 +392,14 @@ pub enum RespCommand {
         end: isize,
     },
 
-    Unsubscribe(String),
-    #[allow(dead_code)]
-    PSubscribe,
-    #[allow(dead_code)]
-    PunSubscribe,
+    Unsubscribe(Vec<u8>),
+    PSubscribe(Vec<Vec<u8>>),
+    PunSubscribe(Vec<u8>),
+    SSubscribe(Vec<Vec<u8>>),
+    /// Shard channels to leave; empty leaves them all.
+    SUnsubscribe(Vec<Vec<u8>>),
+    SPublish(Vec<u8>, Vec<u8>),
+    Reset,
     #[allow(dead_code)]
     Quit,
 
@@ -99,10 +409,15 @@ pub enum RespCommand {
         lat: f64,
         member: String,
     },
-    Zadd(String, f64, String),
+    Zadd {
+        key: String,
+        options: ZaddOptions,
+        members: Vec<(f64, String)>,
+    },
     Zcard(String),
     Zrange(String, i64, i64),
     Zrank(String, String),
+    ZrangeByLex(String, LexBound, LexBound),
     ZScore(String, String),
     ZRem(String, String),
 }
@@ -115,6 +430,13 @@ impl fmt::Display for RespCommand {
             RespCommand::Ping => write!(f, "PING"),
             RespCommand::Echo(_) => write!(f, "ECHO"),
             RespCommand::Subscribe(_) => write!(f, "SUBSCRIBE"),
+            RespCommand::Unsubscribe(_) => write!(f, "UNSUBSCRIBE"),
+            RespCommand::PSubscribe(_) => write!(f, "PSUBSCRIBE"),
+            RespCommand::PunSubscribe(_) => write!(f, "PUNSUBSCRIBE"),
+            RespCommand::SSubscribe(_) => write!(f, "SSUBSCRIBE"),
+            RespCommand::SUnsubscribe(_) => write!(f, "SUNSUBSCRIBE"),
+            RespCommand::SPublish(..) => write!(f, "SPUBLISH"),
+            RespCommand::Reset => write!(f, "RESET"),
             RespCommand::Set { .. } => write!(f, "SET"),
             RespCommand::Get { .. } => write!(f, "get"),
             // …add others as needed…
@@ -124,6 +446,43 @@ impl fmt::Display for RespCommand {
 }
 
 impl RespCommand {
+    /// Commands that mutate the keyspace and therefore belong in the append-only file.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            RespCommand::Append { .. }
+                | RespCommand::Copy { .. }
+                | RespCommand::Del(_)
+                | RespCommand::ExpireAt { .. }
+                | RespCommand::Persist(_)
+                | RespCommand::FlushAll
+                | RespCommand::GetDel(_)
+                | RespCommand::GetEx { .. }
+                | RespCommand::Hset { .. }
+                | RespCommand::HgetDel { .. }
+                | RespCommand::HgetEx { .. }
+                | RespCommand::HExpire { .. }
+                | RespCommand::HsetNx { .. }
+                | RespCommand::Incr(_)
+                | RespCommand::Set { .. }
+                | RespCommand::SetRange { .. }
+                | RespCommand::Sadd { .. }
+                | RespCommand::Xadd { .. }
+                | RespCommand::XGroup(_)
+                | RespCommand::XReadGroup { .. }
+                | RespCommand::XAck { .. }
+                | RespCommand::XClaim { .. }
+                | RespCommand::Rename(_, _)
+                | RespCommand::Rpush { .. }
+                | RespCommand::Lpop(_, _)
+                | RespCommand::BLPop(_, _)
+                | RespCommand::Lpush { .. }
+                | RespCommand::Geoadd { .. }
+                | RespCommand::Zadd { .. }
+                | RespCommand::ZRem(_, _)
+        )
+    }
+
     pub fn _to_resp(self) -> RespValue {
         match self {
             RespCommand::Ping => RespValue::SimpleString("PONG".into()),
@@ -136,8 +495,28 @@ impl RespCommand {
 pub struct Command {
     name: String,
     args: Vec<String>,
+    /// The arguments exactly as sent, for the commands that are binary-safe.
+    raw_args: Vec<Vec<u8>>,
+    /// Whether some argument was not valid UTF-8 and `args` holds a lossy copy of it.
+    lossy: bool,
 }
 
+/// Commands that read `raw_args`, so non-UTF-8 arguments are fine for them.
+const BINARY_SAFE: [&str; 12] = [
+    "publish",
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "spublish",
+    "ssubscribe",
+    "sunsubscribe",
+    "hset",
+    "hsetnx",
+    "append",
+    "setrange",
+];
+
 impl Command {
     pub fn new(input: Vec<RespValue>) -> Result<Self, io::Error> {
         if let Some(resp_value) = input.first() {
@@ -147,16 +526,25 @@ impl Command {
                 _ => invalid_data("Unexpected RespValue")?,
             };
             let mut args = Vec::with_capacity(input.len());
+            let mut raw_args = Vec::with_capacity(input.len());
+            let mut lossy = false;
             for arg in input.iter().skip(1) {
-                let s = match arg {
-                    RespValue::BulkString(s) => convert_bulk_string(s.to_owned())?,
-                    RespValue::SimpleString(s) => s.clone(),
-
+                let raw = match arg {
+                    RespValue::BulkString(Some(bytes)) => bytes.clone(),
+                    RespValue::SimpleString(s) => s.clone().into_bytes(),
                     _ => invalid_data("Unexpected RespValue")?,
                 };
+                let s = String::from_utf8_lossy(&raw).into_owned();
+                lossy |= s.as_bytes() != raw.as_slice();
                 args.push(s);
+                raw_args.push(raw);
             }
-            Ok(Self { name, args })
+            Ok(Self {
+                name,
+                args,
+                raw_args,
+                lossy,
+            })
         } else {
             invalid_data("Unexpected RespValue")?
         }
@@ -167,25 +555,133 @@ impl Command {
             RespValue::RDB(info) => Ok(RespCommand::RDB(info)),
             RespValue::Array(a) => {
                 let command = Command::new(a)?;
-                match command.name.to_ascii_lowercase().as_str() {
-                    "subscribe" => Ok(RespCommand::Subscribe(command.args[0].clone())),
+                let name = command.name.to_ascii_lowercase();
+                if command.lossy && !BINARY_SAFE.contains(&name.as_str()) {
+                    return invalid_data("Invalid UTF-8");
+                }
+                // The parsers below may index the arguments their arity guarantees.
+                if let Some(spec) = command_table::lookup(&name) {
+                    if !spec.arity_matches(command.args.len() + 1) {
+                        return invalid_data(format!(
+                            "ERR wrong number of arguments for '{name}' command"
+                        ));
+                    }
+                }
+                match name.as_str() {
+                    "subscribe" if command.args.is_empty() => {
+                        invalid_data("ERR wrong number of arguments for 'subscribe' command")
+                    }
+                    "subscribe" => Ok(RespCommand::Subscribe(command.raw_args)),
                     "multi" => Ok(RespCommand::Multi),
                     "discard" => Ok(RespCommand::Discard),
                     "exec" => Ok(RespCommand::Exec),
                     "ping" => Ok(RespCommand::Ping),
+                    "quit" => Ok(RespCommand::Quit),
                     "publish" => Ok(RespCommand::Publish(
-                        command.args[0].clone(),
-                        command.args[1].clone(),
+                        command.raw_args[0].clone(),
+                        command.raw_args[1].clone(),
                     )),
 
                     "echo" => Ok(RespCommand::Echo(command.args[0].clone())),
+                    "lolwut" => parse_lolwut(command),
+                    "lcs" => parse_lcs(command),
+                    "copy" => parse_copy(command),
+                    "move" => parse_move(command),
+                    "del" if command.args.is_empty() => {
+                        invalid_data("ERR wrong number of arguments for 'del' command")
+                    }
+                    "del" => Ok(RespCommand::Del(command.args)),
+                    "exists" => Ok(RespCommand::Exists(command.args)),
+                    "rename" => match command.args.as_slice() {
+                        [key, newkey] => Ok(RespCommand::Rename(key.clone(), newkey.clone())),
+                        _ => invalid_data("ERR wrong number of arguments for 'rename' command"),
+                    },
+                    "sort" => parse_sort(command),
+                    "expire" => parse_expireat(command, 1000, false),
+                    "pexpire" => parse_expireat(command, 1, false),
+                    "expireat" => parse_expireat(command, 1000, true),
+                    "pexpireat" => parse_expireat(command, 1, true),
+                    "persist" => Ok(RespCommand::Persist(command.args[0].clone())),
                     "get" => Ok(RespCommand::Get(command.args[0].clone())),
+                    "getdel" => match command.args.as_slice() {
+                        [key] => Ok(RespCommand::GetDel(key.clone())),
+                        _ => invalid_data("ERR wrong number of arguments for 'getdel' command"),
+                    },
+                    "getex" => parse_getex(command),
+                    "ttl" | "pttl" => match command.args.as_slice() {
+                        [key] => Ok(RespCommand::Ttl {
+                            key: key.clone(),
+                            unit_ms: if command.name.eq_ignore_ascii_case("ttl") { 1000 } else { 1 },
+                        }),
+                        _ => invalid_data(format!(
+                            "ERR wrong number of arguments for '{}' command",
+                            command.name.to_ascii_lowercase()
+                        )),
+                    },
+                    "flushall" => parse_flushall(command),
+                    "bgrewriteaof" => Ok(RespCommand::BgRewriteAof),
+                    "object" => parse_object(command),
+                    "memory" => parse_memory(command),
+                    "pubsub" => parse_pubsub(command),
+                    "client" | "xinfo" | "slowlog" => {
+                        parse_help_only(command)
+                    }
                     "set" => parse_set(command),
+                    "hset" => parse_hset(command),
+                    "hsetnx" => parse_hsetnx(command),
+                    "append" => match command.raw_args.as_slice() {
+                        [key, value] => Ok(RespCommand::Append {
+                            key: String::from_utf8_lossy(key).into_owned(),
+                            value: value.clone(),
+                        }),
+                        _ => invalid_data("ERR wrong number of arguments for 'append' command"),
+                    },
+                    "setrange" => parse_setrange(command),
+                    "hgetdel" => parse_hgetdel(command),
+                    "auth" => parse_auth(command),
+                    "acl" => parse_acl(command),
+                    "hello" => parse_hello(command),
+                    "hgetex" => parse_hgetex(command),
+                    "hexpire" => parse_hexpire(command, 1000, false),
+                    "hpexpire" => parse_hexpire(command, 1, false),
+                    "hexpireat" => parse_hexpire(command, 1000, true),
+                    "hpexpireat" => parse_hexpire(command, 1, true),
+                    "httl" => parse_httl(command, 1000),
+                    "hpttl" => parse_httl(command, 1),
+                    "hrandfield" => parse_hrandfield(command),
+                    "hgetall" | "hkeys" | "hvals" => match command.args.as_slice() {
+                        [key] => Ok(match name.as_str() {
+                            "hgetall" => RespCommand::HGetAll(key.clone()),
+                            "hkeys" => RespCommand::HKeys(key.clone()),
+                            _ => RespCommand::HVals(key.clone()),
+                        }),
+                        _ => invalid_data(format!(
+                            "ERR wrong number of arguments for '{name}' command"
+                        )),
+                    },
+                    "sadd" => parse_sadd(command),
+                    "sintercard" => parse_sintercard(command),
+                    "smismember" => parse_smismember(command),
+                    "srandmember" => parse_srandmember(command),
                     "type" => Ok(RespCommand::Type(command.args[0].clone())),
                     "config" => parse_config(command),
+                    "function" => parse_function(command),
+                    "eval" | "eval_ro" => parse_eval(command, false),
+                    "evalsha" | "evalsha_ro" => parse_eval(command, true),
+                    "script" => parse_script(command),
+                    "latency" => parse_latency(command),
+                    "fcall" | "fcall_ro" => match command.args.first() {
+                        Some(_) if command.args.len() >= 2 => Ok(RespCommand::FCall),
+                        _ => invalid_data(format!(
+                            "ERR wrong number of arguments for '{}' command",
+                            command.name.to_ascii_lowercase()
+                        )),
+                    },
+                    "command" => parse_command(command),
+                    "debug" => parse_debug(command),
                     "keys" => Ok(RespCommand::Keys(command.args[0].clone())),
                     "incr" => Ok(RespCommand::Incr(command.args[0].clone())),
-                    "info" => Ok(RespCommand::Info(command.args[0].clone())),
+                    "info" => Ok(RespCommand::Info(command.args.first().cloned().unwrap_or_default())),
                     "replconf" => parse_replconf(command),
                     "llen" => Ok(RespCommand::Llen(command.args[0].clone())),
                     "lpop" => parse_pop_command(command),
@@ -203,11 +699,35 @@ impl Command {
                     "xadd" => parse_xadd(command),
                     "xrange" => parse_xrange(command),
                     "xread" => parse_xread(command),
-                    "unsubscribe" => Ok(RespCommand::Unsubscribe(command.args[0].clone())),
+                    "xgroup" => parse_xgroup(command),
+                    "xreadgroup" => parse_xreadgroup(command),
+                    "xack" => parse_xack(command),
+                    "xpending" => parse_xpending(command),
+                    "xclaim" => parse_xclaim(command),
+                    "unsubscribe" => match command.raw_args.first() {
+                        Some(channel) => Ok(RespCommand::Unsubscribe(channel.clone())),
+                        None => invalid_data("ERR wrong number of arguments for 'unsubscribe' command"),
+                    },
+                    "psubscribe" if command.args.is_empty() => {
+                        invalid_data("ERR wrong number of arguments for 'psubscribe' command")
+                    }
+                    "psubscribe" => Ok(RespCommand::PSubscribe(command.raw_args)),
+                    "ssubscribe" => Ok(RespCommand::SSubscribe(command.raw_args)),
+                    "sunsubscribe" => Ok(RespCommand::SUnsubscribe(command.raw_args)),
+                    "spublish" => Ok(RespCommand::SPublish(
+                        command.raw_args[0].clone(),
+                        command.raw_args[1].clone(),
+                    )),
+                    "reset" => Ok(RespCommand::Reset),
+                    "punsubscribe" => match command.raw_args.first() {
+                        Some(pattern) => Ok(RespCommand::PunSubscribe(pattern.clone())),
+                        None => invalid_data("ERR wrong number of arguments for 'punsubscribe' command"),
+                    },
                     "zadd" => parse_zadd(command),
                     "zcard" => Ok(RespCommand::Zcard(command.args[0].clone())),
                     "zrange" => parse_zrange(command),
                     "zrank" => parse_zrank(command),
+                    "zrangebylex" => parse_zrangebylex(command),
                     "zscore" => Ok(RespCommand::ZScore(
                         command.args[0].clone(),
                         command.args[1].clone(),
@@ -215,7 +735,14 @@ impl Command {
 
                     "zrem" => parse_zrem(command),
 
-                    other => invalid_data(format!("Unexpected Command: {other}")),
+                    _ => {
+                        let args: String =
+                            command.args.iter().map(|arg| format!("'{arg}' ")).collect();
+                        invalid_data(format!(
+                            "ERR unknown command '{}', with args beginning with: {args}",
+                            command.name
+                        ))
+                    }
                 }
             }
             _ => Err(io::Error::new(
@@ -238,14 +765,49 @@ fn parse_pop_command(command: Command) -> io::Result<RespCommand> {
 }
 
 fn parse_zadd(command: Command) -> io::Result<RespCommand> {
-    if command.args.len() != 3 {
+    let Some(key) = command.args.first().cloned() else {
         return Err(invalid_data_err("Unable to parse args"));
+    };
+    let mut options = ZaddOptions::default();
+    let mut rest = &command.args[1..];
+    while let Some(flag) = rest.first() {
+        match flag.to_ascii_lowercase().as_str() {
+            "nx" => options.nx = true,
+            "xx" => options.xx = true,
+            "gt" => options.gt = true,
+            "lt" => options.lt = true,
+            "ch" => options.ch = true,
+            "incr" => options.incr = true,
+            _ => break,
+        }
+        rest = &rest[1..];
     }
-    let key = command.args[0].clone();
-    let rank = command.args[1]
-        .parse::<f64>()
-        .map_err(|_| invalid_data_err("Unable to parse param"))?;
-    Ok(RespCommand::Zadd(key, rank, command.args[2].clone()))
+
+    if options.nx && options.xx {
+        return invalid_data("ERR XX and NX options at the same time are not compatible");
+    }
+    if [options.nx, options.gt, options.lt].iter().filter(|f| **f).count() > 1 {
+        return invalid_data("ERR GT, LT, and/or NX options at the same time are not compatible");
+    }
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return invalid_data("ERR syntax error");
+    }
+    if options.incr && rest.len() != 2 {
+        return invalid_data("ERR INCR option supports a single increment-element pair");
+    }
+
+    let mut members = Vec::with_capacity(rest.len() / 2);
+    for pair in rest.chunks(2) {
+        let score = pair[0]
+            .parse::<f64>()
+            .map_err(|_| invalid_data_err("ERR value is not a valid float"))?;
+        members.push((score, pair[1].clone()));
+    }
+    Ok(RespCommand::Zadd {
+        key,
+        options,
+        members,
+    })
 }
 
 fn parse_geoadd(command: Command) -> io::Result<RespCommand> {
@@ -294,6 +856,32 @@ fn parse_zrange(command: Command) -> io::Result<RespCommand> {
     Ok(RespCommand::Zrange(key, start, end))
 }
 
+fn parse_zrangebylex(command: Command) -> io::Result<RespCommand> {
+    if command.args.len() != 3 {
+        return Err(invalid_data_err("Unable to parse args"));
+    }
+    let key = command.args[0].clone();
+    let min = parse_lex_bound(&command.args[1])?;
+    let max = parse_lex_bound(&command.args[2])?;
+    Ok(RespCommand::ZrangeByLex(key, min, max))
+}
+
+fn parse_lex_bound(arg: &str) -> io::Result<LexBound> {
+    match arg {
+        "-" => Ok(LexBound::NegativeInfinity),
+        "+" => Ok(LexBound::PositiveInfinity),
+        _ => {
+            if let Some(member) = arg.strip_prefix('[') {
+                Ok(LexBound::Inclusive(member.to_string()))
+            } else if let Some(member) = arg.strip_prefix('(') {
+                Ok(LexBound::Exclusive(member.to_string()))
+            } else {
+                invalid_data("ERR min or max not valid string range item")
+            }
+        }
+    }
+}
+
 fn parse_blpop_command(mut command: Command) -> io::Result<RespCommand> {
     let timeout = match command.args.pop() {
         None => return invalid_data("No timeout given"),
@@ -388,13 +976,231 @@ fn parse_xread(command: Command) -> Result<RespCommand, io::Error> {
     })
 }
 
+fn parse_xgroup(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'xgroup' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("xgroup".into())),
+        "create" => {
+            let (key, group, id) = match &command.args[1..] {
+                [key, group, id] | [key, group, id, _] => (key, group, id),
+                _ => return invalid_data("ERR wrong number of arguments for 'xgroup|create' command"),
+            };
+            let mkstream = match command.args.get(4) {
+                Some(flag) if flag.eq_ignore_ascii_case("mkstream") => true,
+                Some(_) => return invalid_data("ERR syntax error"),
+                None => false,
+            };
+            Ok(RespCommand::XGroup(XGroupCommand::Create {
+                key: key.clone(),
+                group: group.clone(),
+                id: id.clone(),
+                mkstream,
+            }))
+        }
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try XGROUP HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_xreadgroup(command: Command) -> Result<RespCommand, io::Error> {
+    let mut args = command.args.iter();
+    let (Some(keyword), Some(group), Some(consumer)) = (args.next(), args.next(), args.next())
+    else {
+        return invalid_data("ERR wrong number of arguments for 'xreadgroup' command");
+    };
+    if !keyword.eq_ignore_ascii_case("group") {
+        return invalid_data("ERR syntax error");
+    }
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    loop {
+        let Some(arg) = args.next() else {
+            return invalid_data("ERR syntax error");
+        };
+        match arg.to_ascii_lowercase().as_str() {
+            "count" => {
+                count = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .ok_or_else(|| invalid_data_err("ERR value is not an integer or out of range"))?,
+                );
+            }
+            "block" => {
+                block = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                        .ok_or_else(|| invalid_data_err("ERR timeout is not an integer or out of range"))?,
+                );
+            }
+            "noack" => noack = true,
+            "streams" => break,
+            _ => return invalid_data("ERR syntax error"),
+        }
+    }
+    let rest: Vec<String> = args.cloned().collect();
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return invalid_data(
+            "ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified.",
+        );
+    }
+    let (keys, ids) = rest.split_at(rest.len() / 2);
+    Ok(RespCommand::XReadGroup {
+        group: group.clone(),
+        consumer: consumer.clone(),
+        count,
+        block,
+        noack,
+        keys: keys.to_vec(),
+        ids: ids.to_vec(),
+    })
+}
+
+fn parse_xack(command: Command) -> Result<RespCommand, io::Error> {
+    let [key, group, ids @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'xack' command");
+    };
+    if ids.is_empty() {
+        return invalid_data("ERR wrong number of arguments for 'xack' command");
+    }
+    let ids = ids
+        .iter()
+        .map(|id| StreamID::try_from(id.as_str()))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(|_| invalid_data_err("ERR Invalid stream ID specified as stream command argument"))?;
+    Ok(RespCommand::XAck {
+        key: key.clone(),
+        group: group.clone(),
+        ids,
+    })
+}
+
+fn parse_xpending(command: Command) -> Result<RespCommand, io::Error> {
+    let [key, group, rest @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'xpending' command");
+    };
+    if rest.is_empty() {
+        return Ok(RespCommand::XPending {
+            key: key.clone(),
+            group: group.clone(),
+            range: None,
+        });
+    }
+
+    let (min_idle_ms, rest) = match rest {
+        [idle, ms, rest @ ..] if idle.eq_ignore_ascii_case("idle") => {
+            let ms = ms
+                .parse::<u64>()
+                .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+            (Some(ms), rest)
+        }
+        _ => (None, rest),
+    };
+    let (start, end, count, consumer) = match rest {
+        [start, end, count] => (start, end, count, None),
+        [start, end, count, consumer] => (start, end, count, Some(consumer.clone())),
+        _ => return invalid_data("ERR syntax error"),
+    };
+    let bound = |id: &str, open: &str| -> io::Result<Option<StreamID>> {
+        if id == open {
+            return Ok(None);
+        }
+        StreamID::try_from(id).map(Some).map_err(|_| {
+            invalid_data_err("ERR Invalid stream ID specified as stream command argument")
+        })
+    };
+    let count = count
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?
+        .max(0) as usize;
+    Ok(RespCommand::XPending {
+        key: key.clone(),
+        group: group.clone(),
+        range: Some(PendingRange {
+            min_idle_ms,
+            start: bound(start, "-")?,
+            end: bound(end, "+")?,
+            count,
+            consumer,
+        }),
+    })
+}
+
+fn parse_xclaim(command: Command) -> Result<RespCommand, io::Error> {
+    let [key, group, consumer, min_idle, rest @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'xclaim' command");
+    };
+    let not_integer = || invalid_data_err("ERR value is not an integer or out of range");
+    let invalid_id = || {
+        invalid_data_err("ERR Invalid stream ID specified as stream command argument")
+    };
+    let min_idle_ms = min_idle
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR Invalid min-idle-time argument for XCLAIM"))?
+        .max(0) as u64;
+
+    // IDs run until the first argument that is not one; options follow.
+    let id_count = rest
+        .iter()
+        .take_while(|arg| StreamID::try_from(arg.as_str()).is_ok())
+        .count();
+    if id_count == 0 {
+        return invalid_data("ERR wrong number of arguments for 'xclaim' command");
+    }
+    let ids = rest[..id_count]
+        .iter()
+        .map(|id| StreamID::try_from(id.as_str()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut options = ClaimOptions::default();
+    let mut args = rest[id_count..].iter();
+    while let Some(arg) = args.next() {
+        match arg.to_ascii_lowercase().as_str() {
+            "idle" => {
+                let ms = args.next().and_then(|v| v.parse::<i64>().ok()).ok_or_else(not_integer)?;
+                options.idle_ms = Some(ms.max(0) as u64);
+            }
+            "time" => {
+                let ms = args.next().and_then(|v| v.parse::<i64>().ok()).ok_or_else(not_integer)?;
+                options.time_ms = Some(ms);
+            }
+            "retrycount" => {
+                let count = args.next().and_then(|v| v.parse::<u64>().ok()).ok_or_else(not_integer)?;
+                options.retry_count = Some(count);
+            }
+            "force" => options.force = true,
+            "justid" => options.justid = true,
+            "lastid" => {
+                let id = args.next().ok_or_else(invalid_id)?;
+                options.last_id = Some(StreamID::try_from(id.as_str()).map_err(|_| invalid_id())?);
+            }
+            _ => return invalid_data(format!("ERR Unrecognized XCLAIM option '{arg}'")),
+        }
+    }
+    Ok(RespCommand::XClaim {
+        key: key.clone(),
+        group: group.clone(),
+        consumer: consumer.clone(),
+        min_idle_ms,
+        ids,
+        options,
+    })
+}
+
 fn parse_xadd(command: Command) -> Result<RespCommand, io::Error> {
-    let key = command.args[0].clone();
-    let id = command.args[1].clone();
-    let rest = &command.args[2..];
+    let [key, id, rest @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'xadd' command");
+    };
+    let (key, id) = (key.clone(), id.clone());
 
-    if rest.len() % 2 != 0 {
-        return invalid_data("Each field must have a key value pair");
+    // At least one field, and every field with its value.
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return invalid_data("ERR wrong number of arguments for 'xadd' command");
     }
     let fields = rest
         .chunks(2)
@@ -405,7 +1211,9 @@ fn parse_xadd(command: Command) -> Result<RespCommand, io::Error> {
 }
 
 fn parse_xrange(command: Command) -> Result<RespCommand, io::Error> {
-    let key = command.args[0].clone();
+    let Some(key) = command.args.first().cloned() else {
+        return invalid_data("ERR wrong number of arguments for 'xrange' command");
+    };
     let mut range = command.args.iter().skip(1);
     let start = range.next().cloned();
     let end: Option<String> = range.next().cloned();
@@ -413,23 +1221,573 @@ fn parse_xrange(command: Command) -> Result<RespCommand, io::Error> {
 }
 
 fn parse_set(command: Command) -> Result<RespCommand, io::Error> {
-    let key = command.args[0].clone();
-    let value = command.args[1].clone().into_bytes();
+    let [key, value, options @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'set' command");
+    };
+    let key = key.clone();
+    let value = value.clone().into_bytes();
     let mut px = None;
-    let mut optional_args = command.args.iter().skip(2);
+    let mut optional_args = options.iter();
     while let Some(arg) = optional_args.next() {
-        if arg.to_lowercase().as_str() == "px" {
-            if let Some(px_value) = optional_args.next() {
-                match px_value.parse::<u64>() {
-                    Ok(val) => px = Some(val),
-                    Err(_) => return invalid_data("PX value must be a positive integer"),
-                }
-            } else {
-                return invalid_data("PX value must be a positive integer");
+        // EXAT and PXAT are turned into the time left; one already past expires the key
+        // as soon as it is set.
+        let option = arg.to_ascii_lowercase();
+        let unit_ms = match option.as_str() {
+            "px" | "pxat" => 1,
+            "ex" | "exat" => 1000,
+            _ => continue,
+        };
+        let ms = optional_args
+            .next()
+            .and_then(|amount| amount.parse::<u64>().ok())
+            .and_then(|amount| amount.checked_mul(unit_ms))
+            .ok_or_else(|| {
+                invalid_data_err(format!("{} value must be a positive integer", option.to_uppercase()))
+            })?;
+        px = Some(if option.ends_with("at") {
+            ms.saturating_sub(unix_now_ms() as u64)
+        } else {
+            ms
+        });
+    }
+    Ok(RespCommand::Set { key, value, px })
+}
+
+fn parse_flushall(command: Command) -> io::Result<RespCommand> {
+    match command.args.as_slice() {
+        [] => Ok(RespCommand::FlushAll),
+        [mode] if mode.eq_ignore_ascii_case("async") || mode.eq_ignore_ascii_case("sync") => {
+            Ok(RespCommand::FlushAll)
+        }
+        _ => invalid_data("ERR syntax error"),
+    }
+}
+
+fn parse_copy(command: Command) -> io::Result<RespCommand> {
+    let [source, destination, options @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'copy' command");
+    };
+    let mut db = None;
+    let mut replace = false;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_ascii_lowercase().as_str() {
+            "replace" => replace = true,
+            "db" => {
+                let index = options
+                    .next()
+                    .ok_or_else(|| invalid_data_err("ERR syntax error"))?
+                    .parse::<i64>()
+                    .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+                db = Some(index);
             }
+            _ => return invalid_data("ERR syntax error"),
         }
     }
-    Ok(RespCommand::Set { key, value, px })
+    Ok(RespCommand::Copy {
+        source: source.clone(),
+        destination: destination.clone(),
+        db,
+        replace,
+    })
+}
+
+fn parse_move(command: Command) -> io::Result<RespCommand> {
+    let [key, db] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'move' command");
+    };
+    let db = db
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+    Ok(RespCommand::Move(key.clone(), db))
+}
+
+/// Parses the NX/XX/GT/LT flags trailing an EXPIRE-family command.
+fn parse_expire_condition(flags: &[String]) -> io::Result<ExpireCondition> {
+    let mut condition = ExpireCondition::default();
+    for flag in flags {
+        match flag.to_ascii_lowercase().as_str() {
+            "nx" => condition.nx = true,
+            "xx" => condition.xx = true,
+            "gt" => condition.gt = true,
+            "lt" => condition.lt = true,
+            _ => return invalid_data(format!("ERR Unsupported option {flag}")),
+        }
+    }
+    if condition.nx && (condition.xx || condition.gt || condition.lt) {
+        return invalid_data("ERR NX and XX, GT or LT options at the same time are not compatible");
+    }
+    if condition.gt && condition.lt {
+        return invalid_data("ERR GT and LT options at the same time are not compatible");
+    }
+    Ok(condition)
+}
+
+/// EXPIREAT passes `unit_ms` 1000 (seconds), PEXPIREAT passes 1 (milliseconds). EXPIRE
+/// and PEXPIRE take a TTL rather than a timestamp and are resolved to one here.
+fn parse_expireat(command: Command, unit_ms: i64, absolute: bool) -> io::Result<RespCommand> {
+    let name = command.name.to_ascii_lowercase();
+    let [key, timestamp, flags @ ..] = command.args.as_slice() else {
+        return invalid_data(format!("ERR wrong number of arguments for '{name}' command"));
+    };
+    let base = if absolute { 0 } else { unix_now_ms() };
+    let unix_ms = timestamp
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?
+        .checked_mul(unit_ms)
+        .and_then(|ms| ms.checked_add(base))
+        .ok_or_else(|| invalid_data_err(format!("ERR invalid expire time in '{name}' command")))?;
+    Ok(RespCommand::ExpireAt {
+        key: key.clone(),
+        unix_ms,
+        condition: parse_expire_condition(flags)?,
+    })
+}
+
+fn parse_sort(command: Command) -> io::Result<RespCommand> {
+    let Some((key, rest)) = command.args.split_first() else {
+        return invalid_data("ERR wrong number of arguments for 'sort' command");
+    };
+    let mut options = SortOptions::default();
+    let mut args = rest.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_ascii_lowercase().as_str() {
+            "alpha" => options.alpha = true,
+            "asc" => options.desc = false,
+            "desc" => options.desc = true,
+            "limit" => {
+                let (Some(offset), Some(count)) = (args.next(), args.next()) else {
+                    return invalid_data("ERR syntax error");
+                };
+                let parse = |s: &String| {
+                    s.parse::<i64>()
+                        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))
+                };
+                options.limit = Some((parse(offset)?, parse(count)?));
+            }
+            _ => return invalid_data("ERR syntax error"),
+        }
+    }
+    Ok(RespCommand::Sort(key.clone(), options))
+}
+
+fn parse_lcs(command: Command) -> io::Result<RespCommand> {
+    let [key1, key2, rest @ ..] = command.args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'lcs' command");
+    };
+    let mut options = LcsOptions::default();
+    let mut args = rest.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_ascii_lowercase().as_str() {
+            "len" => options.len = true,
+            "idx" => options.idx = true,
+            "withmatchlen" => options.with_match_len = true,
+            "minmatchlen" => {
+                let min = args
+                    .next()
+                    .ok_or_else(|| invalid_data_err("ERR syntax error"))?
+                    .parse::<i64>()
+                    .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+                options.min_match_len = min.max(0) as usize;
+            }
+            _ => return invalid_data("ERR syntax error"),
+        }
+    }
+    if options.len && options.idx {
+        return invalid_data(
+            "ERR If you want both the length and indexes, please just use IDX.",
+        );
+    }
+    Ok(RespCommand::Lcs {
+        key1: key1.clone(),
+        key2: key2.clone(),
+        options,
+    })
+}
+
+fn parse_lolwut(command: Command) -> io::Result<RespCommand> {
+    match command.args.as_slice() {
+        [] => Ok(RespCommand::Lolwut(None)),
+        [option, version] if option.eq_ignore_ascii_case("version") => {
+            let version = version
+                .parse::<i64>()
+                .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+            Ok(RespCommand::Lolwut(Some(version)))
+        }
+        _ => invalid_data("ERR syntax error"),
+    }
+}
+
+fn parse_acl(command: Command) -> io::Result<RespCommand> {
+    let Some((action, rest)) = command.args.split_first() else {
+        return invalid_data("ERR wrong number of arguments for 'acl' command");
+    };
+    let action = action.to_ascii_lowercase();
+    let wrong_arity =
+        || invalid_data(format!("ERR wrong number of arguments for 'acl|{action}' command"));
+    match (action.as_str(), rest) {
+        ("help", []) => Ok(RespCommand::Help("acl".into())),
+        ("whoami", []) => Ok(RespCommand::Acl(AclCommand::WhoAmI)),
+        ("list", []) => Ok(RespCommand::Acl(AclCommand::List)),
+        ("getuser", [name]) => Ok(RespCommand::Acl(AclCommand::GetUser(name.clone()))),
+        ("setuser", [name, rules @ ..]) => Ok(RespCommand::Acl(AclCommand::SetUser(
+            name.clone(),
+            rules.to_vec(),
+        ))),
+        ("help" | "whoami" | "list" | "getuser" | "setuser", _) => wrong_arity(),
+        _ => invalid_data(format!("ERR unknown subcommand '{action}'. Try ACL HELP.")),
+    }
+}
+
+fn parse_auth(command: Command) -> io::Result<RespCommand> {
+    match command.args.as_slice() {
+        [password] => Ok(RespCommand::Auth {
+            username: None,
+            password: password.clone(),
+        }),
+        [username, password] => Ok(RespCommand::Auth {
+            username: Some(username.clone()),
+            password: password.clone(),
+        }),
+        _ => invalid_data("ERR wrong number of arguments for 'auth' command"),
+    }
+}
+
+/// `HELLO [protover [AUTH username password] [SETNAME clientname]]`. SETNAME is accepted
+/// but dropped, since connections don't carry a name yet.
+fn parse_hello(command: Command) -> io::Result<RespCommand> {
+    let Some((protover, rest)) = command.args.split_first() else {
+        return Ok(RespCommand::Hello {
+            protover: None,
+            auth: None,
+        });
+    };
+    let protover = protover.parse::<i64>().map_err(|_| {
+        invalid_data_err("ERR Protocol version is not an integer or out of range")
+    })?;
+    let mut auth = None;
+    let mut args = rest.iter();
+    while let Some(option) = args.next() {
+        match option.to_ascii_lowercase().as_str() {
+            "auth" => {
+                let (Some(username), Some(password)) = (args.next(), args.next()) else {
+                    return invalid_data(format!("ERR Syntax error in HELLO option '{option}'"));
+                };
+                auth = Some((username.clone(), password.clone()));
+            }
+            "setname" if args.next().is_some() => {}
+            _ => return invalid_data(format!("ERR Syntax error in HELLO option '{option}'")),
+        }
+    }
+    Ok(RespCommand::Hello {
+        protover: Some(protover),
+        auth,
+    })
+}
+
+fn parse_hset(command: Command) -> io::Result<RespCommand> {
+    if command.args.len() < 3 || command.args.len().is_multiple_of(2) {
+        return invalid_data("ERR wrong number of arguments for 'hset' command");
+    }
+    let key = command.args[0].clone();
+    let fields = command.raw_args[1..]
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    Ok(RespCommand::Hset { key, fields })
+}
+
+fn parse_hsetnx(command: Command) -> io::Result<RespCommand> {
+    let [key, field, value] = command.raw_args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'hsetnx' command");
+    };
+    Ok(RespCommand::HsetNx {
+        key: String::from_utf8_lossy(key).into_owned(),
+        field: field.clone(),
+        value: value.clone(),
+    })
+}
+
+fn parse_setrange(command: Command) -> io::Result<RespCommand> {
+    let [key, offset, value] = command.raw_args.as_slice() else {
+        return invalid_data("ERR wrong number of arguments for 'setrange' command");
+    };
+    let offset = std::str::from_utf8(offset)
+        .ok()
+        .and_then(|offset| offset.parse::<i64>().ok())
+        .ok_or_else(|| invalid_data_err("ERR value is not an integer or out of range"))?;
+    let offset = usize::try_from(offset).map_err(|_| invalid_data_err("ERR offset is out of range"))?;
+    Ok(RespCommand::SetRange {
+        key: String::from_utf8_lossy(key).into_owned(),
+        offset,
+        value: value.clone(),
+    })
+}
+
+/// Parses the `FIELDS numfields field [field ...]` tail used by the hash-field commands.
+fn parse_fields_block(args: &[String]) -> io::Result<Vec<Vec<u8>>> {
+    let Some((keyword, rest)) = args.split_first() else {
+        return invalid_data("ERR wrong number of arguments");
+    };
+    if !keyword.eq_ignore_ascii_case("fields") {
+        return invalid_data("ERR Mandatory argument FIELDS is missing or not at the right position");
+    }
+    let Some((count, fields)) = rest.split_first() else {
+        return invalid_data("ERR wrong number of arguments");
+    };
+    let count = count
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+    if count <= 0 {
+        return invalid_data("ERR Parameter `numFields` should be greater than 0");
+    }
+    if count as usize != fields.len() {
+        return invalid_data("ERR The `numfields` parameter must match the number of arguments");
+    }
+    Ok(fields.iter().map(|field| field.as_bytes().to_vec()).collect())
+}
+
+fn parse_hgetdel(command: Command) -> io::Result<RespCommand> {
+    let Some((key, rest)) = command.args.split_first() else {
+        return invalid_data("ERR wrong number of arguments for 'hgetdel' command");
+    };
+    Ok(RespCommand::HgetDel {
+        key: key.clone(),
+        fields: parse_fields_block(rest)?,
+    })
+}
+
+/// GETEX key [EX s | PX ms | EXAT unix-s | PXAT unix-ms | PERSIST]. Without an option
+/// the key's TTL is left alone.
+fn parse_getex(command: Command) -> io::Result<RespCommand> {
+    let Some((key, rest)) = command.args.split_first() else {
+        return invalid_data("ERR wrong number of arguments for 'getex' command");
+    };
+    let expiry = match rest {
+        [] => None,
+        [option] if option.eq_ignore_ascii_case("persist") => Some(FieldExpiry::Persist),
+        [option, amount] => {
+            let option = option.to_ascii_lowercase();
+            let unit_ms = match option.as_str() {
+                "ex" | "exat" => 1000,
+                "px" | "pxat" => 1,
+                _ => return invalid_data("ERR syntax error"),
+            };
+            let amount = amount
+                .parse::<i64>()
+                .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+            if amount <= 0 {
+                return invalid_data("ERR invalid expire time in 'getex' command");
+            }
+            let base = if option.ends_with("at") { 0 } else { unix_now_ms() };
+            let unix_ms = amount
+                .checked_mul(unit_ms)
+                .and_then(|ms| ms.checked_add(base))
+                .ok_or_else(|| invalid_data_err("ERR invalid expire time in 'getex' command"))?;
+            Some(FieldExpiry::At(unix_ms))
+        }
+        _ => return invalid_data("ERR syntax error"),
+    };
+    Ok(RespCommand::GetEx {
+        key: key.clone(),
+        expiry,
+    })
+}
+
+fn parse_hgetex(command: Command) -> io::Result<RespCommand> {
+    let Some((key, rest)) = command.args.split_first() else {
+        return invalid_data("ERR wrong number of arguments for 'hgetex' command");
+    };
+    let mut rest = rest;
+    let mut expiry = None;
+    while let Some((option, tail)) = rest.split_first() {
+        let option = option.to_ascii_lowercase();
+        let unit_ms = match option.as_str() {
+            "fields" => break,
+            "persist" => {
+                expiry = Some(FieldExpiry::Persist);
+                rest = tail;
+                continue;
+            }
+            "ex" | "exat" => 1000,
+            "px" | "pxat" => 1,
+            _ => return invalid_data("ERR syntax error"),
+        };
+        let Some((amount, tail)) = tail.split_first() else {
+            return invalid_data("ERR syntax error");
+        };
+        let amount = amount
+            .parse::<i64>()
+            .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+        if amount <= 0 {
+            return invalid_data("ERR invalid expire time in 'hgetex' command");
+        }
+        let base = if option.ends_with("at") { 0 } else { unix_now_ms() };
+        let unix_ms = amount
+            .checked_mul(unit_ms)
+            .and_then(|ms| ms.checked_add(base))
+            .ok_or_else(|| invalid_data_err("ERR invalid expire time in 'hgetex' command"))?;
+        expiry = Some(FieldExpiry::At(unix_ms));
+        rest = tail;
+    }
+    Ok(RespCommand::HgetEx {
+        key: key.clone(),
+        expiry,
+        fields: parse_fields_block(rest)?,
+    })
+}
+
+/// HEXPIRE/HPEXPIRE take a relative TTL, HEXPIREAT/HPEXPIREAT an absolute timestamp;
+/// both are resolved to Unix milliseconds here.
+fn parse_hexpire(command: Command, unit_ms: i64, absolute: bool) -> io::Result<RespCommand> {
+    let name = command.name.to_ascii_lowercase();
+    let [key, amount, rest @ ..] = command.args.as_slice() else {
+        return invalid_data(format!("ERR wrong number of arguments for '{name}' command"));
+    };
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+    let base = if absolute { 0 } else { unix_now_ms() };
+    let unix_ms = amount
+        .checked_mul(unit_ms)
+        .and_then(|ms| ms.checked_add(base))
+        .filter(|_| amount >= 0)
+        .ok_or_else(|| invalid_data_err(format!("ERR invalid expire time in '{name}' command")))?;
+    let fields_at = rest
+        .iter()
+        .position(|arg| arg.eq_ignore_ascii_case("fields"))
+        .unwrap_or(rest.len());
+    let (flags, fields) = rest.split_at(fields_at);
+    Ok(RespCommand::HExpire {
+        key: key.clone(),
+        unix_ms,
+        condition: parse_expire_condition(flags)?,
+        fields: parse_fields_block(fields)?,
+    })
+}
+
+fn parse_httl(command: Command, unit_ms: u128) -> io::Result<RespCommand> {
+    let name = command.name.to_ascii_lowercase();
+    let Some((key, rest)) = command.args.split_first() else {
+        return invalid_data(format!("ERR wrong number of arguments for '{name}' command"));
+    };
+    Ok(RespCommand::HTtl {
+        key: key.clone(),
+        unit_ms,
+        fields: parse_fields_block(rest)?,
+    })
+}
+
+fn parse_hrandfield(command: Command) -> io::Result<RespCommand> {
+    let Some(key) = command.args.first().cloned() else {
+        return Err(invalid_data_err("Unable to parse args"));
+    };
+    let count = command
+        .args
+        .get(1)
+        .map(|s| s.parse::<i64>())
+        .transpose()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+    let with_values = match command.args.get(2) {
+        Some(arg) if arg.eq_ignore_ascii_case("withvalues") => true,
+        Some(_) => return invalid_data("ERR syntax error"),
+        None => false,
+    };
+    if command.args.len() > 3 {
+        return invalid_data("ERR syntax error");
+    }
+    Ok(RespCommand::HrandField {
+        key,
+        count,
+        with_values,
+    })
+}
+
+fn parse_srandmember(command: Command) -> io::Result<RespCommand> {
+    let Some(key) = command.args.first().cloned() else {
+        return Err(invalid_data_err("Unable to parse args"));
+    };
+    if command.args.len() > 2 {
+        return invalid_data("ERR syntax error");
+    }
+    let count = command
+        .args
+        .get(1)
+        .map(|s| s.parse::<i64>())
+        .transpose()
+        .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+    Ok(RespCommand::SRandMember { key, count })
+}
+
+fn parse_sadd(command: Command) -> io::Result<RespCommand> {
+    if command.args.len() < 2 {
+        return Err(invalid_data_err("Unable to parse args"));
+    }
+    let key = command.args[0].clone();
+    let members = command
+        .args
+        .iter()
+        .skip(1)
+        .map(|s| s.as_bytes().to_vec())
+        .collect();
+    Ok(RespCommand::Sadd { key, members })
+}
+
+fn parse_smismember(command: Command) -> io::Result<RespCommand> {
+    if command.args.len() < 2 {
+        return Err(invalid_data_err("Unable to parse args"));
+    }
+    let key = command.args[0].clone();
+    let members = command
+        .args
+        .iter()
+        .skip(1)
+        .map(|s| s.as_bytes().to_vec())
+        .collect();
+    Ok(RespCommand::SmIsMember { key, members })
+}
+
+/// Splits `numkeys key [key ...] rest...` into the keys and whatever follows them.
+/// Commands like SINTERCARD need at least one key; EVAL-style ones pass `allow_zero`.
+fn split_numkeys(args: &[String], allow_zero: bool) -> io::Result<(&[String], &[String])> {
+    let Some((numkeys, rest)) = args.split_first() else {
+        return invalid_data("ERR numkeys should be greater than 0");
+    };
+    let numkeys = match numkeys.parse::<i64>() {
+        Ok(n) if n > 0 => n as usize,
+        Ok(0) if allow_zero => 0,
+        Ok(n) if n < 0 && allow_zero => {
+            return invalid_data("ERR Number of keys can't be negative");
+        }
+        Err(_) if allow_zero => {
+            return invalid_data("ERR value is not an integer or out of range");
+        }
+        _ => return invalid_data("ERR numkeys should be greater than 0"),
+    };
+    if numkeys > rest.len() {
+        return invalid_data("ERR Number of keys can't be greater than number of args");
+    }
+    Ok(rest.split_at(numkeys))
+}
+
+fn parse_sintercard(command: Command) -> io::Result<RespCommand> {
+    let (keys, rest) = split_numkeys(&command.args, false)?;
+    let keys = keys.to_vec();
+
+    let mut limit = 0;
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.to_ascii_lowercase().as_str() {
+            "limit" => {
+                limit = rest
+                    .next()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .ok_or_else(|| invalid_data_err("ERR LIMIT can't be negative"))?;
+            }
+            _ => return invalid_data("ERR syntax error"),
+        }
+    }
+    Ok(RespCommand::SinterCard { keys, limit })
 }
 
 fn parse_replconf(command: Command) -> io::Result<RespCommand> {
@@ -484,6 +1842,7 @@ fn parse_config(command: Command) -> Result<RespCommand, io::Error> {
     };
 
     match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("config".into())),
         "get" => {
             let key = command
                 .args
@@ -491,10 +1850,282 @@ fn parse_config(command: Command) -> Result<RespCommand, io::Error> {
                 .ok_or_else(|| invalid_data_err("Missing CONFIG GET key"))?;
             Ok(RespCommand::ConfigCommand(ConfigCommand::Get(key.clone())))
         }
+        "set" => match &command.args[1..] {
+            [parameter, value] => Ok(RespCommand::ConfigCommand(ConfigCommand::Set(
+                parameter.to_ascii_lowercase(),
+                value.clone(),
+            ))),
+            _ => invalid_data("ERR wrong number of arguments for 'config|set' command"),
+        },
         _ => invalid_data("Unknown CONFIG action"),
     }
 }
 
+/// Enough of FUNCTION for clients that probe it on connect; there is no engine behind it.
+/// EVAL script numkeys [key ...] [arg ...]; EVALSHA takes the script's SHA-1 instead.
+fn parse_eval(command: Command, by_sha: bool) -> io::Result<RespCommand> {
+    let Some((body, rest)) = command.args.split_first().filter(|(_, rest)| !rest.is_empty())
+    else {
+        return invalid_data(format!(
+            "ERR wrong number of arguments for '{}' command",
+            command.name.to_ascii_lowercase()
+        ));
+    };
+    let (keys, args) = split_numkeys(rest, true)?;
+    let (keys, args) = (keys.to_vec(), args.to_vec());
+    if by_sha {
+        Ok(RespCommand::EvalSha {
+            sha: body.clone(),
+            keys,
+            args,
+        })
+    } else {
+        Ok(RespCommand::Eval {
+            script: body.clone(),
+            keys,
+            args,
+        })
+    }
+}
+
+fn parse_function(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'function' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("function".into())),
+        "list" => Ok(RespCommand::Function(FunctionCommand::List)),
+        "dump" => Ok(RespCommand::Function(FunctionCommand::Dump)),
+        "stats" => Ok(RespCommand::Function(FunctionCommand::Stats)),
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try FUNCTION HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_latency(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'latency' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("latency".into())),
+        "latest" => Ok(RespCommand::Latency(LatencyCommand::Latest)),
+        "history" => match command.args.get(1) {
+            Some(event) if command.args.len() == 2 => {
+                Ok(RespCommand::Latency(LatencyCommand::History(event.clone())))
+            }
+            _ => invalid_data("ERR wrong number of arguments for 'latency|history' command"),
+        },
+        "reset" => Ok(RespCommand::Latency(LatencyCommand::Reset(
+            command.args[1..].to_vec(),
+        ))),
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try LATENCY HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_script(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'script' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("script".into())),
+        "load" => match command.args.get(1) {
+            Some(script) if command.args.len() == 2 => {
+                Ok(RespCommand::Script(ScriptCommand::Load(script.clone())))
+            }
+            _ => invalid_data("ERR wrong number of arguments for 'script|load' command"),
+        },
+        "exists" if command.args.len() >= 2 => Ok(RespCommand::Script(ScriptCommand::Exists(
+            command.args[1..].to_vec(),
+        ))),
+        "exists" => invalid_data("ERR wrong number of arguments for 'script|exists' command"),
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try SCRIPT HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_object(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'object' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("object".into())),
+        "encoding" => match command.args.get(1) {
+            Some(key) if command.args.len() == 2 => {
+                Ok(RespCommand::Object(ObjectCommand::Encoding(key.clone())))
+            }
+            _ => invalid_data("ERR wrong number of arguments for 'object|encoding' command"),
+        },
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try OBJECT HELP.",
+            action
+        )),
+    }
+}
+
+/// `MEMORY USAGE key [SAMPLES count]` samples 5 elements of a collection by default,
+/// like Redis.
+fn parse_memory(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'memory' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("memory".into())),
+        "doctor" => Ok(RespCommand::Memory(MemoryCommand::Doctor)),
+        "usage" => match &command.args[1..] {
+            [key] => Ok(RespCommand::Memory(MemoryCommand::Usage(key.clone(), 5))),
+            [key, option, count] if option.eq_ignore_ascii_case("samples") => {
+                let samples = count
+                    .parse::<usize>()
+                    .map_err(|_| invalid_data_err("ERR value is not an integer or out of range"))?;
+                Ok(RespCommand::Memory(MemoryCommand::Usage(key.clone(), samples)))
+            }
+            [_, _, _] => invalid_data("ERR syntax error"),
+            _ => invalid_data("ERR wrong number of arguments for 'memory|usage' command"),
+        },
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try MEMORY HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_pubsub(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("ERR wrong number of arguments for 'pubsub' command");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("pubsub".into())),
+        "channels" => match command.args.len() {
+            1 | 2 => Ok(RespCommand::PubSub(PubSubCommand::Channels(
+                command.args.get(1).cloned(),
+            ))),
+            _ => invalid_data("ERR wrong number of arguments for 'pubsub|channels' command"),
+        },
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try PUBSUB HELP.",
+            action
+        )),
+    }
+}
+
+/// Subcommand families whose only subcommand so far is HELP.
+fn parse_help_only(command: Command) -> Result<RespCommand, io::Error> {
+    let family = command.name.to_ascii_lowercase();
+    match command.args.first() {
+        Some(action) if action.eq_ignore_ascii_case("help") => Ok(RespCommand::Help(family)),
+        Some(action) => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try {} HELP.",
+            action,
+            family.to_ascii_uppercase()
+        )),
+        None => invalid_data(format!(
+            "ERR wrong number of arguments for '{}' command",
+            family
+        )),
+    }
+}
+
+fn parse_command(command: Command) -> Result<RespCommand, io::Error> {
+    // Bare COMMAND, which redis-cli sends on connect, describes every command.
+    let Some(action) = command.args.first() else {
+        return Ok(RespCommand::Command(CommandCommand::Info(vec![])));
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "getkeys" => {
+            if command.args.len() < 2 {
+                return invalid_data("ERR wrong number of arguments for 'command|getkeys' command");
+            }
+            Ok(RespCommand::Command(CommandCommand::GetKeys(
+                command.args[1..].to_vec(),
+            )))
+        }
+        "info" => Ok(RespCommand::Command(CommandCommand::Info(
+            command.args[1..].to_vec(),
+        ))),
+        _ => invalid_data(format!(
+            "ERR unknown subcommand '{}'. Try COMMAND HELP.",
+            action
+        )),
+    }
+}
+
+fn parse_debug(command: Command) -> Result<RespCommand, io::Error> {
+    let Some(action) = command.args.first() else {
+        return invalid_data("Missing DEBUG subcommand");
+    };
+
+    match action.to_ascii_lowercase().as_str() {
+        "help" => Ok(RespCommand::Help("debug".into())),
+        "change-repl-id" => Ok(RespCommand::Debug(DebugCommand::ChangeReplId)),
+        "stringmatch-len" => match &command.args[1..] {
+            [pattern, string] => Ok(RespCommand::Debug(DebugCommand::StringMatchLen(
+                pattern.clone(),
+                string.clone(),
+            ))),
+            _ => invalid_data("ERR wrong number of arguments for 'debug|stringmatch-len' command"),
+        },
+        "quicklist-packed-threshold" => {
+            let size = command
+                .args
+                .get(1)
+                .ok_or_else(|| invalid_data_err("Missing DEBUG QUICKLIST-PACKED-THRESHOLD size"))?
+                .parse::<usize>()
+                .map_err(|_| invalid_data_err("ERR argument must be a memory value"))?;
+            Ok(RespCommand::Debug(DebugCommand::QuicklistPackedThreshold(size)))
+        }
+        "object" => match command.args.get(1) {
+            Some(key) if command.args.len() == 2 => {
+                Ok(RespCommand::Debug(DebugCommand::Object(key.clone())))
+            }
+            _ => invalid_data("ERR wrong number of arguments for 'debug|object' command"),
+        },
+        "sleep" => {
+            let seconds = command
+                .args
+                .get(1)
+                .ok_or_else(|| invalid_data_err("ERR wrong number of arguments for 'debug|sleep' command"))?
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .ok_or_else(|| invalid_data_err("ERR value is not a valid float"))?;
+            Ok(RespCommand::Debug(DebugCommand::Sleep(Duration::from_secs_f64(seconds))))
+        }
+        "error" => match command.args.get(1) {
+            Some(message) if command.args.len() == 2 => {
+                Ok(RespCommand::Debug(DebugCommand::Error(message.clone())))
+            }
+            _ => invalid_data("ERR wrong number of arguments for 'debug|error' command"),
+        },
+        "set-active-expire" => {
+            let flag = command
+                .args
+                .get(1)
+                .ok_or_else(|| invalid_data_err("Missing DEBUG SET-ACTIVE-EXPIRE flag"))?;
+            let enabled = match flag.as_str() {
+                "0" => false,
+                "1" => true,
+                _ => return invalid_data("DEBUG SET-ACTIVE-EXPIRE flag must be 0 or 1"),
+            };
+            Ok(RespCommand::Debug(DebugCommand::SetActiveExpire(enabled)))
+        }
+        _ => invalid_data("Unknown DEBUG subcommand"),
+    }
+}
+
 fn parse_psync(command: Command) -> Result<RespCommand, io::Error> {
     if command.args.len() < 2 {
         invalid_data("Unknown CONFIG action")
@@ -0,0 +1,223 @@
+use std::io;
+
+use crate::error_helpers::{invalid_data, invalid_data_err};
+
+/// Where a command's key names sit in its argument vector. Positions count the
+/// command name as 0, matching the `COMMAND` reply.
+#[derive(Debug, Clone, Copy)]
+pub enum KeySpec {
+    None,
+    /// Keys from `first` to `last` (negative counts from the end) every `step` arguments.
+    Range { first: usize, last: isize, step: usize },
+    /// The argument at `numkeys` says how many keys follow it.
+    NumKeys { numkeys: usize },
+    /// Keys are the first half of the arguments after the STREAMS keyword.
+    Streams,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Exact argument count including the name, or the minimum when negative.
+    pub arity: i64,
+    pub keys: KeySpec,
+    /// The flags COMMAND INFO reports, in Redis's spelling.
+    pub flags: &'static [&'static str],
+}
+
+const fn spec(name: &'static str, arity: i64, keys: KeySpec) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        keys,
+        flags: &[],
+    }
+}
+
+const fn single(name: &'static str, arity: i64) -> CommandSpec {
+    spec(name, arity, KeySpec::Range { first: 1, last: 1, step: 1 })
+}
+
+const fn keyless(name: &'static str, arity: i64) -> CommandSpec {
+    spec(name, arity, KeySpec::None)
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    keyless("acl", -2).flags(&["admin", "noscript", "loading", "stale"]),
+    single("append", 3).flags(&["write", "fast"]),
+    keyless("auth", -2).flags(&["noscript", "loading", "stale", "fast"]),
+    keyless("bgrewriteaof", 1).flags(&["admin", "noscript"]),
+    spec("blpop", -3, KeySpec::Range { first: 1, last: -2, step: 1 }).flags(&["write", "noscript", "blocking"]),
+    keyless("client", -2).flags(&["admin", "noscript", "loading", "stale"]),
+    keyless("command", -1).flags(&["loading", "stale"]),
+    keyless("config", -2).flags(&["admin", "noscript", "loading", "stale"]),
+    spec("copy", -3, KeySpec::Range { first: 1, last: 2, step: 1 }).flags(&["write"]),
+    keyless("debug", -2).flags(&["admin", "noscript", "loading", "stale"]),
+    spec("del", -2, KeySpec::Range { first: 1, last: -1, step: 1 }).flags(&["write"]),
+    keyless("discard", 1).flags(&["noscript", "loading", "stale", "fast"]),
+    keyless("echo", 2).flags(&["fast"]),
+    spec("eval", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["noscript", "stale"]),
+    spec("eval_ro", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["readonly", "noscript", "stale"]),
+    spec("evalsha", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["noscript", "stale"]),
+    spec("evalsha_ro", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["readonly", "noscript", "stale"]),
+    keyless("exec", 1).flags(&["noscript", "loading", "stale"]),
+    spec("exists", -2, KeySpec::Range { first: 1, last: -1, step: 1 }).flags(&["readonly", "fast"]),
+    single("expire", -3).flags(&["write", "fast"]),
+    single("expireat", -3).flags(&["write", "fast"]),
+    spec("fcall", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["noscript", "stale"]),
+    spec("fcall_ro", -3, KeySpec::NumKeys { numkeys: 2 }).flags(&["readonly", "noscript", "stale"]),
+    keyless("flushall", -1).flags(&["write"]),
+    keyless("function", -2).flags(&["noscript"]),
+    single("geoadd", -5).flags(&["write"]),
+    single("get", 2).flags(&["readonly", "fast"]),
+    single("getdel", 2).flags(&["write", "fast"]),
+    single("getex", -2).flags(&["write", "fast"]),
+    keyless("hello", -1).flags(&["noscript", "loading", "stale", "fast"]),
+    single("hexpire", -6).flags(&["write", "fast"]),
+    single("hexpireat", -6).flags(&["write", "fast"]),
+    single("hgetall", 2).flags(&["readonly"]),
+    single("hgetdel", -5).flags(&["write", "fast"]),
+    single("hgetex", -5).flags(&["write", "fast"]),
+    single("hkeys", 2).flags(&["readonly"]),
+    single("hpexpire", -6).flags(&["write", "fast"]),
+    single("hpexpireat", -6).flags(&["write", "fast"]),
+    single("hpttl", -5).flags(&["readonly", "fast"]),
+    single("hrandfield", -2).flags(&["readonly"]),
+    single("hset", -4).flags(&["write", "fast"]),
+    single("hsetnx", 4).flags(&["write", "fast"]),
+    single("httl", -5).flags(&["readonly", "fast"]),
+    single("hvals", 2).flags(&["readonly"]),
+    single("incr", 2).flags(&["write", "fast"]),
+    keyless("info", -1).flags(&["loading", "stale"]),
+    keyless("keys", 2).flags(&["readonly"]),
+    keyless("latency", -2).flags(&["admin", "noscript", "loading", "stale"]),
+    spec("lcs", -3, KeySpec::Range { first: 1, last: 2, step: 1 }).flags(&["readonly"]),
+    single("llen", 2).flags(&["readonly", "fast"]),
+    keyless("lolwut", -1).flags(&["readonly", "fast"]),
+    single("lpop", -2).flags(&["write", "fast"]),
+    single("lpush", -3).flags(&["write", "fast"]),
+    single("lrange", 4).flags(&["readonly"]),
+    spec("memory", -2, KeySpec::Range { first: 2, last: 2, step: 1 }).flags(&["readonly"]),
+    single("move", 3).flags(&["write", "fast"]),
+    keyless("multi", 1).flags(&["noscript", "loading", "stale", "fast"]),
+    spec("object", -2, KeySpec::Range { first: 2, last: 2, step: 1 }).flags(&["readonly"]),
+    single("persist", 2).flags(&["write", "fast"]),
+    single("pexpire", -3).flags(&["write", "fast"]),
+    single("pexpireat", -3).flags(&["write", "fast"]),
+    keyless("ping", -1).flags(&["fast"]),
+    keyless("psubscribe", -2).flags(&["pubsub", "noscript", "loading", "stale"]),
+    keyless("psync", -3).flags(&["admin", "noscript"]),
+    single("pttl", 2).flags(&["readonly", "fast"]),
+    keyless("publish", 3).flags(&["pubsub", "loading", "stale", "fast"]),
+    keyless("pubsub", -2).flags(&["pubsub", "loading", "stale"]),
+    keyless("punsubscribe", -1).flags(&["pubsub", "noscript", "loading", "stale"]),
+    keyless("quit", -1).flags(&["noscript", "loading", "stale", "fast"]),
+    spec("rename", 3, KeySpec::Range { first: 1, last: 2, step: 1 }).flags(&["write"]),
+    keyless("replconf", -1).flags(&["admin", "noscript", "loading", "stale"]),
+    keyless("reset", 1).flags(&["noscript", "loading", "stale", "fast"]),
+    single("rpush", -3).flags(&["write", "fast"]),
+    single("sadd", -3).flags(&["write", "fast"]),
+    keyless("script", -2).flags(&["noscript"]),
+    single("set", -3).flags(&["write"]),
+    single("setrange", 4).flags(&["write"]),
+    single("sort", -2).flags(&["write"]),
+    spec("sintercard", -3, KeySpec::NumKeys { numkeys: 1 }).flags(&["readonly"]),
+    keyless("slowlog", -2).flags(&["admin", "loading", "stale"]),
+    single("smismember", -3).flags(&["readonly", "fast"]),
+    single("srandmember", -2).flags(&["readonly"]),
+    keyless("spublish", 3).flags(&["pubsub", "loading", "stale", "fast"]),
+    keyless("ssubscribe", -2).flags(&["pubsub", "noscript", "loading", "stale"]),
+    keyless("subscribe", -2).flags(&["pubsub", "noscript", "loading", "stale"]),
+    keyless("sunsubscribe", -1).flags(&["pubsub", "noscript", "loading", "stale"]),
+    single("ttl", 2).flags(&["readonly", "fast"]),
+    single("type", 2).flags(&["readonly", "fast"]),
+    keyless("unsubscribe", -1).flags(&["pubsub", "noscript", "loading", "stale"]),
+    keyless("wait", 3).flags(&["noscript"]),
+    single("xack", -4).flags(&["write", "fast"]),
+    single("xadd", -5).flags(&["write", "fast"]),
+    single("xclaim", -6).flags(&["write", "fast"]),
+    spec("xgroup", -2, KeySpec::Range { first: 2, last: 2, step: 1 }).flags(&["write"]),
+    keyless("xinfo", -2).flags(&["readonly"]),
+    single("xpending", -3).flags(&["readonly"]),
+    single("xrange", -4).flags(&["readonly"]),
+    spec("xread", -4, KeySpec::Streams).flags(&["readonly", "blocking"]),
+    spec("xreadgroup", -7, KeySpec::Streams).flags(&["write", "blocking"]),
+    single("zadd", -4).flags(&["write", "fast"]),
+    single("zcard", 2).flags(&["readonly", "fast"]),
+    single("zrange", -4).flags(&["readonly"]),
+    single("zrangebylex", -4).flags(&["readonly"]),
+    single("zrank", -3).flags(&["readonly", "fast"]),
+    single("zrem", -3).flags(&["write", "fast"]),
+    single("zscore", 3).flags(&["readonly", "fast"]),
+];
+
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+impl CommandSpec {
+    const fn flags(self, flags: &'static [&'static str]) -> Self {
+        CommandSpec { flags, ..self }
+    }
+
+    /// The (first, last, step) key positions COMMAND INFO reports. Commands whose keys
+    /// move with their arguments report zeros and carry the `movablekeys` flag instead.
+    pub fn key_positions(&self) -> (i64, i64, i64) {
+        match self.keys {
+            KeySpec::Range { first, last, step } => (first as i64, last as i64, step as i64),
+            KeySpec::None | KeySpec::NumKeys { .. } | KeySpec::Streams => (0, 0, 0),
+        }
+    }
+
+    pub fn has_movable_keys(&self) -> bool {
+        matches!(self.keys, KeySpec::NumKeys { .. } | KeySpec::Streams)
+    }
+
+    pub fn arity_matches(&self, argc: usize) -> bool {
+        if self.arity >= 0 {
+            argc as i64 == self.arity
+        } else {
+            argc as i64 >= -self.arity
+        }
+    }
+
+    /// Returns the key names in `argv`, where `argv[0]` is the command name.
+    pub fn keys<'a>(&self, argv: &'a [String]) -> io::Result<Vec<&'a String>> {
+        match self.keys {
+            KeySpec::None => Ok(vec![]),
+            KeySpec::Range { first, last, step } => {
+                let last = if last < 0 {
+                    argv.len() as isize + last
+                } else {
+                    last
+                };
+                if last < first as isize || last as usize >= argv.len() {
+                    return Ok(vec![]);
+                }
+                Ok(argv[first..=last as usize].iter().step_by(step).collect())
+            }
+            KeySpec::NumKeys { numkeys } => {
+                let count = argv
+                    .get(numkeys)
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .ok_or_else(|| invalid_data_err("ERR Invalid arguments specified for command"))?;
+                let keys = argv
+                    .get(numkeys + 1..numkeys + 1 + count)
+                    .ok_or_else(|| invalid_data_err("ERR Invalid arguments specified for command"))?;
+                Ok(keys.iter().collect())
+            }
+            KeySpec::Streams => {
+                let Some(pos) = argv.iter().position(|arg| arg.eq_ignore_ascii_case("streams")) else {
+                    return invalid_data("ERR Invalid arguments specified for command");
+                };
+                let rest = &argv[pos + 1..];
+                if rest.is_empty() || !rest.len().is_multiple_of(2) {
+                    return invalid_data("ERR Invalid arguments specified for command");
+                }
+                Ok(rest[..rest.len() / 2].iter().collect())
+            }
+        }
+    }
+}
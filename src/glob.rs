@@ -0,0 +1,131 @@
+/// Redis-style glob matching over raw bytes: `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and
+/// backslash escapes, following the semantics of Redis's `stringmatchlen`.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern.first() {
+        None => string.is_empty(),
+        Some(b'*') => {
+            // Collapse runs of stars, then try every possible split point.
+            let rest = &pattern[pattern.iter().take_while(|&&c| c == b'*').count()..];
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=string.len()).any(|i| glob_match(rest, &string[i..]))
+        }
+        Some(b'?') => !string.is_empty() && glob_match(&pattern[1..], &string[1..]),
+        Some(b'[') => {
+            let Some((&c, string_rest)) = string.split_first() else {
+                return false;
+            };
+            let (matched, consumed) = match_class(&pattern[1..], c);
+            matched && glob_match(&pattern[1 + consumed..], string_rest)
+        }
+        Some(b'\\') if pattern.len() >= 2 => {
+            string.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &string[1..])
+        }
+        Some(&c) => string.first() == Some(&c) && glob_match(&pattern[1..], &string[1..]),
+    }
+}
+
+/// Matches `c` against a character class whose body starts at `class` (just past `[`).
+/// Returns whether it matched and how many pattern bytes the class used, including `]`.
+fn match_class(class: &[u8], c: u8) -> (bool, usize) {
+    let negate = class.first() == Some(&b'^');
+    let mut i = usize::from(negate);
+    let mut matched = false;
+    while i < class.len() && class[i] != b']' {
+        if class[i] == b'\\' && i + 1 < class.len() {
+            matched |= class[i + 1] == c;
+            i += 2;
+        } else if i + 2 < class.len() && class[i + 1] == b'-' && class[i + 2] != b']' {
+            let (mut start, mut end) = (class[i], class[i + 2]);
+            if start > end {
+                std::mem::swap(&mut start, &mut end);
+            }
+            matched |= (start..=end).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    // An unterminated class runs to the end of the pattern, as in Redis.
+    let consumed = (i + 1).min(class.len());
+    (matched != negate, consumed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn stars_match_any_run() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("h*llo", "hllo"));
+        assert!(matches("h*llo", "heeeello"));
+        assert!(matches("h**llo", "hello"));
+        assert!(matches("user:*:name", "user:42:name"));
+        assert!(!matches("h*llo", "hellx"));
+        assert!(!matches("a*", "ba"));
+    }
+
+    #[test]
+    fn question_marks_match_one_byte() {
+        assert!(matches("h?llo", "hello"));
+        assert!(matches("h?llo", "hallo"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(!matches("?", ""));
+        assert!(matches("??", "ab"));
+    }
+
+    #[test]
+    fn classes_sets_ranges_and_negation() {
+        assert!(matches("h[ae]llo", "hello"));
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-b]llo", "hbllo"));
+        assert!(!matches("h[a-b]llo", "hcllo"));
+        // Reversed ranges are swapped.
+        assert!(matches("[z-a]", "m"));
+        // A dash next to the closing bracket is literal.
+        assert!(matches("[a-]", "-"));
+        // An empty class matches nothing.
+        assert!(!matches("[]", "]"));
+    }
+
+    #[test]
+    fn brackets_inside_a_class_are_literal() {
+        assert!(matches("[[]", "["));
+        assert!(matches("[a[]x", "[x"));
+        assert!(matches("[a[]x", "ax"));
+        assert!(!matches("[[]]", "["));
+        assert!(matches("[[]]", "[]"));
+        // An unterminated class runs to the end of the pattern.
+        assert!(matches("[abc", "b"));
+        assert!(!matches("[abc", "d"));
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert!(matches("h\\*llo", "h*llo"));
+        assert!(!matches("h\\*llo", "hello"));
+        assert!(matches("\\?", "?"));
+        assert!(!matches("\\?", "a"));
+        assert!(matches("[\\]]", "]"));
+        assert!(matches("[\\^a]", "^"));
+        // A trailing backslash matches itself.
+        assert!(matches("a\\", "a\\"));
+    }
+
+    #[test]
+    fn matches_raw_bytes() {
+        assert!(glob_match(b"k?y", b"k\xffy"));
+        assert!(glob_match(b"[\x00-\x10]", b"\x05"));
+    }
+}
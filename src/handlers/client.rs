@@ -1,6 +1,9 @@
-use crate::resp::{RespCodec, RespValue};
-use std::{net::SocketAddr};
-use tokio::{net::TcpStream, sync::mpsc::{self, Receiver, Sender}};
+use crate::{
+    handlers::connection::Transport,
+    resp::{RespCodec, RespValue},
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio_util::codec::Framed;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -10,27 +13,61 @@ pub enum ClientMode {
     Multi,
 }
 
-pub struct Client {
-    pub framed: Framed<TcpStream, RespCodec>,
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+pub struct Client<S> {
+    pub id: u64,
+    pub framed: Framed<S, RespCodec>,
     pub mode: ClientMode,
-    pub addr: SocketAddr,
-    pub channels: Vec<String>,
+    /// `ip:port` for TCP clients, `<socket path>:0` for Unix socket ones, as Redis shows them.
+    pub addr: String,
+    pub channels: Vec<Vec<u8>>,
+    pub patterns: Vec<Vec<u8>>,
+    pub shard_channels: Vec<Vec<u8>>,
     pub rx: Receiver<RespValue>,
     pub tx: Sender<RespValue>,
+    /// False until the connection AUTHs, when the default user has a password.
+    pub authenticated: bool,
+    /// The ACL user this connection runs as.
+    pub user: String,
+    /// RESP version negotiated with HELLO; 2 until the client asks for 3.
+    pub protocol: i64,
+    /// Set by QUIT: the connection closes once the reply already sent has gone out.
+    pub close_after_reply: bool,
 }
 
-impl Client {
-    pub fn new(socket: TcpStream) -> Self {
-        let addr = socket.peer_addr().unwrap();
+impl<S: Transport> Client<S> {
+    pub fn new(socket: S, addr: String, authenticated: bool) -> Self {
         let (tx, rx) = mpsc::channel(1024);
 
         Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
             framed: Framed::new(socket, RespCodec),
             mode: ClientMode::Normal,
             addr,
             channels: vec![],
+            patterns: vec![],
+            shard_channels: vec![],
             rx,
             tx,
+            authenticated,
+            user: "default".into(),
+            protocol: 2,
+            close_after_reply: false,
         }
     }
-}
+
+    /// Wraps pub/sub output the way this connection expects it: a push frame under
+    /// RESP3, a plain array under RESP2.
+    pub fn push(&self, items: Vec<RespValue>) -> RespValue {
+        if self.protocol == 3 {
+            RespValue::Push(items)
+        } else {
+            RespValue::Array(items)
+        }
+    }
+
+    /// Channels and patterns together: the count every (un)subscribe confirmation carries.
+    pub fn subscription_count(&self) -> i64 {
+        (self.channels.len() + self.patterns.len()) as i64
+    }}
@@ -0,0 +1,133 @@
+use crate::{
+    acl::Acl, command::AclCommand, handlers::client::Client, resp::RespValue,
+    server_info::ServerInfo,
+};
+
+/// Authenticates the connection as `username`, or as `default` when no username is given.
+pub fn auth<S>(client: &mut Client<S>, acl: &Acl, username: Option<&str>, password: &str) -> RespValue {
+    if username.is_none() && acl.default_is_open() {
+        return RespValue::Error(
+            "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?".into(),
+        );
+    }
+    let username = username.unwrap_or("default");
+    if !acl.authenticate(username, password) {
+        return RespValue::Error(
+            "WRONGPASS invalid username-password pair or user is disabled.".into(),
+        );
+    }
+    client.authenticated = true;
+    client.user = username.to_string();
+    RespValue::SimpleString("OK".into())
+}
+
+/// Only RESP2 and RESP3 are spoken, so any other protocol version is refused. The
+/// server details come back as a map once the connection speaks RESP3.
+pub fn hello<S>(
+    client: &mut Client<S>,
+    acl: &Acl,
+    info: &ServerInfo,
+    protover: Option<i64>,
+    credentials: Option<(String, String)>,
+) -> RespValue {
+    if protover.is_some_and(|protover| protover != 2 && protover != 3) {
+        return RespValue::Error("NOPROTO unsupported protocol version".into());
+    }
+    if let Some((username, password)) = credentials {
+        let reply = auth(client, acl, Some(&username), &password);
+        if matches!(reply, RespValue::Error(_)) {
+            return reply;
+        }
+    } else if !client.authenticated {
+        return RespValue::Error(
+            "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".into(),
+        );
+    }
+    if let Some(protover) = protover {
+        client.protocol = protover;
+    }
+
+    let role = if info.role == "master" { "master" } else { "replica" };
+    let bulk = |value: &str| RespValue::BulkString(Some(value.as_bytes().to_vec()));
+    let fields = vec![
+        (bulk("server"), bulk("redis")),
+        (bulk("version"), bulk(&info.redis_version)),
+        (bulk("proto"), RespValue::Integer(client.protocol)),
+        (bulk("id"), RespValue::Integer(client.id as i64)),
+        (bulk("mode"), bulk(&info.redis_mode)),
+        (bulk("role"), bulk(role)),
+        (bulk("modules"), RespValue::Array(vec![])),
+    ];
+    if client.protocol == 3 {
+        return RespValue::Map(fields);
+    }
+    RespValue::Array(fields.into_iter().flat_map(|(name, value)| [name, value]).collect())
+}
+
+pub fn acl_command<S>(client: &Client<S>, acl: &Acl, command: AclCommand) -> RespValue {
+    let bulk = |value: &str| RespValue::BulkString(Some(value.as_bytes().to_vec()));
+    match command {
+        AclCommand::WhoAmI => bulk(&client.user),
+        AclCommand::List => RespValue::Array(acl.list().iter().map(|line| bulk(line)).collect()),
+        AclCommand::SetUser(name, rules) => match acl.set_user(&name, &rules) {
+            Ok(()) => RespValue::SimpleString("OK".into()),
+            Err(e) => RespValue::Error(e),
+        },
+        AclCommand::GetUser(name) => {
+            let Some(user) = acl.get_user(&name) else {
+                return RespValue::BulkString(None);
+            };
+            let mut flags = vec![bulk(if user.enabled { "on" } else { "off" })];
+            if user.nopass {
+                flags.push(bulk("nopass"));
+            }
+            RespValue::Array(vec![
+                bulk("flags"),
+                RespValue::Array(flags),
+                bulk("passwords"),
+                RespValue::Array(user.passwords.iter().map(|digest| bulk(digest)).collect()),
+                bulk("commands"),
+                bulk("+@all"),
+                bulk("keys"),
+                bulk("~*"),
+                bulk("channels"),
+                bulk("&*"),
+            ])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> Client<tokio::io::DuplexStream> {
+        Client::new(tokio::io::duplex(64).0, "127.0.0.1:0".into(), true)
+    }
+
+    #[tokio::test]
+    async fn hello_replies_with_a_flat_array_under_resp2() {
+        let (acl, info) = (Acl::new(None), ServerInfo::new().unwrap());
+        let mut client = client();
+        let RespValue::Array(fields) = hello(&mut client, &acl, &info, Some(2), None) else {
+            panic!("expected an array");
+        };
+        assert_eq!(fields.len(), 14);
+        assert!(matches!(&fields[0], RespValue::BulkString(Some(name)) if name == b"server"));
+    }
+
+    #[tokio::test]
+    async fn hello_3_replies_with_a_map() {
+        let (acl, info) = (Acl::new(None), ServerInfo::new().unwrap());
+        let mut client = client();
+        let RespValue::Map(fields) = hello(&mut client, &acl, &info, Some(3), None) else {
+            panic!("expected a map");
+        };
+        assert_eq!(fields.len(), 7);
+        assert!(fields.iter().any(|(name, value)| {
+            matches!(name, RespValue::BulkString(Some(name)) if name == b"proto")
+                && matches!(value, RespValue::Integer(3))
+        }));
+        assert_eq!(client.protocol, 3);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    command::CommandCommand,
+    command_table::{self, CommandSpec},
+    resp::RespValue,
+};
+
+pub fn command_command(command: CommandCommand) -> RespValue {
+    match command {
+        CommandCommand::GetKeys(argv) => get_keys(&argv),
+        CommandCommand::Info(names) if names.is_empty() => {
+            RespValue::Array(command_table::COMMANDS.iter().map(describe).collect())
+        }
+        CommandCommand::Info(names) => RespValue::Array(
+            names
+                .iter()
+                .map(|name| match command_table::lookup(name) {
+                    Some(spec) => describe(spec),
+                    None => RespValue::NullArray,
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// [name, arity, flags, first-key, last-key, key-step], the reply COMMAND INFO gives
+/// per command.
+fn describe(spec: &CommandSpec) -> RespValue {
+    let mut flags: Vec<RespValue> = spec
+        .flags
+        .iter()
+        .map(|flag| RespValue::SimpleString((*flag).into()))
+        .collect();
+    if spec.has_movable_keys() {
+        flags.push(RespValue::SimpleString("movablekeys".into()));
+    }
+    let (first, last, step) = spec.key_positions();
+    RespValue::Array(vec![
+        RespValue::BulkString(Some(spec.name.as_bytes().to_vec())),
+        RespValue::Integer(spec.arity),
+        RespValue::Array(flags),
+        RespValue::Integer(first),
+        RespValue::Integer(last),
+        RespValue::Integer(step),
+    ])
+}
+
+fn get_keys(argv: &[String]) -> RespValue {
+    let Some(spec) = command_table::lookup(&argv[0]) else {
+        return RespValue::Error("ERR Invalid command specified".into());
+    };
+    if !spec.arity_matches(argv.len()) {
+        return RespValue::Error("ERR Invalid number of arguments specified for command".into());
+    }
+    match spec.keys(argv) {
+        Ok(keys) if keys.is_empty() => {
+            RespValue::Error("ERR The command has no key arguments".into())
+        }
+        Ok(keys) => RespValue::Array(
+            keys.into_iter()
+                .map(|key| RespValue::BulkString(Some(key.clone().into_bytes())))
+                .collect(),
+        ),
+        Err(e) => RespValue::Error(e.to_string()),
+    }
+}
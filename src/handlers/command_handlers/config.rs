@@ -2,13 +2,24 @@
 use std::sync::Arc;
 
 use crate::{
-    command::ConfigCommand, rdb_parser::config::RdbConfig, resp::RespValue
+    command::ConfigCommand,
+    rdb_parser::config::RdbConfig,
+    resp::RespValue,
+    shared_store::{keyspace_events, shared_store::Store},
 };
 
-pub fn config_command(command: ConfigCommand, rdb: Arc<RdbConfig>) -> RespValue {
+pub fn config_command(command: ConfigCommand, rdb: Arc<RdbConfig>, store: &Store) -> RespValue {
     match command {
         ConfigCommand::Get(key) => {
-            if let Some(resp) = rdb.get(key.as_str()) {
+            // Settings CONFIG SET can change live in the store rather than the startup config.
+            let runtime = match key.as_str() {
+                "notify-keyspace-events" => {
+                    Some(keyspace_events::flags_to_string(store.keyspace_events()))
+                }
+                "zset-max-listpack-entries" => Some(store.zset_max_listpack_entries().to_string()),
+                _ => None,
+            };
+            if let Some(resp) = runtime.or_else(|| rdb.get(key.as_str())) {
                 let vec = vec![
                     RespValue::BulkString(Some(key.into_bytes())),
                     RespValue::BulkString(Some(resp.into_bytes())),
@@ -18,6 +29,28 @@ pub fn config_command(command: ConfigCommand, rdb: Arc<RdbConfig>) -> RespValue
                 RespValue::BulkString(None)
             }
         }
-        _ => RespValue::SimpleString("Ok".into()),
+        ConfigCommand::Set(parameter, value) => match parameter.as_str() {
+            "notify-keyspace-events" => match keyspace_events::parse_flags(&value) {
+                Some(flags) => {
+                    store.set_keyspace_events(flags);
+                    RespValue::SimpleString("OK".into())
+                }
+                None => RespValue::Error(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{parameter}') - Invalid event class character. Use 'Ag$lshzxeKEtmdn'."
+                )),
+            },
+            "zset-max-listpack-entries" => match value.parse() {
+                Ok(entries) => {
+                    store.set_zset_max_listpack_entries(entries);
+                    RespValue::SimpleString("OK".into())
+                }
+                Err(_) => RespValue::Error(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{parameter}') - argument couldn't be parsed into an integer"
+                )),
+            },
+            _ => RespValue::Error(format!(
+                "ERR Unknown option or number of arguments for CONFIG SET - '{parameter}'"
+            )),
+        },
     }
 }
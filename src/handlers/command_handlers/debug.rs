@@ -0,0 +1,60 @@
+
+use std::sync::Arc;
+
+use crate::{
+    command::DebugCommand, glob, rdb_parser::config::RdbConfig, resp::RespValue,
+    server_info::ServerInfo, shared_store::shared_store::Store,
+};
+
+pub async fn debug_command(
+    command: DebugCommand,
+    store: &Arc<Store>,
+    info: &Arc<ServerInfo>,
+    rdb: &Arc<RdbConfig>,
+) -> RespValue {
+    match command {
+        DebugCommand::ChangeReplId => {
+            info.change_replid();
+            RespValue::SimpleString("OK".into())
+        }
+        DebugCommand::QuicklistPackedThreshold(bytes) => {
+            store.set_list_packed_threshold(bytes);
+            RespValue::SimpleString("OK".into())
+        }
+        DebugCommand::StringMatchLen(pattern, string) => {
+            let matched = glob::glob_match(pattern.as_bytes(), string.as_bytes());
+            RespValue::Integer(matched as i64)
+        }
+        DebugCommand::Object(key) => {
+            match store.debug_object(&key, rdb.list_max_listpack_size).await {
+                // Addresses and LRU clocks have no meaning here, so they are reported as 0.
+                Some(object) => {
+                    let mut reply = format!(
+                        "Value at:0x0 refcount:1 encoding:{} serializedlength:{} lru:0 lru_seconds_idle:0",
+                        object.encoding, object.serialized_len
+                    );
+                    if let Some(ql) = object.quicklist {
+                        reply.push_str(&format!(
+                            " ql_nodes:{} ql_avg_node:{:.2} ql_listpack_max:{} ql_compressed:0 ql_uncompressed_size:{}",
+                            ql.nodes, ql.avg_node, ql.listpack_max, ql.uncompressed_size
+                        ));
+                    }
+                    if let Some(length) = object.zset_length {
+                        reply.push_str(&format!(" zset_length:{length}"));
+                    }
+                    RespValue::SimpleString(reply)
+                }
+                None => RespValue::Error("ERR no such key".into()),
+            }
+        }
+        DebugCommand::Sleep(duration) => {
+            tokio::time::sleep(duration).await;
+            RespValue::SimpleString("OK".into())
+        }
+        DebugCommand::Error(message) => RespValue::Error(message),
+        DebugCommand::SetActiveExpire(enabled) => {
+            store.set_active_expire(enabled);
+            RespValue::SimpleString("OK".into())
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    command::ScriptCommand,
+    resp::RespValue,
+    scripting::{self, ScriptCache, ScriptHost},
+};
+
+fn to_bytes(values: Vec<String>) -> Vec<Vec<u8>> {
+    values.into_iter().map(String::into_bytes).collect()
+}
+
+fn compile_error(e: String) -> RespValue {
+    RespValue::Error(format!("ERR Error compiling script (new function): {e}"))
+}
+
+async fn run_source(
+    source: &str,
+    keys: Vec<String>,
+    args: Vec<String>,
+    host: &dyn ScriptHost,
+) -> RespValue {
+    match scripting::compile(source) {
+        Ok(program) => scripting::run(&program, to_bytes(keys), to_bytes(args), host).await,
+        Err(e) => compile_error(e),
+    }
+}
+
+/// EVAL caches a script that compiles before running it, so a later EVALSHA finds it.
+pub async fn eval(
+    scripts: &ScriptCache,
+    host: &dyn ScriptHost,
+    script: &str,
+    keys: Vec<String>,
+    args: Vec<String>,
+) -> RespValue {
+    match scripting::compile(script) {
+        Ok(program) => {
+            scripts.load(script);
+            scripting::run(&program, to_bytes(keys), to_bytes(args), host).await
+        }
+        Err(e) => compile_error(e),
+    }
+}
+
+pub async fn evalsha(
+    scripts: &ScriptCache,
+    host: &dyn ScriptHost,
+    sha: &str,
+    keys: Vec<String>,
+    args: Vec<String>,
+) -> RespValue {
+    match scripts.get(sha) {
+        Some(source) => run_source(&source, keys, args, host).await,
+        None => RespValue::Error("NOSCRIPT No matching script. Please use EVAL.".into()),
+    }
+}
+
+pub fn script_command(scripts: &ScriptCache, command: ScriptCommand) -> RespValue {
+    match command {
+        ScriptCommand::Load(script) => match scripting::compile(&script) {
+            Ok(_) => RespValue::BulkString(Some(scripts.load(&script).into_bytes())),
+            Err(e) => compile_error(e),
+        },
+        ScriptCommand::Exists(shas) => RespValue::Array(
+            shas.iter()
+                .map(|sha| RespValue::Integer(scripts.exists(sha) as i64))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, future::Future, pin::Pin, sync::Mutex};
+
+    use super::*;
+    use crate::sha1;
+
+    /// A keyspace of plain strings that understands just GET and SET.
+    #[derive(Default)]
+    struct FakeHost {
+        keys: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    }
+
+    impl ScriptHost for FakeHost {
+        fn call<'a>(
+            &'a self,
+            args: Vec<Vec<u8>>,
+        ) -> Pin<Box<dyn Future<Output = RespValue> + Send + 'a>> {
+            Box::pin(async move {
+                let mut keys = self.keys.lock().unwrap();
+                match (args[0].to_ascii_uppercase().as_slice(), &args[1..]) {
+                    (b"GET", [key]) => RespValue::BulkString(keys.get(key).cloned()),
+                    (b"SET", [key, value]) => {
+                        keys.insert(key.clone(), value.clone());
+                        RespValue::SimpleString("OK".into())
+                    }
+                    _ => RespValue::Error("ERR unknown command".into()),
+                }
+            })
+        }
+    }
+
+    const COMPARE_AND_SET: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+        return redis.call('SET', KEYS[1], ARGV[2]) end return 0";
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    async fn compare_and_set(scripts: &ScriptCache, host: &FakeHost, expected: &str) -> RespValue {
+        eval(scripts, host, COMPARE_AND_SET, strings(&["k"]), strings(&[expected, "new"])).await
+    }
+
+    #[tokio::test]
+    async fn compare_and_set_writes_when_the_value_matches() {
+        let (scripts, host) = (ScriptCache::new(), FakeHost::default());
+        host.keys.lock().unwrap().insert(b"k".to_vec(), b"old".to_vec());
+
+        let reply = compare_and_set(&scripts, &host, "old").await;
+        assert!(matches!(reply, RespValue::SimpleString(ok) if ok == "OK"));
+        assert_eq!(host.keys.lock().unwrap()[&b"k".to_vec()], b"new");
+    }
+
+    #[tokio::test]
+    async fn compare_and_set_leaves_a_different_value_alone() {
+        let (scripts, host) = (ScriptCache::new(), FakeHost::default());
+        host.keys.lock().unwrap().insert(b"k".to_vec(), b"other".to_vec());
+
+        let reply = compare_and_set(&scripts, &host, "old").await;
+        assert!(matches!(reply, RespValue::Integer(0)));
+        assert_eq!(host.keys.lock().unwrap()[&b"k".to_vec()], b"other");
+    }
+
+    #[tokio::test]
+    async fn evalsha_runs_a_script_eval_cached() {
+        let (scripts, host) = (ScriptCache::new(), FakeHost::default());
+        compare_and_set(&scripts, &host, "missing").await;
+
+        let sha = sha1::hex_digest(COMPARE_AND_SET.as_bytes());
+        let reply = evalsha(&scripts, &host, &sha, strings(&["k"]), strings(&["", "x"])).await;
+        assert!(matches!(reply, RespValue::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn unsupported_constructs_fail_to_compile_and_are_not_cached() {
+        let (scripts, host) = (ScriptCache::new(), FakeHost::default());
+        let script = "for i = 1, 3 do end";
+
+        let reply = eval(&scripts, &host, script, vec![], vec![]).await;
+        let RespValue::Error(message) = reply else {
+            panic!("expected a compile error, got {reply:?}");
+        };
+        assert_eq!(
+            message,
+            "ERR Error compiling script (new function): user_script:1: 'for' is not supported"
+        );
+        assert!(!scripts.exists(&sha1::hex_digest(script.as_bytes())));
+    }
+}
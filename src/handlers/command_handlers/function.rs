@@ -0,0 +1,24 @@
+use crate::{command::FunctionCommand, resp::RespValue};
+
+/// No libraries can be loaded, so every FUNCTION reply describes an empty registry.
+pub fn function_command(command: FunctionCommand) -> RespValue {
+    let bulk = |value: &str| RespValue::BulkString(Some(value.as_bytes().to_vec()));
+    match command {
+        FunctionCommand::List => RespValue::Array(vec![]),
+        FunctionCommand::Dump => RespValue::BulkString(Some(vec![])),
+        FunctionCommand::Stats => RespValue::Array(vec![
+            bulk("running_script"),
+            RespValue::BulkString(None),
+            bulk("engines"),
+            RespValue::Array(vec![
+                bulk("LUA"),
+                RespValue::Array(vec![
+                    bulk("libraries_count"),
+                    RespValue::Integer(0),
+                    bulk("functions_count"),
+                    RespValue::Integer(0),
+                ]),
+            ]),
+        ]),
+    }
+}
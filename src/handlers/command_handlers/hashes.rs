@@ -0,0 +1,168 @@
+use std::{io, sync::Arc};
+
+use crate::{
+    command::{ExpireCondition, FieldExpiry},
+    resp::RespValue,
+    shared_store::shared_store::Store,
+};
+
+pub async fn hset(
+    store: &Arc<Store>,
+    key: String,
+    fields: Vec<(Vec<u8>, Vec<u8>)>,
+) -> io::Result<Option<RespValue>> {
+    match store.hset(key, fields).await {
+        Ok(added) => Ok(Some(RespValue::Integer(added as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn hsetnx(
+    store: &Arc<Store>,
+    key: String,
+    field: Vec<u8>,
+    value: Vec<u8>,
+) -> io::Result<Option<RespValue>> {
+    match store.hsetnx(key, field, value).await {
+        Ok(set) => Ok(Some(RespValue::Integer(set as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn hgetdel(
+    store: &Arc<Store>,
+    key: String,
+    fields: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.hgetdel(&key, &fields).await {
+        Ok(values) => Ok(Some(RespValue::Array(
+            values.into_iter().map(RespValue::BulkString).collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn hgetex(
+    store: &Arc<Store>,
+    key: String,
+    expiry: Option<FieldExpiry>,
+    fields: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.hgetex(&key, &fields, expiry).await {
+        Ok(values) => Ok(Some(RespValue::Array(
+            values.into_iter().map(RespValue::BulkString).collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn hexpire(
+    store: &Arc<Store>,
+    key: String,
+    unix_ms: i64,
+    condition: ExpireCondition,
+    fields: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.hexpire(&key, unix_ms, condition, &fields).await {
+        Ok(codes) => Ok(Some(RespValue::Array(
+            codes.into_iter().map(RespValue::Integer).collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn httl(
+    store: &Arc<Store>,
+    key: String,
+    unit_ms: u128,
+    fields: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.httl(&key, &fields, unit_ms).await {
+        Ok(ttls) => Ok(Some(RespValue::Array(
+            ttls.into_iter().map(RespValue::Integer).collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn hrandfield(
+    store: &Arc<Store>,
+    key: String,
+    count: Option<i64>,
+    with_values: bool,
+) -> io::Result<Option<RespValue>> {
+    let picked = match store.hrandfield(&key, count.unwrap_or(1)).await {
+        Ok(picked) => picked,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+
+    // Without a count the reply is a single bulk string rather than an array.
+    if count.is_none() {
+        let field = picked.into_iter().next().map(|(field, _)| field);
+        return Ok(Some(RespValue::BulkString(field)));
+    }
+
+    let mut values = vec![];
+    for (field, value) in picked {
+        values.push(RespValue::BulkString(Some(field)));
+        if with_values {
+            values.push(RespValue::BulkString(Some(value)));
+        }
+    }
+    Ok(Some(RespValue::Array(values)))
+}
+
+/// RESP3 clients get a map; RESP2 clients get the pairs flattened into one array.
+pub async fn hgetall(
+    store: &Arc<Store>,
+    key: String,
+    protocol: i64,
+) -> io::Result<Option<RespValue>> {
+    let pairs = match store.hgetall(&key).await {
+        Ok(pairs) => pairs,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+    if protocol == 3 {
+        return Ok(Some(RespValue::Map(
+            pairs
+                .into_iter()
+                .map(|(field, value)| {
+                    (
+                        RespValue::BulkString(Some(field)),
+                        RespValue::BulkString(Some(value)),
+                    )
+                })
+                .collect(),
+        )));
+    }
+    Ok(Some(RespValue::Array(
+        pairs
+            .into_iter()
+            .flat_map(|(field, value)| {
+                [
+                    RespValue::BulkString(Some(field)),
+                    RespValue::BulkString(Some(value)),
+                ]
+            })
+            .collect(),
+    )))
+}
+
+/// HKEYS when `values` is false, HVALS when it is true.
+pub async fn hkeys_or_vals(
+    store: &Arc<Store>,
+    key: String,
+    values: bool,
+) -> io::Result<Option<RespValue>> {
+    match store.hgetall(&key).await {
+        Ok(pairs) => Ok(Some(RespValue::Array(
+            pairs
+                .into_iter()
+                .map(|(field, value)| {
+                    RespValue::BulkString(Some(if values { value } else { field }))
+                })
+                .collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::resp::RespValue;
+
+/// Canned `<COMMAND> HELP` output for the multi-subcommand families.
+pub fn help_command(command: &str) -> RespValue {
+    let lines: &[&str] = match command {
+        "acl" => &[
+            "ACL <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "GETUSER <username>",
+            "    Get the user's details.",
+            "LIST",
+            "    Show users details in config file format.",
+            "SETUSER <username> <attribute> [<attribute> ...]",
+            "    Create or modify a user with the specified attributes.",
+            "WHOAMI",
+            "    Return the current connection username.",
+        ],
+        "config" => &[
+            "CONFIG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "GET <parameter>",
+            "    Return the value of <parameter>.",
+            "SET <parameter> <value>",
+            "    Set the value of <parameter>. Only notify-keyspace-events can change at runtime.",
+        ],
+        "debug" => &[
+            "DEBUG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "CHANGE-REPL-ID",
+            "    Change the replication IDs of the instance.",
+            "ERROR <string>",
+            "    Return a Redis protocol error with <string> as message. Useful for clients",
+            "    unit tests to simulate Redis errors.",
+            "SLEEP <seconds>",
+            "    Stop the server for <seconds>. Decimals allowed.",
+            "OBJECT <key>",
+            "    Show low level info about the `key` and associated value.",
+            "QUICKLIST-PACKED-THRESHOLD <size>",
+            "    Sets the threshold for elements to be inserted as plain vs packed nodes.",
+            "STRINGMATCH-LEN <pattern> <string>",
+            "    Run a glob match of <string> against <pattern> and reply 1 or 0.",
+            "SET-ACTIVE-EXPIRE <0|1>",
+            "    Setting it to 0 disables expiring keys in background when they are not accessed.",
+        ],
+        "function" => &[
+            "FUNCTION <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "DUMP",
+            "    Return a serialized payload representing the current functions.",
+            "LIST",
+            "    Return general information on all the libraries.",
+            "STATS",
+            "    Return information about the current function running.",
+        ],
+        "latency" => &[
+            "LATENCY <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "HISTORY <event>",
+            "    Return time-latency samples for the <event> class.",
+            "LATEST",
+            "    Return the latest latency samples for all events.",
+            "RESET [<event> ...]",
+            "    Reset latency data of one or more <event> classes.",
+            "    (default: reset all data for all event classes)",
+        ],
+        "memory" => &[
+            "MEMORY <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "DOCTOR",
+            "    Return memory problems reports.",
+            "USAGE <key> [SAMPLES <count>]",
+            "    Return memory in bytes used by <key> and its value. Nested values are",
+            "    sampled up to <count> times (default: 5, 0 means sample all).",
+        ],
+        "object" => &[
+            "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "ENCODING <key>",
+            "    Return the kind of internal representation used in order to store the value",
+            "    associated with a <key>.",
+        ],
+        "script" => &[
+            "SCRIPT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "EXISTS <sha1> [<sha1> ...]",
+            "    Return information about the existence of the scripts in the script cache.",
+            "LOAD <script>",
+            "    Load a script into the scripts cache without executing it.",
+        ],
+        "client" => &["CLIENT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:"],
+        "xgroup" => &[
+            "XGROUP <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "CREATE <key> <groupname> <id|$> [option]",
+            "    Create a new consumer group. Options are:",
+            "    * MKSTREAM",
+            "      Create the empty stream if it does not exist.",
+        ],
+        "xinfo" => &["XINFO <subcommand> [<arg> [value] [opt] ...]. Subcommands are:"],
+        "pubsub" => &[
+            "PUBSUB <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            "CHANNELS [<pattern>]",
+            "    Return the currently active channels matching a <pattern> (default: '*').",
+        ],
+        "slowlog" => &["SLOWLOG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:"],
+        _ => &[],
+    };
+
+    let mut response: Vec<RespValue> = lines
+        .iter()
+        .map(|line| RespValue::SimpleString((*line).into()))
+        .collect();
+    response.push(RespValue::SimpleString("HELP".into()));
+    response.push(RespValue::SimpleString("    Print this help.".into()));
+    RespValue::Array(response)
+}
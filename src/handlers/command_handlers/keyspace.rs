@@ -0,0 +1,117 @@
+use std::{io, sync::Arc};
+
+use tokio::sync::Mutex;
+
+use crate::{
+    command::{ExpireCondition, SortOptions},
+    replication_manager::manager::ReplicationManager,
+    resp::RespValue,
+    shared_store::shared_store::Store,
+};
+
+/// Only database 0 exists, so any other index is out of range.
+fn check_db(db: i64) -> Result<(), RespValue> {
+    if db == 0 {
+        Ok(())
+    } else {
+        Err(RespValue::Error("ERR DB index is out of range".into()))
+    }
+}
+
+pub async fn copy(
+    store: &Arc<Store>,
+    source: String,
+    destination: String,
+    db: Option<i64>,
+    replace: bool,
+) -> io::Result<Option<RespValue>> {
+    if let Err(e) = check_db(db.unwrap_or(0)) {
+        return Ok(Some(e));
+    }
+    if source == destination {
+        return Ok(Some(RespValue::Error(
+            "ERR source and destination objects are the same".into(),
+        )));
+    }
+    let copied = store.copy(&source, destination, replace).await?;
+    Ok(Some(RespValue::Integer(copied as i64)))
+}
+
+/// Keys that had already expired count as absent. The command goes to replicas as
+/// sent, like SET, so they drop the same keys.
+pub async fn del(
+    store: &Arc<Store>,
+    manager: &Arc<Mutex<ReplicationManager>>,
+    keys: Vec<String>,
+    bytes: Vec<u8>,
+) -> io::Result<Option<RespValue>> {
+    let removed = store.del(&keys).await;
+    // Deleting nothing changes nothing, so replicas don't need to hear about it.
+    if removed > 0 {
+        store.append_to_log(bytes.clone()).await;
+        manager.lock().await.send_to_replicas(bytes).await?;
+    }
+    Ok(Some(RespValue::Integer(removed as i64)))
+}
+
+pub async fn exists(store: &Arc<Store>, keys: Vec<String>) -> io::Result<Option<RespValue>> {
+    Ok(Some(RespValue::Integer(store.exists(&keys).await)))
+}
+
+pub async fn rename(
+    store: &Arc<Store>,
+    key: String,
+    newkey: String,
+) -> io::Result<Option<RespValue>> {
+    if store.rename(&key, newkey).await {
+        Ok(Some(RespValue::SimpleString("OK".into())))
+    } else {
+        Ok(Some(RespValue::Error("ERR no such key".into())))
+    }
+}
+
+/// With a single database the target is either out of range or the source itself.
+pub fn move_key(db: i64) -> RespValue {
+    match check_db(db) {
+        Ok(()) => RespValue::Error("ERR source and destination objects are the same".into()),
+        Err(e) => e,
+    }
+}
+
+pub async fn sort(
+    store: &Arc<Store>,
+    key: String,
+    options: SortOptions,
+) -> io::Result<Option<RespValue>> {
+    match store.sort(&key, &options).await {
+        Ok(sorted) => Ok(Some(RespValue::Array(
+            sorted
+                .into_iter()
+                .map(|element| RespValue::BulkString(Some(element)))
+                .collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn expire_at(
+    store: &Arc<Store>,
+    key: String,
+    unix_ms: i64,
+    condition: ExpireCondition,
+) -> io::Result<Option<RespValue>> {
+    let applied = store.expire_at(&key, unix_ms, condition).await?;
+    Ok(Some(RespValue::Integer(applied as i64)))
+}
+
+pub async fn persist(store: &Arc<Store>, key: String) -> io::Result<Option<RespValue>> {
+    let removed = store.persist(&key).await;
+    Ok(Some(RespValue::Integer(removed as i64)))
+}
+
+/// TTL and PTTL. Seconds are rounded to the nearest whole second, as Redis does.
+pub async fn ttl(store: &Arc<Store>, key: String, unit_ms: i64) -> io::Result<Option<RespValue>> {
+    let ms = store.pttl(&key).await;
+    let remaining = if ms < 0 { ms } else { (ms + unit_ms / 2) / unit_ms };
+    Ok(Some(RespValue::Integer(remaining)))
+}
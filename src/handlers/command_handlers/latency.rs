@@ -0,0 +1,30 @@
+use crate::{command::LatencyCommand, latency::LatencyMonitor, resp::RespValue};
+
+pub fn latency_command(monitor: &LatencyMonitor, command: LatencyCommand) -> RespValue {
+    match command {
+        LatencyCommand::Latest => RespValue::Array(
+            monitor
+                .latest()
+                .into_iter()
+                .map(|(event, time, latest_ms, max_ms)| {
+                    RespValue::Array(vec![
+                        RespValue::BulkString(Some(event.into_bytes())),
+                        RespValue::Integer(time),
+                        RespValue::Integer(latest_ms as i64),
+                        RespValue::Integer(max_ms as i64),
+                    ])
+                })
+                .collect(),
+        ),
+        LatencyCommand::History(event) => RespValue::Array(
+            monitor
+                .history(&event)
+                .into_iter()
+                .map(|(time, ms)| {
+                    RespValue::Array(vec![RespValue::Integer(time), RespValue::Integer(ms as i64)])
+                })
+                .collect(),
+        ),
+        LatencyCommand::Reset(events) => RespValue::Integer(monitor.reset(&events) as i64),
+    }
+}
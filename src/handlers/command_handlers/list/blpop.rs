@@ -1,8 +1,12 @@
-use futures::future::select_all;
 use std::{io, sync::Arc, time::Duration};
-use tokio::sync::Notify;
+use tokio::{sync::Notify, time::Instant};
 
-use crate::{resp::RespValue, shared_store::shared_store::Store};
+use crate::{
+    aof::{Aof, AofEntry},
+    handlers::command_handlers::stream::wait_for_keys,
+    resp::RespValue,
+    shared_store::shared_store::Store,
+};
 
 /// Attempt to pop from any key immediately.
 async fn poll_lpop(store: &Arc<Store>, keys: &[String]) -> io::Result<Option<RespValue>> {
@@ -25,63 +29,77 @@ async fn try_poll_lpop(store: &Arc<Store>, keys: &[String]) -> io::Result<Option
     poll_lpop(store, keys).await
 }
 
-/// Wait with a timeout for any key to receive a push.
-async fn wait_with_timeout(
-    store: &Arc<Store>,
-    keys: &[String],
-    notifiers: &[Arc<Notify>],
-    timeout_ms: u64,
-) -> io::Result<Option<RespValue>> {
-    let timeout = Duration::from_millis(timeout_ms);
-    let futures = notifiers.iter().map(|n| Box::pin(n.notified())).collect::<Vec<_>>();
+/// A blocked BLPOP's place in line on its keys. Leaving, whether served, timed out or
+/// disconnected, wakes the others so the next in line re-checks.
+struct Turn<'a> {
+    store: &'a Store,
+    keys: &'a [String],
+    notifiers: Vec<Arc<Notify>>,
+    ticket: u64,
+}
 
-    tokio::select! {
-        _ = select_all(futures) => {
-            try_poll_lpop(store, keys).await
-        }
-        _ = tokio::time::sleep(timeout) => {
-            Ok(Some(RespValue::NullArray)) // *-1\r\n for timeout
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.store.leave_list_queue(self.keys, self.ticket);
+        for notify in &self.notifiers {
+            notify.notify_waiters();
         }
     }
 }
 
-/// Wait forever until a value is available.
-async fn wait_forever(
-    store: &Arc<Store>,
-    keys: &[String],
-    notifiers: &[Arc<Notify>],
-) -> io::Result<Option<RespValue>> {
-    loop {
-        // Poll first: maybe a value appeared while awaiting
-        if let Some(resp) = try_poll_lpop(store, keys).await? {
-            return Ok(Some(resp));
+/// Logs a successful pop before returning it. Only a single attempt holds up an AOF
+/// rewrite, never the wait between attempts.
+async fn poll_in_turn(turn: &Turn<'_>, aof: Option<&Aof>) -> io::Result<Option<RespValue>> {
+    let _order = match aof {
+        Some(aof) => Some(aof.order_write().await),
+        None => None,
+    };
+    for key in turn.keys {
+        if let Some(value) = turn.store.lpop_in_turn(key, turn.ticket).await? {
+            let reply = Some(RespValue::Array(vec![
+                RespValue::BulkString(Some(key.as_bytes().into())),
+                RespValue::BulkString(Some(value)),
+            ]));
+            if let Some(aof) = aof {
+                aof.record(AofEntry::PopFromReply, &reply);
+            }
+            return Ok(reply);
         }
-
-        // No value yet: wait for any notifier
-        let futures = notifiers.iter().map(|n| Box::pin(n.notified())).collect::<Vec<_>>();
-        select_all(futures).await;
-
-        // Once notified, loop again to attempt poll
     }
+    Ok(None)
 }
 
-/// Main BLPOP command entry
+/// Main BLPOP command entry. Every client blocked on a key is woken by a push, but the
+/// element goes to the one that blocked first; the rest find it is not their turn and go
+/// back to waiting.
 pub async fn blpop_command(
     store: &Arc<Store>,
+    aof: Option<&Aof>,
     keys: &[String],
     timeout: u64,
 ) -> io::Result<Option<RespValue>> {
-    // Lock keyspace while doing both
-    let notifiers = store.get_notifiers(keys).await; // register first
-
-    if let Some(result) = try_poll_lpop(store, keys).await? {
-        // pop first if any value exists
-        return Ok(Some(result));
+    // A timeout of 0 blocks until something arrives.
+    let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_millis(timeout));
+    let turn = Turn {
+        store,
+        keys,
+        notifiers: store.get_notifiers(keys).await,
+        ticket: store.join_list_queue(keys),
+    };
+    loop {
+        match wait_for_keys(&turn.notifiers, deadline, poll_in_turn(&turn, aof)).await? {
+            Ok(_) => continue,
+            Err(reply) => return Ok(Some(reply)),
+        }
     }
+}
 
-    if timeout == 0 {
-        wait_forever(store, keys, &notifiers).await
-    } else {
-        wait_with_timeout(store, keys, &notifiers, timeout).await
-    }
+/// BLPOP as it runs inside MULTI/EXEC: a single poll, replying as if the timeout had
+/// already expired when every list is empty.
+pub async fn blpop_now(store: &Arc<Store>, keys: &[String]) -> io::Result<Option<RespValue>> {
+    Ok(Some(
+        try_poll_lpop(store, keys)
+            .await?
+            .unwrap_or(RespValue::NullArray),
+    ))
 }
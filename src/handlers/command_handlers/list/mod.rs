@@ -9,19 +9,25 @@ pub async fn rpush(
     key: String,
     values: Vec<Vec<u8>>,
 ) -> io::Result<Option<RespValue>> {
-   let len = store.rpush(key, values).await?;
-    let result = RespValue::Integer(len as i64);
-    Ok(Some(result))
+    match store.rpush(key, values).await {
+        Ok(len) => Ok(Some(RespValue::Integer(len as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
 }
 
 pub async fn llen(store: Arc<Store>, key: String) -> io::Result<Option<RespValue>> {
-    let len = store.llen(key).await?;
-    let result = RespValue::Integer(len as i64);
-    Ok(Some(result))
+    match store.llen(key).await {
+        Ok(len) => Ok(Some(RespValue::Integer(len as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
 }
 
 pub async fn lpop(store: Arc<Store>, key: String, amount: usize) -> io::Result<Option<RespValue>> {
-    match store.lpop(key, amount).await? {
+    let popped = match store.lpop(key, amount).await {
+        Ok(popped) => popped,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+    match popped {
         None => Ok(Some(RespValue::BulkString(None))),
         Some(mut values) if values.len() == 1 => Ok(Some(RespValue::BulkString(values.pop()))),
         Some(values) => Ok(Some(RespValue::Array(
@@ -38,9 +44,10 @@ pub async fn lpush(
     key: String,
     values: Vec<Vec<u8>>,
 ) -> io::Result<Option<RespValue>> {
-    let len = store.lpush(key, values).await?;
-    let result = RespValue::Integer(len as i64);
-    Ok(Some(result))
+    match store.lpush(key, values).await {
+        Ok(len) => Ok(Some(RespValue::Integer(len as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
 }
 
 pub async fn lrange(
@@ -49,7 +56,10 @@ pub async fn lrange(
     start: isize,
     end: isize,
 ) -> io::Result<Option<RespValue>> {
-    let values = store.lrange(key, start, end).await?;
+    let values = match store.lrange(key, start, end).await {
+        Ok(values) => values,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
     let mut arr = vec![];
     for v in values {
         arr.push(RespValue::BulkString(Some(v)));
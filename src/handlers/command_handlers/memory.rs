@@ -0,0 +1,21 @@
+use std::{io, sync::Arc};
+
+use crate::{command::MemoryCommand, resp::RespValue, shared_store::shared_store::Store};
+
+pub async fn memory_command(
+    command: MemoryCommand,
+    store: &Arc<Store>,
+) -> io::Result<Option<RespValue>> {
+    match command {
+        MemoryCommand::Usage(key, samples) => Ok(Some(
+            match store.memory_usage(&key, samples).await {
+                Some(bytes) => RespValue::Integer(bytes as i64),
+                None => RespValue::BulkString(None),
+            },
+        )),
+        MemoryCommand::Doctor => Ok(Some(RespValue::BulkString(Some(
+            "Hi Sam, I can't find any memory issue in your instance. I can only account for what occurs on this base."
+                .into(),
+        )))),
+    }
+}
@@ -2,8 +2,22 @@ pub mod stream;
 pub mod wait;
 pub mod xadd;
 pub mod xrange;
+pub mod stream_group;
 pub mod set;
+pub mod sets;
 pub mod type_command;
 pub mod config;
+pub mod function;
+pub mod hashes;
+pub mod debug;
 pub mod psync;
-pub mod list;
\ No newline at end of file
+pub mod list;
+pub mod help;
+pub mod command_info;
+pub mod keyspace;
+pub mod object;
+pub mod auth;
+pub mod strings;
+pub mod eval;
+pub mod latency;
+pub mod memory;
@@ -0,0 +1,23 @@
+use std::{io, sync::Arc};
+
+use crate::{
+    command::ObjectCommand, rdb_parser::config::RdbConfig, resp::RespValue,
+    shared_store::shared_store::Store,
+};
+
+pub async fn object_command(
+    command: ObjectCommand,
+    store: &Arc<Store>,
+    rdb: &Arc<RdbConfig>,
+) -> io::Result<Option<RespValue>> {
+    match command {
+        ObjectCommand::Encoding(key) => {
+            let encoding = store
+                .object_encoding(&key, rdb.list_max_listpack_size)
+                .await?;
+            Ok(Some(RespValue::BulkString(
+                encoding.map(|encoding| encoding.as_bytes().to_vec()),
+            )))
+        }
+    }
+}
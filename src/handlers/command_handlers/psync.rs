@@ -1,48 +1,73 @@
 use std::{io, sync::Arc};
 
-use tokio::{
-    io::AsyncWriteExt,
-    net::TcpStream,
-};
+use tokio::io::{self as tokio_io, AsyncRead, AsyncWriteExt};
 use tokio_util::codec::{Framed, FramedRead};
 use futures::StreamExt;
 
 use crate::{
     command::{self, ReplconfCommand, RespCommand},
     error_helpers::invalid_data_err,
+    handlers::connection::Transport,
     replication_manager::manager::ReplicationManager,
     resp::{RespCodec},
     server_info::ServerInfo,
 };
 
-pub async fn psync_command(
-    framed: Framed<TcpStream, RespCodec>,
-    _string: String,
-    _pos: i64,
+pub async fn psync_command<S: Transport>(
+    framed: Framed<S, RespCodec>,
+    replid: String,
+    psync_offset: i64,
     info: Arc<ServerInfo>,
     manager: Arc<tokio::sync::Mutex<ReplicationManager>>,
     peer_addr: String,
 ) -> io::Result<()> {
     let mut stream = framed.into_inner();
-    let peer_address = stream.peer_addr()?;
-    let first_response = format!("+FULLRESYNC {} 0\r\n", info.master_replid);
 
-    stream.write_all(first_response.as_bytes()).await?;
+    // Hold the manager across the reply so nothing is propagated between choosing the
+    // sync point and registering the replica.
+    let mut guard = manager.lock().await;
+    let continuation = if psync_offset > 0 && info.can_continue(&replid, psync_offset as u64) {
+        // Replicas ask for the byte after the last one they processed.
+        guard.backlog_since(psync_offset as u64 - 1)
+    } else {
+        None
+    };
+
+    let pending = match continuation {
+        Some(pending) => {
+            let response = format!("+CONTINUE {}\r\n", info.master_replid());
+            stream.write_all(response.as_bytes()).await?;
+            pending
+        }
+        None => {
+            let first_response =
+                format!("+FULLRESYNC {} {}\r\n", info.master_replid(), guard.offset());
+            stream.write_all(first_response.as_bytes()).await?;
 
-    let blank_hex = "524544495330303131fa0972656469732d76657205372e322e30fa0a72656469732d62697473c040fa056374696d65c26d08bc65fa08757365642d6d656dc2b0c41000fa08616f662d62617365c000fff06e3bfec0ff5aa2";
-    let rdb_bytes = hex::decode(blank_hex).unwrap();
-    let header = format!("${}\r\n", rdb_bytes.len());
-    stream.write_all(header.as_bytes()).await?;
-    stream.write_all(rdb_bytes.as_slice()).await?;
+            let blank_hex = "524544495330303131fa0972656469732d76657205372e322e30fa0a72656469732d62697473c040fa056374696d65c26d08bc65fa08757365642d6d656dc2b0c41000fa08616f662d62617365c000fff06e3bfec0ff5aa2";
+            let rdb_bytes = hex::decode(blank_hex).unwrap();
+            let header = format!("${}\r\n", rdb_bytes.len());
+            stream.write_all(header.as_bytes()).await?;
+            stream.write_all(rdb_bytes.as_slice()).await?;
+            vec![]
+        }
+    };
 
     stream.flush().await?;
-    let (read_half, write_half) = stream.into_split();
-    manager
-        .lock()
-        .await
-        .add_replica(&peer_addr, peer_address, write_half)
-        .await?;
+    let (read_half, write_half) = tokio_io::split(stream);
+    guard.add_replica(&peer_addr, write_half, pending).await?;
+    drop(guard);
     let mut framed_reader = FramedRead::new(read_half, RespCodec);
+    let result = read_acks(&mut framed_reader, &manager, &peer_addr).await;
+    manager.lock().await.remove_replica(&peer_addr).await;
+    result
+}
+
+async fn read_acks(
+    framed_reader: &mut FramedRead<impl AsyncRead + Unpin, RespCodec>,
+    manager: &Arc<tokio::sync::Mutex<ReplicationManager>>,
+    peer_addr: &String,
+) -> io::Result<()> {
     while let Some(result) = framed_reader.next().await {
         let (resp_value, _) = result?;
         let command: command::RespCommand = command::Command::try_from_resp(resp_value)?;
@@ -54,7 +79,7 @@ pub async fn psync_command(
                 manager
                     .lock()
                     .await
-                    .update_offset(&peer_addr, offset)
+                    .update_offset(peer_addr, offset)
                     .await?;
             }
 
@@ -62,4 +87,4 @@ pub async fn psync_command(
         };
     }
     Ok(())
-}
\ No newline at end of file
+}
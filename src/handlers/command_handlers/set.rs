@@ -4,7 +4,6 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::{
-    command::RespCommand,
     replication_manager::manager::ReplicationManager,
     resp::RespValue,
     shared_store::shared_store::Store,
@@ -19,14 +18,9 @@ pub async fn set_command(
     bytes: Vec<u8>,
 ) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
     store.set(&key, value.to_vec(), px).await;
-    store.append_to_log(bytes).await;
+    store.append_to_log(bytes.clone()).await;
 
-    let copied_command = RespCommand::Set {
-        key,
-        value: value.to_vec(),
-        px,
-    };
-    let guard = manager.lock().await;
-    guard.send_to_replicas(copied_command).await?;
+    let mut guard = manager.lock().await;
+    guard.send_to_replicas(bytes).await?;
     Ok(Some(RespValue::SimpleString("OK".into())))
 }
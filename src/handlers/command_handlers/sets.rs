@@ -0,0 +1,62 @@
+use std::{io, sync::Arc};
+
+use crate::{resp::RespValue, shared_store::shared_store::Store};
+
+pub async fn sadd(
+    store: &Arc<Store>,
+    key: String,
+    members: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.sadd(key, members).await {
+        Ok(added) => Ok(Some(RespValue::Integer(added as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn sintercard(
+    store: &Arc<Store>,
+    keys: Vec<String>,
+    limit: usize,
+) -> io::Result<Option<RespValue>> {
+    match store.sintercard(&keys, limit).await {
+        Ok(count) => Ok(Some(RespValue::Integer(count as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn smismember(
+    store: &Arc<Store>,
+    key: String,
+    members: Vec<Vec<u8>>,
+) -> io::Result<Option<RespValue>> {
+    match store.smismember(&key, &members).await {
+        Ok(found) => Ok(Some(RespValue::Array(
+            found
+                .into_iter()
+                .map(|present| RespValue::Integer(present as i64))
+                .collect(),
+        ))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn srandmember(
+    store: &Arc<Store>,
+    key: String,
+    count: Option<i64>,
+) -> io::Result<Option<RespValue>> {
+    let picked = match store.srandmember(&key, count.unwrap_or(1)).await {
+        Ok(picked) => picked,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+    // Without a count the reply is a single bulk string rather than an array.
+    if count.is_none() {
+        return Ok(Some(RespValue::BulkString(picked.into_iter().next())));
+    }
+    Ok(Some(RespValue::Array(
+        picked
+            .into_iter()
+            .map(|member| RespValue::BulkString(Some(member)))
+            .collect(),
+    )))
+}
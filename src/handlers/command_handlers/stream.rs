@@ -1,12 +1,10 @@
 
 use std::{io, sync::Arc, time::Duration};
-use futures::future::select_all;
-use tokio::{
-    sync::Notify,
-    task,
-};
+use futures::{future::select_all, FutureExt};
+use tokio::{sync::Notify, time::Instant};
 
 use crate::{
+    log,
     resp::RespValue,
     shared_store::{redis_stream::StreamEntry, shared_store::Store, stream_id::StreamID},
 };
@@ -42,10 +40,14 @@ async fn poll_xread(
     store: &Arc<Store>,
     keys: &[String],
     ids: &[StreamID],
+    count: Option<u64>,
 ) -> io::Result<Vec<RespValue>> {
     let mut outer = vec![];
     for (key, id) in keys.iter().zip(ids) {
-        let resp = store.xread(key, id).await?;
+        let mut resp = store.xread(key, id).await?;
+        if let Some(count) = count {
+            resp.truncate(count as usize);
+        }
         if !resp.is_empty() {
             let inner = RespValue::Array(encode_stream(resp));
             let full = vec![RespValue::BulkString(Some(key.clone().into_bytes())), inner];
@@ -59,84 +61,86 @@ async fn try_poll_xread(
     store: &Arc<Store>,
     keys: &[String],
     ids: &[StreamID],
+    count: Option<u64>,
 ) -> io::Result<Option<RespValue>> {
-    let result = poll_xread(store, keys, ids).await?;
+    let result = poll_xread(store, keys, ids, count).await?;
     if result.is_empty() {
         Ok(None)
     } else {
         Ok(Some(RespValue::Array(result)))
     }
 }
-async fn wait_with_timeout(
-    store: &Arc<Store>,
-    keys: &[String],
-    ids: &[StreamID],
+/// Runs `poll` and, if it found nothing, waits for any of `notifiers` to fire. Returns the
+/// indexes of every notifier that did, or `Err` with the reply to send: `poll`'s result, or
+/// a null array once `deadline` passes. The futures are registered before `poll` runs, so
+/// a write landing in between still wakes us instead of being missed. BLPOP and the
+/// stream reads all block through this.
+pub async fn wait_for_keys(
     notifiers: &[Arc<Notify>],
-    timeout_ms: u64,
-) -> io::Result<Option<RespValue>> {
-    let timeout = Duration::from_millis(timeout_ms);
-    let futures = notifiers
+    deadline: Option<Instant>,
+    poll: impl std::future::Future<Output = io::Result<Option<RespValue>>>,
+) -> io::Result<Result<Vec<usize>, RespValue>> {
+    let wakeups: Vec<_> = notifiers
         .iter()
-        .map(|n| Box::pin(n.notified()))
-        .collect::<Vec<_>>();
-
-    tokio::select! {
-        _ = select_all(futures) => {
-            try_poll_xread(store, keys, ids).await
-        }
-        _ = tokio::time::sleep(timeout) => {
-            Ok(Some(RespValue::NullArray))
-        }
-    }
-}
-
-async fn wait_forever(
-    store: &Arc<Store>,
-    keys: &[String],
-    ids: &[StreamID],
-    notifiers: &[Arc<Notify>],
-) -> io::Result<Option<RespValue>> {
-    println!("Waiting Forever .");
-
-    loop {
-        let futures = notifiers
-            .iter()
-            .map(|n| Box::pin(n.notified()))
-            .collect::<Vec<_>>();
-
-        tokio::select! {
-            _ = select_all(futures) => {
-                println!("Waiting Forever called.");
-                            task::yield_now().await;
-
-                if let Some(resp) = try_poll_xread(store, keys, ids).await? {
-                    dbg!(&resp);
-                    return Ok(Some(resp));
-                }
-            }
-        }
+        .enumerate()
+        .map(|(i, notify)| {
+            let mut notified = Box::pin(notify.notified());
+            notified.as_mut().enable();
+            Box::pin(async move {
+                notified.await;
+                i
+            })
+        })
+        .collect();
+    if let Some(reply) = poll.await? {
+        return Ok(Err(reply));
     }
+    let (first, _, rest) = match deadline {
+        Some(deadline) => match tokio::time::timeout_at(deadline, select_all(wakeups)).await {
+            Ok(woken) => woken,
+            Err(_) => return Ok(Err(RespValue::NullArray)),
+        },
+        None => select_all(wakeups).await,
+    };
+    // Other streams may have been written at the same moment.
+    let mut woken = vec![first];
+    woken.extend(rest.into_iter().filter_map(|wakeup| wakeup.now_or_never()));
+    woken.sort_unstable();
+    Ok(Ok(woken))
 }
 
 pub async fn xread_command(
     store: &Arc<Store>,
+    count: Option<u64>,
     block: &Option<u64>,
     keys: &[String],
     ids: &[String],
 ) -> io::Result<Option<RespValue>> {
-    let ids = store.resolve_stream_ids(keys, ids).await?;
-    // First, check if any stream already has entries
-    if let Some(result) = try_poll_xread(store, keys, &ids).await? {
-        return Ok(Some(result));
-    }
-
-    // Get notifiers for the keys
+    let ids = match store.resolve_stream_ids(keys, ids).await {
+        Ok(ids) => ids,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+    let Some(block) = block else {
+        return match try_poll_xread(store, keys, &ids, count).await {
+            Ok(result) => Ok(Some(result.unwrap_or(RespValue::NullArray))),
+            Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+        };
+    };
+    // BLOCK 0 waits forever.
+    let deadline = (*block > 0).then(|| Instant::now() + Duration::from_millis(*block));
     let notifiers = store.get_notifiers(keys).await;
+    log::debug!("XREAD blocking on {keys:?}");
 
-    // Decide whether to wait with timeout or wait forever
-    match block {
-        Some(0) => wait_forever(store, keys, &ids, &notifiers).await, // <- changed
-        Some(ms) => wait_with_timeout(store, keys, &ids, &notifiers, *ms).await,
-        None => try_poll_xread(store, keys, &ids).await,
+    // Everything is checked once; after that only the streams that were written to.
+    let mut to_check: Vec<usize> = (0..keys.len()).collect();
+    loop {
+        let checked_keys: Vec<String> = to_check.iter().map(|&i| keys[i].clone()).collect();
+        let checked_ids: Vec<StreamID> = to_check.iter().map(|&i| ids[i]).collect();
+        let poll = try_poll_xread(store, &checked_keys, &checked_ids, count);
+        match wait_for_keys(&notifiers, deadline, poll).await {
+            Ok(Ok(woken)) => to_check = woken,
+            Ok(Err(reply)) => return Ok(Some(reply)),
+            Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+        }
     }
 }
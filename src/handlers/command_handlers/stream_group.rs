@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, io, sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    command::{ClaimOptions, PendingRange, XGroupCommand},
+    handlers::command_handlers::stream::{encode_stream, wait_for_keys},
+    resp::RespValue,
+    shared_store::{shared_store::Store, stream_id::StreamID},
+};
+
+pub async fn xgroup(store: &Arc<Store>, command: XGroupCommand) -> RespValue {
+    match command {
+        XGroupCommand::Create {
+            key,
+            group,
+            id,
+            mkstream,
+        } => match store.xgroup_create(&key, &group, &id, mkstream).await {
+            Ok(()) => RespValue::SimpleString("OK".into()),
+            Err(e) => RespValue::Error(e.to_string()),
+        },
+    }
+}
+
+/// Reads every key once; `None` means nothing new arrived for a `>` read.
+async fn poll_xreadgroup(
+    store: &Arc<Store>,
+    group: &str,
+    consumer: &str,
+    count: Option<usize>,
+    noack: bool,
+    keys: &[String],
+    ids: &[String],
+) -> io::Result<Option<RespValue>> {
+    let mut outer = vec![];
+    for (key, id) in keys.iter().zip(ids) {
+        let entries = store
+            .xreadgroup(key, group, consumer, id, count, noack)
+            .await?;
+        // History reads always report the key, even when the consumer has nothing pending.
+        if entries.is_empty() && id == ">" {
+            continue;
+        }
+        outer.push(RespValue::Array(vec![
+            RespValue::BulkString(Some(key.clone().into_bytes())),
+            RespValue::Array(encode_stream(entries)),
+        ]));
+    }
+    Ok((!outer.is_empty()).then_some(RespValue::Array(outer)))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn xreadgroup(
+    store: &Arc<Store>,
+    group: &str,
+    consumer: &str,
+    count: Option<usize>,
+    block: Option<u64>,
+    noack: bool,
+    keys: &[String],
+    ids: &[String],
+) -> RespValue {
+    // Only reads of new entries can block; history is already known.
+    let can_block = block.is_some() && ids.iter().all(|id| id == ">");
+    let poll = || poll_xreadgroup(store, group, consumer, count, noack, keys, ids);
+    let Some(block) = block.filter(|_| can_block) else {
+        return match poll().await {
+            Ok(reply) => reply.unwrap_or(RespValue::NullArray),
+            Err(e) => RespValue::Error(e.to_string()),
+        };
+    };
+    let deadline = (block > 0).then(|| Instant::now() + Duration::from_millis(block));
+    let notifiers = store.get_notifiers(keys).await;
+    loop {
+        // Delivery moves the group's cursor, so every key is re-read on each wakeup.
+        match wait_for_keys(&notifiers, deadline, poll()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(reply)) => return reply,
+            Err(e) => return RespValue::Error(e.to_string()),
+        }
+    }
+}
+
+pub async fn xack(store: &Arc<Store>, key: &str, group: &str, ids: &[StreamID]) -> RespValue {
+    match store.xack(key, group, ids).await {
+        Ok(acked) => RespValue::Integer(acked as i64),
+        Err(e) => RespValue::Error(e.to_string()),
+    }
+}
+
+pub async fn xclaim(
+    store: &Arc<Store>,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    min_idle_ms: u64,
+    ids: &[StreamID],
+    options: ClaimOptions,
+) -> RespValue {
+    match store
+        .xclaim(key, group, consumer, min_idle_ms, ids, &options)
+        .await
+    {
+        Ok(claimed) if options.justid => RespValue::Array(
+            claimed
+                .into_iter()
+                .map(|(id, _)| RespValue::BulkString(Some(id.to_string().into_bytes())))
+                .collect(),
+        ),
+        Ok(claimed) => RespValue::Array(encode_stream(claimed)),
+        Err(e) => RespValue::Error(e.to_string()),
+    }
+}
+
+pub async fn xpending(
+    store: &Arc<Store>,
+    key: &str,
+    group: &str,
+    range: Option<PendingRange>,
+) -> RespValue {
+    let pending = match store.xpending(key, group).await {
+        Ok(pending) => pending,
+        Err(e) => return RespValue::Error(e.to_string()),
+    };
+    let bulk = |value: String| RespValue::BulkString(Some(value.into_bytes()));
+
+    let Some(range) = range else {
+        // Summary form: total, lowest and highest ID, then a count per consumer.
+        let (Some((first, _)), Some((last, _))) = (pending.first(), pending.last()) else {
+            return RespValue::Array(vec![
+                RespValue::Integer(0),
+                RespValue::BulkString(None),
+                RespValue::BulkString(None),
+                RespValue::NullArray,
+            ]);
+        };
+        let mut per_consumer: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, entry) in &pending {
+            *per_consumer.entry(&entry.consumer).or_default() += 1;
+        }
+        return RespValue::Array(vec![
+            RespValue::Integer(pending.len() as i64),
+            bulk(first.to_string()),
+            bulk(last.to_string()),
+            RespValue::Array(
+                per_consumer
+                    .into_iter()
+                    .map(|(consumer, count)| {
+                        RespValue::Array(vec![bulk(consumer.to_string()), bulk(count.to_string())])
+                    })
+                    .collect(),
+            ),
+        ]);
+    };
+
+    let rows = pending
+        .into_iter()
+        .filter(|(id, _)| range.start.is_none_or(|start| *id >= start))
+        .filter(|(id, _)| range.end.is_none_or(|end| *id <= end))
+        .filter(|(_, entry)| {
+            range
+                .consumer
+                .as_ref()
+                .is_none_or(|consumer| entry.consumer == *consumer)
+        })
+        .map(|(id, entry)| (id, entry.delivered_at.elapsed().as_millis(), entry))
+        .filter(|(_, idle, _)| range.min_idle_ms.is_none_or(|min| *idle >= min as u128))
+        .take(range.count)
+        .map(|(id, idle, entry)| {
+            RespValue::Array(vec![
+                bulk(id.to_string()),
+                bulk(entry.consumer),
+                RespValue::Integer(idle as i64),
+                RespValue::Integer(entry.delivery_count as i64),
+            ])
+        })
+        .collect();
+    RespValue::Array(rows)
+}
@@ -0,0 +1,87 @@
+use std::{io, sync::Arc};
+
+use crate::{
+    command::{FieldExpiry, LcsOptions},
+    resp::RespValue,
+    shared_store::shared_store::Store,
+};
+
+pub async fn lcs(
+    store: &Arc<Store>,
+    key1: String,
+    key2: String,
+    options: LcsOptions,
+) -> io::Result<Option<RespValue>> {
+    let lcs = match store.lcs(&key1, &key2).await {
+        Ok(lcs) => lcs,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
+    if options.len {
+        return Ok(Some(RespValue::Integer(lcs.sequence.len() as i64)));
+    }
+    if !options.idx {
+        return Ok(Some(RespValue::BulkString(Some(lcs.sequence))));
+    }
+
+    let range = |(start, end): (usize, usize)| {
+        RespValue::Array(vec![
+            RespValue::Integer(start as i64),
+            RespValue::Integer(end as i64),
+        ])
+    };
+    let matches = lcs
+        .matches
+        .iter()
+        .filter(|run| run.len() >= options.min_match_len)
+        .map(|run| {
+            let mut entry = vec![range(run.a), range(run.b)];
+            if options.with_match_len {
+                entry.push(RespValue::Integer(run.len() as i64));
+            }
+            RespValue::Array(entry)
+        })
+        .collect();
+    Ok(Some(RespValue::Array(vec![
+        RespValue::BulkString(Some(b"matches".to_vec())),
+        RespValue::Array(matches),
+        RespValue::BulkString(Some(b"len".to_vec())),
+        RespValue::Integer(lcs.sequence.len() as i64),
+    ])))
+}
+
+pub async fn append(store: &Arc<Store>, key: String, value: Vec<u8>) -> io::Result<Option<RespValue>> {
+    match store.append(&key, &value).await {
+        Ok(len) => Ok(Some(RespValue::Integer(len as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn setrange(
+    store: &Arc<Store>,
+    key: String,
+    offset: usize,
+    value: Vec<u8>,
+) -> io::Result<Option<RespValue>> {
+    match store.setrange(&key, offset, &value).await {
+        Ok(len) => Ok(Some(RespValue::Integer(len as i64))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn getdel(store: &Arc<Store>, key: String) -> io::Result<Option<RespValue>> {
+    match store.getdel(&key).await {
+        Ok(value) => Ok(Some(RespValue::BulkString(value))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
+
+pub async fn getex(
+    store: &Arc<Store>,
+    key: String,
+    expiry: Option<FieldExpiry>,
+) -> io::Result<Option<RespValue>> {
+    match store.getex(&key, expiry).await {
+        Ok(value) => Ok(Some(RespValue::BulkString(value))),
+        Err(e) => Ok(Some(RespValue::Error(e.to_string()))),
+    }
+}
@@ -4,33 +4,40 @@ use std::{sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 
 use crate::{
-    command::{ReplconfCommand, RespCommand},
     resp::RespValue,
-    replication_manager::manager::ReplicationManager,
-    shared_store::shared_store::Store,
+    replication_manager::manager::{getack_bytes, ReplicationManager},
 };
 
 pub async fn wait_command(
-    store: &Arc<Store>,
     manager: &Arc<Mutex<ReplicationManager>>,
     required_replicas: String,
     timeout_ms: String,
 ) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
-    let offset = store.get_offset().await;
+    let not_an_integer = |_| "ERR value is not an integer or out of range";
+    let required: usize = required_replicas.parse().map_err(not_an_integer)?;
+    // Zero means block until enough replicas acknowledge, however long that takes.
+    let timeout_ms: u64 = timeout_ms.parse().map_err(not_an_integer)?;
     let mut elapsed = 0;
     let poll_interval = 250;
-    let ack_command = RespCommand::ReplconfCommand(ReplconfCommand::Getack("*".into()));
 
-    {
-        let guard = manager.lock().await;
-        guard.send_to_replicas(ack_command.clone()).await?;
-    }
+    // Replicas acknowledge the offset before the GETACK itself, so only writes count.
+    let offset = {
+        let mut guard = manager.lock().await;
+        let offset = guard.write_offset();
+        // Skip the round trip when enough replicas have already caught up.
+        let acked = guard.replica_count(offset).await?;
+        if acked >= required {
+            return Ok(Some(RespValue::Integer(acked as i64)));
+        }
+        guard.request_acks(getack_bytes()).await?;
+        offset
+    };
     loop {
         let acked = {
             let manager = manager.lock().await;
-            manager.replica_count(offset as u64).await?
+            manager.replica_count(offset).await?
         };
-        if acked >= required_replicas.parse()? || elapsed >= timeout_ms.parse()? {
+        if acked >= required || (timeout_ms > 0 && elapsed >= timeout_ms) {
             break Ok(Some(RespValue::Integer(acked as i64)));
         }
 
@@ -13,7 +13,10 @@ pub async fn xrange_command(
     start: Option<String>,
     end: Option<String>,
 ) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
-    let resp = store.xrange(key, start, end).await?;
+    let resp = match store.xrange(key, start, end).await {
+        Ok(resp) => resp,
+        Err(e) => return Ok(Some(RespValue::Error(e.to_string()))),
+    };
     let outer = stream::encode_stream(resp);
     Ok(Some(RespValue::Array(outer)))
 }
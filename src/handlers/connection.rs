@@ -0,0 +1,7 @@
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// What a client connection runs over: TCP, the `--unixsocket` socket, or anything else
+/// that moves bytes both ways. Everything past the accept loop is generic over this.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Transport for T {}
@@ -18,6 +18,7 @@ pub fn encode_geo(long: f64, lat: f64) -> u64 {
     result
 }
 
+#[allow(dead_code)]
 fn decode_geo(value: u64) -> (f64, f64) {
     let mut long_bits = 0u32;
     let mut lat_bits = 0u32;
@@ -41,10 +42,12 @@ fn normalized_long(long: f64) -> u32 {
     normal as u32
 }
 
+#[allow(dead_code)]
 fn denormalize_latitude(normalized: u32) -> f64 {
     MIN_LATITUDE + (normalized as f64 / 2.0_f64.powi(26)) * LATITUDE_RANGE
 }
 
+#[allow(dead_code)]
 fn denormalize_longitude(normalized: u32) -> f64 {
     MIN_LONGITUDE + (normalized as f64 / 2.0_f64.powi(26)) * LONGITUDE_RANGE
 }
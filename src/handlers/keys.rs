@@ -5,9 +5,6 @@ use crate::{
     shared_store::shared_store::Store,
 };
 
-pub async fn keys_command(command: String, store: Arc<Store>) -> RespValue {
-    match command.as_str() {
-        "*" => store.keys().await,
-        _ => RespValue::Array(vec![]),
-    }
+pub async fn keys_command(pattern: String, store: Arc<Store>) -> RespValue {
+    store.keys(pattern.as_bytes()).await
 }
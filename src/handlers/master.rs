@@ -1,33 +1,67 @@
+use std::{future::Future, pin::Pin, time::Instant};
+
+use bytes::BytesMut;
 use futures::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
+use tokio_util::codec::Encoder;
 
 use crate::{
-    command::{self, RespCommand},
+    log,
+    aof::AofEntry,
+    command::{self, PubSubCommand, RespCommand},
+    command_table,
     handlers::{
         client::{Client, ClientMode},
+        connection::Transport,
         command_handlers::{
-            config,
+            auth, command_info, config, debug, eval, function, hashes, help, keyspace, latency,
+            memory, object,
             list::{self},
-            psync, set, stream, type_command, wait, xadd, xrange,
+            psync, set, sets, stream, stream_group, strings, type_command, wait, xadd, xrange,
         },
         geo::{encode_geo},
         replication::handle_replconf_command,
         session::Session,
     },
-    resp::RespValue,
+    resp::{RespCodec, RespValue},
+    scripting::ScriptHost,
     server_context::ServerContext,
 };
 
-pub async fn handle_master_connection(
-    socket: TcpStream,
+pub async fn handle_master_connection<S: Transport>(
+    socket: S,
+    addr: String,
     context: ServerContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = Client::new(socket);
+    let mut client = Client::new(socket, addr, context.acl.default_is_open());
     let mut session = Session::new();
 
     while let Some(result) = client.framed.next().await {
         let (resp_value, bytes) = result?;
-        let command: command::RespCommand = command::Command::try_from_resp(resp_value)?;
+        // A command that doesn't parse is answered with the error; the connection stays up.
+        let command = match command::Command::try_from_resp(resp_value) {
+            Ok(command) => command,
+            Err(e) => {
+                client.framed.send(RespValue::Error(e.to_string())).await?;
+                continue;
+            }
+        };
+
+        if !client.authenticated
+            && !matches!(
+                command,
+                RespCommand::Auth { .. }
+                    | RespCommand::Hello { .. }
+                    | RespCommand::Quit
+                    | RespCommand::Reset
+            )
+        {
+            client
+                .framed
+                .send(RespValue::Error("NOAUTH Authentication required.".into()))
+                .await?;
+            continue;
+        }
 
         if let RespCommand::PSYNC(string, pos) = command.clone() {
             psync::psync_command(
@@ -47,30 +81,53 @@ pub async fn handle_master_connection(
                 handle_normal_mode(&mut client, &mut session, command, bytes, &context).await?;
             }
             ClientMode::Subscribed => {
-                handle_subscribed_mode(&mut client, command, &context).await?;
+                handle_subscribed_mode(&mut client, command, bytes, &context).await?;
             }
             ClientMode::Multi => {
                 handle_multi_mode(&mut client, &mut session, command, bytes, &context).await?;
             }
         }
+        if client.close_after_reply {
+            break;
+        }
     }
 
     Ok(())
 }
 
-async fn handle_normal_mode(
-    client: &mut Client,
-    _session: &mut Session,
+async fn handle_normal_mode<S: Transport>(
+    client: &mut Client<S>,
+    session: &mut Session,
     command: RespCommand,
     bytes: Vec<u8>,
     context: &ServerContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match command {
-        RespCommand::Subscribe(channel_name) => {
+        RespCommand::Subscribe(_) | RespCommand::PSubscribe(_) | RespCommand::SSubscribe(_) => {
             client.mode = ClientMode::Subscribed;
-            run_subscribed_loop(client, context, channel_name).await?;
+            run_subscribed_loop(client, context, command).await?;
             return Ok(()); // Break the loop after subscribe
         }
+        RespCommand::Auth { username, password } => {
+            let reply = auth::auth(client, &context.acl, username.as_deref(), &password);
+            client.framed.send(reply).await?;
+        }
+        RespCommand::Hello { protover, auth } => {
+            let reply = auth::hello(client, &context.acl, &context.info, protover, auth);
+            client.framed.send(reply).await?;
+        }
+        RespCommand::Acl(command) => {
+            let reply = auth::acl_command(client, &context.acl, command);
+            client.framed.send(reply).await?;
+        }
+        RespCommand::SUnsubscribe(channel_names) => {
+            sunsubscribe_from_channels(context, channel_names, client).await?;
+        }
+        RespCommand::Quit => quit(client).await?,
+        RespCommand::Reset => {
+            session.reset();
+            reset_client(client, context).await?;
+        }
         RespCommand::Multi => {
             client.mode = ClientMode::Multi;
             client
@@ -79,9 +136,23 @@ async fn handle_normal_mode(
                 .await?;
         }
         _ => {
-            let response =
-                process_command(context, command, bytes, &mut Some(client.addr.to_string()))
-                    .await?;
+            // Blocking commands go without the script lock, so a waiting BLPOP can't hold
+            // scripts off.
+            let _guard = match latency_event(&command) {
+                Some(_) => Some(script_guard(context, [&command]).await),
+                None => None,
+            };
+            // A failing command is answered with its error; only the socket's own
+            // errors end the connection.
+            let response = process_command(
+                context,
+                command,
+                bytes,
+                &mut Some(client.addr.to_string()),
+                client.protocol,
+            )
+            .await
+            .unwrap_or_else(|e| Some(RespValue::Error(e.to_string())));
             if let Some(response) = response {
                 client.framed.send(response).await?;
             }
@@ -90,14 +161,26 @@ async fn handle_normal_mode(
     Ok(())
 }
 
-async fn handle_subscribed_mode(
-    client: &mut Client,
+async fn handle_subscribed_mode<S: Transport>(
+    client: &mut Client<S>,
     command: RespCommand,
+    bytes: Vec<u8>,
     context: &ServerContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match command {
-        RespCommand::Subscribe(channel_name) => {
-            subscribe_to_channel(context, channel_name, client).await?;
+        RespCommand::Subscribe(channel_names) => {
+            // One confirmation per channel, in argument order. Messages queue on the
+            // client's receiver meanwhile, so none can overtake a confirmation.
+            for channel_name in channel_names {
+                subscribe_to_channel(context, channel_name, client).await?;
+            }
+        }
+        RespCommand::Ping if client.protocol == 3 => {
+            // RESP3 can interleave replies with pushes, so PING answers normally.
+            client
+                .framed
+                .send(RespValue::SimpleString("PONG".into()))
+                .await?;
         }
         RespCommand::Ping => {
             let response = vec![
@@ -111,31 +194,34 @@ async fn handle_subscribed_mode(
             // TODO: Implement unsubscribe logic
             unsubscribe_from_channel(context, channel_name, client).await?;
         }
-        RespCommand::PSubscribe => {
-            // TODO: Implement psubscribe logic
-            client
-                .framed
-                .send(RespValue::SimpleString("OK".into()))
-                .await?;
+        RespCommand::PSubscribe(patterns) => {
+            for pattern in patterns {
+                psubscribe_to_pattern(context, pattern, client).await?;
+            }
         }
-        RespCommand::PunSubscribe => {
-            // TODO: Implement punsubscribe logic
-            client
-                .framed
-                .send(RespValue::SimpleString("OK".into()))
-                .await?;
+        RespCommand::PunSubscribe(pattern) => {
+            punsubscribe_from_pattern(context, pattern, client).await?;
         }
-        RespCommand::Quit => {
-            // TODO: Implement quit logic
-            client
-                .framed
-                .send(RespValue::SimpleString("OK".into()))
+        RespCommand::SSubscribe(channel_names) => {
+            for channel_name in channel_names {
+                ssubscribe_to_channel(context, channel_name, client).await?;
+            }
+        }
+        RespCommand::SUnsubscribe(channel_names) => {
+            sunsubscribe_from_channels(context, channel_names, client).await?;
+        }
+        RespCommand::Quit => quit(client).await?,
+        RespCommand::Reset => {
+            // Drops every subscription, so the subscribed loop ends after this.
+            reset_client(client, context).await?;
+        }
+        // RESP3 tells replies and pushes apart, so a subscribed client may run anything
+        // else, including a PUBLISH it receives itself. Transactions stay out of reach.
+        command if client.protocol == 3 && !matches!(command, RespCommand::Multi) => {
+            // Boxed: normal mode can itself enter the subscribed loop.
+            Box::pin(handle_normal_mode(client, &mut Session::new(), command, bytes, context))
                 .await?;
         }
-        // RespCommand::Reset => {
-        //     // TODO: Implement reset logic
-        //     client.framed.send(RespValue::SimpleString("OK".into())).await?;
-        // }
         _ => {
             let error_message = format!(
                 "ERR Can't execute '{command}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context"
@@ -146,8 +232,8 @@ async fn handle_subscribed_mode(
     Ok(())
 }
 
-async fn handle_multi_mode(
-    client: &mut Client,
+async fn handle_multi_mode<S: Transport>(
+    client: &mut Client<S>,
     session: &mut Session,
     command: RespCommand,
     bytes: Vec<u8>,
@@ -156,20 +242,33 @@ async fn handle_multi_mode(
     match command {
         RespCommand::Exec => {
             client.mode = ClientMode::Normal;
+            if session.dirty {
+                session.reset();
+                client
+                    .framed
+                    .send(RespValue::Error(
+                        "EXECABORT Transaction discarded because of previous errors.".into(),
+                    ))
+                    .await?;
+                return Ok(());
+            }
             if session.queued.is_empty() {
                 client.framed.send(RespValue::Array(vec![])).await?;
                 return Ok(());
             }
             let mut responses = Vec::new();
             let queue = &session.queued.clone();
+            let _guard = script_guard(context, queue.iter().map(|(command, _)| command)).await;
             for (queued_command, bytes) in queue {
-                let response = process_command(
+                let response = process_without_blocking(
                     context,
                     queued_command.clone(),
                     bytes.clone(),
                     &mut Some(client.addr.to_string()),
+                    client.protocol,
                 )
-                .await?;
+                .await
+                .unwrap_or_else(|e| Some(RespValue::Error(e.to_string())));
 
                 if let Some(resp) = response {
                     responses.push(resp);
@@ -177,7 +276,36 @@ async fn handle_multi_mode(
             }
 
             client.framed.send(RespValue::Array(responses)).await?;
-            session.queued.clear();
+            session.reset();
+        }
+        RespCommand::Subscribe(_)
+        | RespCommand::Unsubscribe(_)
+        | RespCommand::PSubscribe(_)
+        | RespCommand::PunSubscribe(_)
+        | RespCommand::SSubscribe(_)
+        | RespCommand::SUnsubscribe(_) => {
+            session.dirty = true;
+            client
+                .framed
+                .send(RespValue::Error(format!(
+                    "ERR {command} is not allowed in transactions"
+                )))
+                .await?;
+        }
+        RespCommand::Quit => quit(client).await?,
+        RespCommand::Reset => {
+            // RESET is never queued: it throws the transaction away along with the rest.
+            session.reset();
+            reset_client(client, context).await?;
+        }
+        RespCommand::Auth { .. } | RespCommand::Hello { .. } | RespCommand::Acl(_) => {
+            session.dirty = true;
+            client
+                .framed
+                .send(RespValue::Error(
+                    "ERR Command not allowed inside a transaction".into(),
+                ))
+                .await?;
         }
         RespCommand::Discard => {
             client.mode = ClientMode::Normal;
@@ -185,7 +313,7 @@ async fn handle_multi_mode(
                 .framed
                 .send(RespValue::SimpleString("OK".into()))
                 .await?;
-            session.queued.clear();
+            session.reset();
         }
         _ => {
             session.queued.push((command, bytes));
@@ -198,40 +326,270 @@ async fn handle_multi_mode(
     Ok(())
 }
 
+/// Runs a command from EXEC or a script. Blocking commands can't stall either: they run
+/// as if their timeout had already expired.
+async fn process_without_blocking(
+    context: &ServerContext,
+    command: RespCommand,
+    bytes: Vec<u8>,
+    peer_addr: &mut Option<String>,
+    protocol: i64,
+) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
+    // The commands handled here skip process_command, so they are logged here too.
+    let aof_entry = match (&context.aof, &command) {
+        (Some(_), RespCommand::BLPop(..) | RespCommand::XReadGroup { .. }) => {
+            Some(AofEntry::for_command(&command, &bytes))
+        }
+        _ => None,
+    };
+    let _order = match (&context.aof, &aof_entry) {
+        (Some(aof), Some(_)) => Some(aof.order_write().await),
+        _ => None,
+    };
+    let response = match command {
+        RespCommand::BLPop(keys, _) => list::blpop::blpop_now(&context.store, &keys).await?,
+        RespCommand::Xread {
+            count, keys, ids, ..
+        } => stream::xread_command(&context.store, count, &None, &keys, &ids).await?,
+        RespCommand::XReadGroup {
+            group,
+            consumer,
+            count,
+            noack,
+            keys,
+            ids,
+            ..
+        } => Some(
+            stream_group::xreadgroup(
+                &context.store,
+                &group,
+                &consumer,
+                count,
+                None,
+                noack,
+                &keys,
+                &ids,
+            )
+            .await,
+        ),
+        command => process_command(context, command, bytes, peer_addr, protocol).await?,
+    };
+    if let (Some(aof), Some(entry)) = (&context.aof, aof_entry) {
+        aof.record(entry, &response);
+    }
+    Ok(response)
+}
+
+/// Keeps the guard `script_guard` took for as long as the command runs.
+struct ScriptGuard<'a> {
+    _shared: Option<RwLockReadGuard<'a, ()>>,
+    _exclusive: Option<RwLockWriteGuard<'a, ()>>,
+}
+
+/// Scripts run atomically: EVAL, EVALSHA and FCALL take the script lock exclusively and
+/// every other client command shares it. A transaction holding a script takes it
+/// exclusively for the whole EXEC.
+async fn script_guard<'a, 'c>(
+    context: &'a ServerContext,
+    commands: impl IntoIterator<Item = &'c RespCommand>,
+) -> ScriptGuard<'a> {
+    let runs_script = commands.into_iter().any(|command| {
+        matches!(
+            command,
+            RespCommand::Eval { .. } | RespCommand::EvalSha { .. } | RespCommand::FCall
+        )
+    });
+    if runs_script {
+        ScriptGuard {
+            _shared: None,
+            _exclusive: Some(context.script_lock.write().await),
+        }
+    } else {
+        ScriptGuard {
+            _shared: Some(context.script_lock.read().await),
+            _exclusive: None,
+        }
+    }
+}
+
+/// Lets `redis.call` reach the keyspace through the same dispatch as clients.
+struct ScriptContext<'a> {
+    context: &'a ServerContext,
+}
+
+impl ScriptHost for ScriptContext<'_> {
+    fn call<'a>(
+        &'a self,
+        args: Vec<Vec<u8>>,
+    ) -> Pin<Box<dyn Future<Output = RespValue> + Send + 'a>> {
+        Box::pin(async move {
+            let name = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+            let request = || {
+                RespValue::Array(
+                    args.iter()
+                        .map(|arg| RespValue::BulkString(Some(arg.clone())))
+                        .collect(),
+                )
+            };
+            // Keep the encoded form so writes made by the script reach the AOF.
+            let mut bytes = BytesMut::new();
+            if RespCodec.encode(request(), &mut bytes).is_err() {
+                return RespValue::Error("ERR Unable to encode command".into());
+            }
+            let command = match command::Command::try_from_resp(request()) {
+                Ok(command) => command,
+                Err(_) if command_table::lookup(&name).is_none() => {
+                    return RespValue::Error("ERR Unknown Redis command called from script".into())
+                }
+                Err(e) => return RespValue::Error(e.to_string()),
+            };
+            if matches!(
+                command,
+                RespCommand::Subscribe(_)
+                    | RespCommand::PSubscribe(_)
+                    | RespCommand::Unsubscribe(_)
+                    | RespCommand::PunSubscribe(_)
+                    | RespCommand::SSubscribe(_)
+                    | RespCommand::SUnsubscribe(_)
+                    | RespCommand::Reset
+                    | RespCommand::Multi
+                    | RespCommand::Exec
+                    | RespCommand::Discard
+                    | RespCommand::Eval { .. }
+                    | RespCommand::EvalSha { .. }
+                    | RespCommand::Auth { .. }
+                    | RespCommand::Hello { .. }
+                    | RespCommand::Acl(_)
+                    | RespCommand::Wait(_, _)
+                    | RespCommand::PSYNC(_, _)
+                    | RespCommand::Quit
+            ) {
+                return RespValue::Error("ERR This Redis command is not allowed from script".into());
+            }
+            // Scripts always see RESP2 replies.
+            let reply =
+                process_without_blocking(self.context, command, bytes.to_vec(), &mut None, 2).await;
+            match reply {
+                Ok(Some(reply)) => reply,
+                Ok(None) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(e.to_string()),
+            }
+        })
+    }
+}
+
+/// Applies a command read back from the append-only file.
+pub async fn replay_command(
+    context: &ServerContext,
+    command: RespCommand,
+    bytes: Vec<u8>,
+) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
+    process_command(context, command, bytes, &mut None, 2).await
+}
+
 async fn process_command(
     context: &ServerContext,
     command: RespCommand,
     bytes: Vec<u8>,
     peer_addr: &mut Option<String>,
+    protocol: i64,
 ) -> Result<Option<RespValue>, Box<dyn std::error::Error>> {
+    // A BLPOP that may block logs its own pop, so it never holds up a rewrite while it
+    // waits.
+    let aof_entry = match &context.aof {
+        Some(_) if matches!(command, RespCommand::BLPop(..)) => None,
+        Some(_) if command.is_write() => Some(AofEntry::for_command(&command, &bytes)),
+        _ => None,
+    };
+    let event = latency_event(&command);
+    // A blocking XREADGROUP would stall every other write behind a pending rewrite.
+    let _order = match (&context.aof, &aof_entry, event) {
+        (Some(aof), Some(_), Some(_)) => Some(aof.order_write().await),
+        _ => None,
+    };
+    let started = Instant::now();
     let response_value = match command {
         RespCommand::Ping => Some(RespValue::SimpleString("PONG".into())),
         RespCommand::Publish(channel, msg) => {
             let amount = context.store.send_to_channel(channel, msg).await?;
             Some(RespValue::Integer(amount as i64))
         }
+        RespCommand::SPublish(channel, msg) => {
+            let amount = context.store.send_to_shard_channel(channel, msg).await;
+            Some(RespValue::Integer(amount as i64))
+        }
         RespCommand::Geoadd {
             key,
             long,
             lat,
             member,
         } => {
-            if long > 180.00 || long < -180.00 || lat > 85.05112878 || lat < -85.05112878 {
+            if !(-180.00..=180.00).contains(&long) || !(-85.05112878..=85.05112878).contains(&lat) {
                 let err = format!("ERR invalid longitude,latitude pair {}, {}", long, lat);
                 Some(RespValue::Error(err))
             } else {
                 let encoded = encode_geo(long, lat);
-                println!("{} {}", encoded, encoded as f64);
-                let result = context.store.zadd(key, encoded as f64, member).await?;
-                Some(RespValue::Integer(result))
+                log::debug!("{} {}", encoded, encoded as f64);
+                match context.store.zadd(key, encoded as f64, member).await {
+                    Ok(result) => Some(RespValue::Integer(result)),
+                    Err(e) => Some(RespValue::Error(e.to_string())),
+                }
             }
         }
 
         RespCommand::Echo(s) => Some(RespValue::BulkString(Some(s.into_bytes()))),
+        RespCommand::Copy {
+            source,
+            destination,
+            db,
+            replace,
+        } => keyspace::copy(&context.store, source, destination, db, replace).await?,
+        RespCommand::ExpireAt {
+            key,
+            unix_ms,
+            condition,
+        } => keyspace::expire_at(&context.store, key, unix_ms, condition).await?,
+        RespCommand::Persist(key) => keyspace::persist(&context.store, key).await?,
+        RespCommand::Move(_key, db) => Some(keyspace::move_key(db)),
+        RespCommand::Del(keys) => {
+            keyspace::del(&context.store, &context.manager, keys, bytes).await?
+        }
+        RespCommand::Exists(keys) => keyspace::exists(&context.store, keys).await?,
+        RespCommand::Rename(key, newkey) => keyspace::rename(&context.store, key, newkey).await?,
+        RespCommand::Object(command) => {
+            object::object_command(command, &context.store, &context.rdb).await?
+        }
+        RespCommand::Memory(command) => memory::memory_command(command, &context.store).await?,
+        RespCommand::PubSub(PubSubCommand::Channels(pattern)) => {
+            let channels = context.store.active_channels(pattern.as_deref()).await;
+            Some(RespValue::Array(
+                channels
+                    .into_iter()
+                    .map(|name| RespValue::BulkString(Some(name)))
+                    .collect(),
+            ))
+        }
+        RespCommand::Sort(key, options) => keyspace::sort(&context.store, key, options).await?,
+        RespCommand::Lcs { key1, key2, options } => {
+            strings::lcs(&context.store, key1, key2, options).await?
+        }
+        RespCommand::Lolwut(_version) => {
+            // Every version gets the same art; real Redis varies it per release.
+            let art = format!(
+                " _._\n\
+                 (o o)   LOLWUT\n\
+                 ( v )\n\
+                 \n\
+                 Redis ver. {}\n",
+                context.info.redis_version
+            );
+            Some(RespValue::BulkString(Some(art.into_bytes())))
+        }
         RespCommand::Exec => Some(RespValue::Error("ERR EXEC without MULTI".into())),
         RespCommand::Discard => Some(RespValue::Error("ERR DISCARD without MULTI".into())),
         RespCommand::BLPop(keys, timeout) => {
-            list::blpop::blpop_command(&context.store, &keys, timeout).await?
+            list::blpop::blpop_command(&context.store, context.aof.as_deref(), &keys, timeout)
+                .await?
         }
 
         RespCommand::Llen(key) => list::llen(context.store.clone(), key).await?,
@@ -246,58 +604,170 @@ async fn process_command(
             list::lrange(context.store.clone(), key, start, end).await?
         }
 
-        RespCommand::Zadd(key, rank, value) => {
-            let result = context.store.zadd(key, rank, value).await?;
-            Some(RespValue::Integer(result))
+        RespCommand::Hset { key, fields } => hashes::hset(&context.store, key, fields).await?,
+        RespCommand::HgetDel { key, fields } => hashes::hgetdel(&context.store, key, fields).await?,
+        RespCommand::HgetEx {
+            key,
+            expiry,
+            fields,
+        } => hashes::hgetex(&context.store, key, expiry, fields).await?,
+        RespCommand::HExpire {
+            key,
+            unix_ms,
+            condition,
+            fields,
+        } => hashes::hexpire(&context.store, key, unix_ms, condition, fields).await?,
+        RespCommand::HTtl {
+            key,
+            unit_ms,
+            fields,
+        } => hashes::httl(&context.store, key, unit_ms, fields).await?,
+        RespCommand::HsetNx { key, field, value } => {
+            hashes::hsetnx(&context.store, key, field, value).await?
+        }
+        RespCommand::HrandField {
+            key,
+            count,
+            with_values,
+        } => hashes::hrandfield(&context.store, key, count, with_values).await?,
+        RespCommand::HGetAll(key) => hashes::hgetall(&context.store, key, protocol).await?,
+        RespCommand::HKeys(key) => hashes::hkeys_or_vals(&context.store, key, false).await?,
+        RespCommand::HVals(key) => hashes::hkeys_or_vals(&context.store, key, true).await?,
+        RespCommand::Sadd { key, members } => sets::sadd(&context.store, key, members).await?,
+        RespCommand::SRandMember { key, count } => {
+            sets::srandmember(&context.store, key, count).await?
+        }
+        RespCommand::SmIsMember { key, members } => {
+            sets::smismember(&context.store, key, members).await?
+        }
+        RespCommand::SinterCard { keys, limit } => {
+            sets::sintercard(&context.store, keys, limit).await?
+        }
+
+        RespCommand::Zadd {
+            key,
+            options,
+            mut members,
+        } => {
+            if options.incr {
+                let (increment, member) = members.remove(0);
+                match context
+                    .store
+                    .zadd_incr(key, &options, increment, member)
+                    .await
+                {
+                    Ok(Some(score)) => Some(RespValue::BulkString(Some(score.to_string().into()))),
+                    Ok(None) => Some(RespValue::BulkString(None)),
+                    Err(e) => Some(RespValue::Error(e.to_string())),
+                }
+            } else {
+                match context.store.zadd_members(key, &options, members).await {
+                    Ok(result) => Some(RespValue::Integer(result)),
+                    Err(e) => Some(RespValue::Error(e.to_string())),
+                }
+            }
         }
         RespCommand::Zcard(key) => {
-            let result = context.store.zcard(key).await?;
-            Some(RespValue::Integer(result))
+            match context.store.zcard(key).await {
+                Ok(result) => Some(RespValue::Integer(result)),
+                Err(e) => Some(RespValue::Error(e.to_string())),
+            }
         }
         RespCommand::Zrange(key, start, stop) => {
-            let result = context.store.zrange(key, start, stop).await?;
-            let mut response = vec![];
-            for ret in result {
-                response.push(RespValue::BulkString(Some(ret.into())))
+            match context.store.zrange(key, start, stop).await {
+                Ok(result) => Some(RespValue::Array(
+                    result
+                        .into_iter()
+                        .map(|member| RespValue::BulkString(Some(member.into())))
+                        .collect(),
+                )),
+                Err(e) => Some(RespValue::Error(e.to_string())),
+            }
+        }
+        RespCommand::ZrangeByLex(key, min, max) => {
+            match context.store.zrangebylex(key, min, max).await {
+                Ok(members) => Some(RespValue::Array(
+                    members
+                        .into_iter()
+                        .map(|member| RespValue::BulkString(Some(member.into())))
+                        .collect(),
+                )),
+                Err(e) => Some(RespValue::Error(e.to_string())),
             }
-            Some(RespValue::Array(response))
         }
         RespCommand::ZScore(key, value) => {
-            if let Some(result) = context.store.zscore(key, value).await? {
-                let string_msg = result.to_string();
-                Some(RespValue::BulkString(Some(string_msg.into())))
-            } else {
-                Some(RespValue::BulkString(None))
+            match context.store.zscore(key, value).await {
+                Ok(result) => Some(RespValue::BulkString(
+                    result.map(|score| score.to_string().into()),
+                )),
+                Err(e) => Some(RespValue::Error(e.to_string())),
             }
         }
         RespCommand::Zrank(key, value) => {
-            let result = context.store.zrank_command(key, value).await?;
-            if let Some(result) = result {
-                Some(RespValue::Integer(result as i64))
-            } else {
-                Some(RespValue::BulkString(None))
+            match context.store.zrank_command(key, value).await {
+                Ok(Some(result)) => Some(RespValue::Integer(result as i64)),
+                Ok(None) => Some(RespValue::BulkString(None)),
+                Err(e) => Some(RespValue::Error(e.to_string())),
             }
         }
         RespCommand::ZRem(key, value) => {
-            let result = context.store.zrem(key, value).await?;
-            if let Some(result) = result {
-                Some(RespValue::Integer(result))
-            } else {
-                Some(RespValue::Integer(0))
+            match context.store.zrem(key, value).await {
+                Ok(result) => Some(RespValue::Integer(result.unwrap_or(0))),
+                Err(e) => Some(RespValue::Error(e.to_string())),
             }
         }
 
         RespCommand::Multi => Some(RespValue::Error("ERR MULTI calls can not be nested".into())),
+        RespCommand::BgRewriteAof => match &context.aof {
+            Some(aof) => {
+                // The snapshot is cheap; encoding and the file swap run on the AOF writer task.
+                aof.rewrite(&context.store).await?;
+                Some(RespValue::SimpleString(
+                    "Background append only file rewriting started".into(),
+                ))
+            }
+            None => Some(RespValue::Error("ERR Append only file is disabled".into())),
+        },
+        RespCommand::FlushAll => {
+            context.store.flush_all().await;
+            Some(RespValue::SimpleString("OK".into()))
+        }
         RespCommand::Incr(key) => context.store.incr(&key).await?,
         RespCommand::Get(key) => Some(context.store.get(&key).await?),
+        RespCommand::GetDel(key) => strings::getdel(&context.store, key).await?,
+        RespCommand::GetEx { key, expiry } => strings::getex(&context.store, key, expiry).await?,
+        RespCommand::Ttl { key, unit_ms } => keyspace::ttl(&context.store, key, unit_ms).await?,
         RespCommand::Set { key, value, px } => {
             set::set_command(&context.store, &context.manager, key, &value, px, bytes).await?
         }
+        RespCommand::Append { key, value } => strings::append(&context.store, key, value).await?,
+        RespCommand::SetRange { key, offset, value } => {
+            strings::setrange(&context.store, key, offset, value).await?
+        }
 
         RespCommand::Type(key) => type_command::type_command(&context.store, key).await?,
+        RespCommand::Function(command) => Some(function::function_command(command)),
+        RespCommand::FCall => Some(RespValue::Error("ERR Function not found".into())),
+        RespCommand::Eval { script, keys, args } => {
+            let host = ScriptContext { context };
+            Some(eval::eval(&context.scripts, &host, &script, keys, args).await)
+        }
+        RespCommand::Latency(command) => {
+            Some(latency::latency_command(&context.latency, command))
+        }
+        RespCommand::Script(command) => Some(eval::script_command(&context.scripts, command)),
+        RespCommand::EvalSha { sha, keys, args } => {
+            let host = ScriptContext { context };
+            Some(eval::evalsha(&context.scripts, &host, &sha, keys, args).await)
+        }
         RespCommand::ConfigCommand(command) => {
-            Some(config::config_command(command, context.rdb.clone()))
+            Some(config::config_command(command, context.rdb.clone(), &context.store))
         }
+        RespCommand::Command(command) => Some(command_info::command_command(command)),
+        RespCommand::Help(command) => Some(help::help_command(&command)),
+        RespCommand::Debug(command) => Some(
+            debug::debug_command(command, &context.store, &context.info, &context.rdb).await,
+        ),
         RespCommand::Keys(string) => {
             Some(super::keys::keys_command(string, context.store.clone()).await)
         }
@@ -311,7 +781,6 @@ async fn process_command(
         RespCommand::RDB(_) => None,
         RespCommand::Wait(required_replicas, timeout_ms) => {
             wait::wait_command(
-                &context.store,
                 &context.manager,
                 required_replicas,
                 timeout_ms,
@@ -326,82 +795,513 @@ async fn process_command(
             xrange::xrange_command(&context.store, key, start, end).await?
         }
         RespCommand::Xread {
-            count: _,
+            count,
             block,
             keys,
             ids,
-        } => stream::xread_command(&context.store, &block, &keys, &ids).await?,
-        _ => {
-            unimplemented!("{:?}", format!("{}", command))
+        } => stream::xread_command(&context.store, count, &block, &keys, &ids).await?,
+        RespCommand::XGroup(command) => Some(stream_group::xgroup(&context.store, command).await),
+        RespCommand::XReadGroup {
+            group,
+            consumer,
+            count,
+            block,
+            noack,
+            keys,
+            ids,
+        } => Some(
+            stream_group::xreadgroup(
+                &context.store,
+                &group,
+                &consumer,
+                count,
+                block,
+                noack,
+                &keys,
+                &ids,
+            )
+            .await,
+        ),
+        RespCommand::XAck { key, group, ids } => {
+            Some(stream_group::xack(&context.store, &key, &group, &ids).await)
         }
+        RespCommand::XPending { key, group, range } => {
+            Some(stream_group::xpending(&context.store, &key, &group, range).await)
+        }
+        RespCommand::XClaim {
+            key,
+            group,
+            consumer,
+            min_idle_ms,
+            ids,
+            options,
+        } => Some(
+            stream_group::xclaim(&context.store, &key, &group, &consumer, min_idle_ms, &ids, options)
+                .await,
+        ),
+        _ => Some(RespValue::Error(format!("ERR unsupported command '{command}'"))),
     };
 
+    if let Some(event) = event {
+        context.latency.observe(event, started.elapsed());
+    }
+    if let (Some(aof), Some(entry)) = (&context.aof, aof_entry) {
+        aof.record(entry, &response_value);
+    }
     Ok(response_value)
 }
 
-async fn run_subscribed_loop(
-    client: &mut Client,
+/// The LATENCY event a command's run time counts toward. Blocking commands wait by
+/// design, so they are left out.
+fn latency_event(command: &RespCommand) -> Option<&'static str> {
+    match command {
+        RespCommand::BLPop(..)
+        | RespCommand::Wait(..)
+        | RespCommand::Xread { block: Some(_), .. }
+        | RespCommand::XReadGroup { block: Some(_), .. } => None,
+        // Rewriting the AOF is where Redis would fork.
+        RespCommand::BgRewriteAof => Some("fork"),
+        _ => Some("command"),
+    }
+}
+
+async fn run_subscribed_loop<S: Transport>(
+    client: &mut Client<S>,
     context: &ServerContext,
-    channel_name: String,
+    first: RespCommand,
 ) -> anyhow::Result<()> {
-    _ = subscribe_to_channel(context, channel_name, client).await;
+    _ = handle_subscribed_mode(client, first, vec![], context).await;
+
+    let result = forward_subscribed(client, context).await;
+    // The connection is gone, so nothing should keep a Sender for it.
+    for channel_name in client.channels.drain(..) {
+        _ = context.store.unsubscribe(channel_name, client.id).await;
+    }
+    for pattern in client.patterns.drain(..) {
+        context.store.punsubscribe(pattern, client.id).await;
+    }
+    for channel_name in client.shard_channels.drain(..) {
+        context.store.sunsubscribe(channel_name, client.id).await;
+    }
+    result
+}
 
+async fn forward_subscribed<S: Transport>(client: &mut Client<S>, context: &ServerContext) -> anyhow::Result<()> {
     loop {
         tokio::select! {
-               Some(msg) = client.rx.recv() => {
-                   // send pub/sub message to client
-                   client.framed.send(msg).await?;
-               },
-               Some(Ok((resp_value, _bytes))) = client.framed.next() => {
-                        let command: command::RespCommand = command::Command::try_from_resp(resp_value)?;
-
-              _ = handle_subscribed_mode(client,  command, context).await;
-           },
-           else => break,
-        // both streams closed
-           }
+            Some(msg) = client.rx.recv() => {
+                // send pub/sub message to client
+                let msg = match msg {
+                    RespValue::Array(items) => client.push(items),
+                    other => other,
+                };
+                client.framed.send(msg).await?;
+            },
+            frame = client.framed.next() => {
+                // A closed socket or an undecodable frame ends the session.
+                let Some(Ok((resp_value, bytes))) = frame else {
+                    break;
+                };
+                let command = match command::Command::try_from_resp(resp_value) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        client.framed.send(RespValue::Error(e.to_string())).await?;
+                        continue;
+                    }
+                };
+
+                _ = handle_subscribed_mode(client, command, bytes, context).await;
+                if client.mode != ClientMode::Subscribed || client.close_after_reply {
+                    break;
+                }
+            },
+        }
     }
     Ok(())
 }
 
-async fn subscribe_to_channel(
+async fn subscribe_to_channel<S: Transport>(
     context: &ServerContext,
-    channel_name: String,
-    client: &mut Client,
+    channel_name: Vec<u8>,
+    client: &mut Client<S>,
 ) -> anyhow::Result<()> {
+    let limit = context.rdb.max_subscriptions_per_client;
+    if limit > 0 && client.channels.len() >= limit && !client.channels.contains(&channel_name) {
+        client
+            .framed
+            .send(RespValue::Error(format!(
+                "ERR max subscriptions per client reached ({limit})"
+            )))
+            .await?;
+        return Ok(());
+    }
     context
         .store
-        .subscribe(channel_name.clone(), client.addr, client.tx.clone())
+        .subscribe(channel_name.clone(), client.id, client.tx.clone())
         .await;
-    client.channels.push(channel_name.clone());
+    if !client.channels.contains(&channel_name) {
+        client.channels.push(channel_name.clone());
+    }
     let response = vec![
         RespValue::BulkString(Some("subscribe".into())),
-        RespValue::BulkString(Some(channel_name.into())),
-        RespValue::Integer(client.channels.len() as i64),
+        RespValue::BulkString(Some(channel_name)),
+        RespValue::Integer(client.subscription_count()),
     ];
-    if (client.framed.send(RespValue::Array(response)).await).is_err() {
+    if (client.framed.send(client.push(response)).await).is_err() {
         return Ok(()); // client disconnected immediately
     }
     Ok(())
 }
 
-async fn unsubscribe_from_channel(
+async fn unsubscribe_from_channel<S: Transport>(
     context: &ServerContext,
-    channel_name: String,
-    client: &mut Client,
+    channel_name: Vec<u8>,
+    client: &mut Client<S>,
 ) -> anyhow::Result<()> {
     _ = context
         .store
-        .unsubscribe(channel_name.clone(), client.addr)
+        .unsubscribe(channel_name.clone(), client.id)
         .await;
-    client.channels.pop();
+    client.channels.retain(|subscribed| *subscribed != channel_name);
     let response = vec![
         RespValue::BulkString(Some("unsubscribe".into())),
-        RespValue::BulkString(Some(channel_name.into())),
-        RespValue::Integer(client.channels.len() as i64),
+        RespValue::BulkString(Some(channel_name)),
+        RespValue::Integer(client.subscription_count()),
     ];
-    if (client.framed.send(RespValue::Array(response)).await).is_err() {
+    if (client.framed.send(client.push(response)).await).is_err() {
         return Ok(()); // client disconnected immediately
     }
     Ok(())
 }
+
+async fn psubscribe_to_pattern<S: Transport>(
+    context: &ServerContext,
+    pattern: Vec<u8>,
+    client: &mut Client<S>,
+) -> anyhow::Result<()> {
+    context
+        .store
+        .psubscribe(pattern.clone(), client.id, client.tx.clone())
+        .await;
+    if !client.patterns.contains(&pattern) {
+        client.patterns.push(pattern.clone());
+    }
+    let response = vec![
+        RespValue::BulkString(Some("psubscribe".into())),
+        RespValue::BulkString(Some(pattern)),
+        RespValue::Integer(client.subscription_count()),
+    ];
+    if (client.framed.send(client.push(response)).await).is_err() {
+        return Ok(()); // client disconnected immediately
+    }
+    Ok(())
+}
+
+async fn punsubscribe_from_pattern<S: Transport>(
+    context: &ServerContext,
+    pattern: Vec<u8>,
+    client: &mut Client<S>,
+) -> anyhow::Result<()> {
+    context.store.punsubscribe(pattern.clone(), client.id).await;
+    client.patterns.retain(|subscribed| *subscribed != pattern);
+    let response = vec![
+        RespValue::BulkString(Some("punsubscribe".into())),
+        RespValue::BulkString(Some(pattern)),
+        RespValue::Integer(client.subscription_count()),
+    ];
+    if (client.framed.send(client.push(response)).await).is_err() {
+        return Ok(()); // client disconnected immediately
+    }
+    Ok(())
+}
+
+async fn ssubscribe_to_channel<S: Transport>(
+    context: &ServerContext,
+    channel_name: Vec<u8>,
+    client: &mut Client<S>,
+) -> anyhow::Result<()> {
+    context
+        .store
+        .ssubscribe(channel_name.clone(), client.id, client.tx.clone())
+        .await;
+    if !client.shard_channels.contains(&channel_name) {
+        client.shard_channels.push(channel_name.clone());
+    }
+    // Shard confirmations count shard channels alone, as Redis does.
+    let response = vec![
+        RespValue::BulkString(Some("ssubscribe".into())),
+        RespValue::BulkString(Some(channel_name)),
+        RespValue::Integer(client.shard_channels.len() as i64),
+    ];
+    if (client.framed.send(client.push(response)).await).is_err() {
+        return Ok(()); // client disconnected immediately
+    }
+    Ok(())
+}
+
+/// SUNSUBSCRIBE: leaves the named shard channels, or every one of them when none are
+/// named. With nothing to leave, a single confirmation with a nil channel goes out.
+async fn sunsubscribe_from_channels<S: Transport>(
+    context: &ServerContext,
+    channel_names: Vec<Vec<u8>>,
+    client: &mut Client<S>,
+) -> anyhow::Result<()> {
+    let channel_names = if channel_names.is_empty() {
+        client.shard_channels.clone()
+    } else {
+        channel_names
+    };
+    if channel_names.is_empty() {
+        let response = vec![
+            RespValue::BulkString(Some("sunsubscribe".into())),
+            RespValue::BulkString(None),
+            RespValue::Integer(0),
+        ];
+        client.framed.send(client.push(response)).await?;
+        return Ok(());
+    }
+    for channel_name in channel_names {
+        context.store.sunsubscribe(channel_name.clone(), client.id).await;
+        client.shard_channels.retain(|subscribed| *subscribed != channel_name);
+        let response = vec![
+            RespValue::BulkString(Some("sunsubscribe".into())),
+            RespValue::BulkString(Some(channel_name)),
+            RespValue::Integer(client.shard_channels.len() as i64),
+        ];
+        client.framed.send(client.push(response)).await?;
+    }
+    Ok(())
+}
+
+/// QUIT: answers OK, then has the connection loop hang up. `send` flushes, so the
+/// reply is on the wire before the socket is dropped.
+async fn quit<S: Transport>(client: &mut Client<S>) -> anyhow::Result<()> {
+    client
+        .framed
+        .send(RespValue::SimpleString("OK".into()))
+        .await?;
+    client.close_after_reply = true;
+    Ok(())
+}
+
+/// RESET: returns the connection to the state it had right after connecting. Every
+/// subscription is dropped, the protocol goes back to RESP2, and the client is `default`
+/// again, authenticated only if `default` needs no password.
+async fn reset_client<S: Transport>(
+    client: &mut Client<S>,
+    context: &ServerContext,
+) -> anyhow::Result<()> {
+    for channel_name in client.channels.drain(..) {
+        _ = context.store.unsubscribe(channel_name, client.id).await;
+    }
+    for pattern in client.patterns.drain(..) {
+        context.store.punsubscribe(pattern, client.id).await;
+    }
+    for channel_name in client.shard_channels.drain(..) {
+        context.store.sunsubscribe(channel_name, client.id).await;
+    }
+    // Messages that arrived before the unsubscribes belong to the old session.
+    while client.rx.try_recv().is_ok() {}
+    client.mode = ClientMode::Normal;
+    client.protocol = 2;
+    client.user = "default".into();
+    client.authenticated = context.acl.default_is_open();
+    client
+        .framed
+        .send(RespValue::SimpleString("RESET".into()))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        acl::Acl, latency::LatencyMonitor, rdb_parser::config::RdbConfig,
+        replication_manager::manager::ReplicationManager, scripting::ScriptCache,
+        server_info::ServerInfo, shared_store::shared_store::Store,
+    };
+
+    fn context() -> ServerContext {
+        let info = Arc::new(ServerInfo::new().unwrap());
+        ServerContext::new(
+            Arc::new(Store::new()),
+            Arc::new(RdbConfig::new()),
+            Arc::new(Mutex::new(ReplicationManager::new(info.clone()))),
+            info,
+            None,
+            Arc::new(Acl::new(None)),
+            Arc::new(ScriptCache::new()),
+            Arc::new(LatencyMonitor::new(0)),
+        )
+    }
+
+    fn request(args: &[&str]) -> (RespCommand, Vec<u8>) {
+        let value = || {
+            RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(Some(arg.as_bytes().to_vec())))
+                    .collect(),
+            )
+        };
+        let mut bytes = BytesMut::new();
+        RespCodec.encode(value(), &mut bytes).unwrap();
+        (command::Command::try_from_resp(value()).unwrap(), bytes.to_vec())
+    }
+
+    /// The reply as RESP2 text, so expectations read like the wire.
+    fn wire(reply: Option<RespValue>) -> String {
+        let mut bytes = BytesMut::new();
+        RespCodec.encode(reply.expect("a reply"), &mut bytes).unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Runs a command as a client would outside a transaction.
+    async fn run(context: &ServerContext, args: &[&str]) -> String {
+        let (command, bytes) = request(args);
+        wire(process_command(context, command, bytes, &mut None, 2).await.unwrap())
+    }
+
+    /// Runs a command as EXEC runs a queued one.
+    async fn run_queued(context: &ServerContext, args: &[&str]) -> String {
+        let (command, bytes) = request(args);
+        wire(process_without_blocking(context, command, bytes, &mut None, 2).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn blpop_in_a_transaction_returns_nil_at_once_on_an_empty_list() {
+        let context = context();
+        let started = Instant::now();
+        assert_eq!(run_queued(&context, &["BLPOP", "empty", "0"]).await, "*-1\r\n");
+        assert!(started.elapsed() < std::time::Duration::from_millis(100));
+
+        run(&context, &["RPUSH", "list", "a"]).await;
+        assert_eq!(
+            run_queued(&context, &["BLPOP", "list", "0"]).await,
+            "*2\r\n$4\r\nlist\r\n$1\r\na\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn xpending_lists_entries_read_but_not_acked() {
+        let context = context();
+        run(&context, &["XADD", "s", "1-1", "f", "v"]).await;
+        run(&context, &["XADD", "s", "2-1", "f", "w"]).await;
+        run(&context, &["XGROUP", "CREATE", "s", "g", "0"]).await;
+        run(&context, &["XREADGROUP", "GROUP", "g", "alice", "COUNT", "1", "STREAMS", "s", ">"]).await;
+
+        assert_eq!(
+            run(&context, &["XPENDING", "s", "g"]).await,
+            "*4\r\n:1\r\n$3\r\n1-1\r\n$3\r\n1-1\r\n*1\r\n*2\r\n$5\r\nalice\r\n$1\r\n1\r\n"
+        );
+        let detail = run(&context, &["XPENDING", "s", "g", "-", "+", "10"]).await;
+        assert!(detail.starts_with("*1\r\n*4\r\n$3\r\n1-1\r\n$5\r\nalice\r\n:"));
+        assert!(detail.ends_with(":1\r\n"));
+
+        assert_eq!(run(&context, &["XACK", "s", "g", "1-1"]).await, ":1\r\n");
+        assert_eq!(
+            run(&context, &["XPENDING", "s", "g"]).await,
+            "*4\r\n:0\r\n$-1\r\n$-1\r\n*-1\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn xclaim_hands_an_idle_entry_to_another_consumer() {
+        let context = context();
+        run(&context, &["XADD", "s", "1-1", "f", "v"]).await;
+        run(&context, &["XGROUP", "CREATE", "s", "g", "0"]).await;
+        run(&context, &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", ">"]).await;
+
+        // Not idle long enough yet, so alice keeps it.
+        assert_eq!(run(&context, &["XCLAIM", "s", "g", "bob", "60000", "1-1"]).await, "*0\r\n");
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(
+            run(&context, &["XCLAIM", "s", "g", "bob", "10", "1-1"]).await,
+            "*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+        );
+
+        let pending = run(&context, &["XPENDING", "s", "g", "-", "+", "10"]).await;
+        assert!(pending.starts_with("*1\r\n*4\r\n$3\r\n1-1\r\n$3\r\nbob\r\n:"));
+        // Claiming counts as another delivery.
+        assert!(pending.ends_with(":2\r\n"));
+        assert_eq!(run(&context, &["XPENDING", "s", "g", "-", "+", "10", "alice"]).await, "*0\r\n");
+    }
+
+    #[tokio::test]
+    async fn xread_count_limits_each_stream() {
+        let context = context();
+        for id in ["1-1", "2-1", "3-1"] {
+            run(&context, &["XADD", "a", id, "f", "v"]).await;
+        }
+        run(&context, &["XADD", "b", "1-1", "f", "v"]).await;
+
+        assert_eq!(
+            run(&context, &["XREAD", "COUNT", "2", "STREAMS", "a", "b", "0", "0"]).await,
+            "*2\r\n\
+             *2\r\n$1\r\na\r\n*2\r\n\
+             *2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n\
+             *2\r\n$3\r\n2-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n\
+             *2\r\n$1\r\nb\r\n*1\r\n\
+             *2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn xadd_rejects_a_generated_sequence_behind_the_top() {
+        let context = context();
+        run(&context, &["XADD", "s", "5-0", "f", "v"]).await;
+
+        assert_eq!(
+            run(&context, &["XADD", "s", "1-*", "f", "v"]).await,
+            "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"
+        );
+        assert_eq!(run(&context, &["XADD", "s", "5-*", "f", "v"]).await, "$3\r\n5-1\r\n");
+        assert_eq!(run(&context, &["XADD", "s", "6-*", "f", "v"]).await, "$3\r\n6-0\r\n");
+        assert_eq!(
+            run(&context, &["XRANGE", "s", "-", "+"]).await.matches("$1\r\nf\r\n").count(),
+            3
+        );
+    }
+
+    /// Lets a blocked command reach its wait before the next step runs.
+    async fn settle() {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn blpop_woken_by_del_keeps_waiting_for_the_next_push() {
+        let context = context();
+        let (popped, _) = tokio::join!(run(&context, &["BLPOP", "list", "1"]), async {
+            settle().await;
+            run(&context, &["DEL", "list"]).await;
+            settle().await;
+            run(&context, &["RPUSH", "list", "fresh"]).await;
+        });
+        assert_eq!(popped, "*2\r\n$4\r\nlist\r\n$5\r\nfresh\r\n");
+    }
+
+    #[tokio::test]
+    async fn one_push_serves_exactly_one_of_two_blocked_clients() {
+        let context = context();
+        let (first, second, _) = tokio::join!(
+            run(&context, &["BLPOP", "list", "0.2"]),
+            async {
+                settle().await;
+                run(&context, &["BLPOP", "list", "0.2"]).await
+            },
+            async {
+                settle().await;
+                settle().await;
+                run(&context, &["RPUSH", "list", "x"]).await;
+            }
+        );
+        // The client that blocked first is served; the other times out.
+        assert_eq!(first, "*2\r\n$4\r\nlist\r\n$1\r\nx\r\n");
+        assert_eq!(second, "*-1\r\n");
+        assert_eq!(run(&context, &["LLEN", "list"]).await, ":0\r\n");
+    }
+}
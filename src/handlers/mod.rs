@@ -7,4 +7,5 @@ pub mod session;
 pub mod slave;
 pub mod utils;
 pub mod client;
+pub mod connection;
 pub mod geo;
\ No newline at end of file
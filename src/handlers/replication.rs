@@ -1,6 +1,6 @@
 use std::{sync::Arc};
 use futures::{SinkExt, StreamExt};
-use tokio::{net::TcpStream};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::codec::Framed;
 
 use crate::{
@@ -10,8 +10,8 @@ use crate::{
     shared_store::shared_store::Store,
 };
 
-pub async fn handle_replication_connection(
-    framed: &mut Framed<TcpStream, RespCodec>,
+pub async fn handle_replication_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    framed: &mut Framed<S, RespCodec>,
     store: Arc<Store>,
     info: Arc<ServerInfo>,
 ) -> Result<(), Box<dyn std::error::Error>>{
@@ -25,6 +25,12 @@ pub async fn handle_replication_connection(
 
                 None
             }
+            RespCommand::Del(keys) => {
+                store.del(&keys).await;
+                store.append_to_log(bytes).await;
+
+                None
+            }
             RespCommand::Get(key) => Some(store.get(&key).await?),
 
             RespCommand::Info(string) => Some(super::info::info_command(string, info.clone())),
@@ -35,7 +41,7 @@ pub async fn handle_replication_connection(
             }
             RespCommand::ReplconfCommand(ReplconfCommand::Getack(string)) => {
                 //store.append_to_log(bytes).await;
-                let resp = handle_ack_command(string, store.clone()).await;
+                let resp = handle_ack_command(string, store.clone(), &info).await;
                 if let Some(value) = resp {
                     framed.send(value).await?;
                     store.append_to_log(bytes).await;
@@ -49,6 +55,7 @@ pub async fn handle_replication_connection(
         if let Some(value) = response {
             framed.send(value).await?;
         }
+        info.set_slave_repl_offset(replica_offset(&store, &info).await);
     }
 
     Ok(())
@@ -67,13 +74,23 @@ pub fn handle_replconf_command(
     RespValue::SimpleString("OK".into())
 }
 
-pub async fn handle_ack_command(string: String, store: Arc<Store>) -> Option<RespValue> {
+/// Replication offset this replica has processed: where its sync started plus every
+/// byte applied from the master since.
+pub async fn replica_offset(store: &Store, info: &ServerInfo) -> u64 {
+    info.repl_offset() + store.get_offset().await as u64
+}
+
+pub async fn handle_ack_command(
+    string: String,
+    store: Arc<Store>,
+    info: &ServerInfo,
+) -> Option<RespValue> {
     match string.to_ascii_lowercase().as_str() {
         "*" => {
             let mut values = vec![];
             values.push(RespValue::BulkString(Some("REPLCONF".into())));
             values.push(RespValue::BulkString(Some("ACK".into())));
-            let length = store.get_offset().await;
+            let length = replica_offset(&store, info).await;
             values.push(RespValue::BulkString(Some(length.to_string().into())));
 
             Some(RespValue::Array(values))
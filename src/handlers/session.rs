@@ -2,12 +2,21 @@ use crate::command::RespCommand;
 
 pub struct Session {
     pub queued: Vec<(RespCommand, Vec<u8>)>,
+    /// Set when a command is rejected while queueing, so EXEC aborts the transaction.
+    pub dirty: bool,
 }
 
 impl Session {
     pub fn new() -> Self {
         Self {
             queued: vec![],
+            dirty: false,
         }
     }
+
+    /// Forgets the queued commands and any queueing error once a transaction ends.
+    pub fn reset(&mut self) {
+        self.queued.clear();
+        self.dirty = false;
+    }
 }
@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
-use tokio::sync::Mutex;
+use tokio::{sync::Mutex, task::JoinHandle};
 use tokio_util::codec::Framed;
 
 use crate::{
@@ -15,18 +15,22 @@ use super::replication::handle_replication_connection;
 
 type ArcFrame = Arc<Mutex<Framed<tokio::net::TcpStream, resp::RespCodec>>>;
 
-pub fn setup_heartbeat(framed: ArcFrame, store: Arc<Store>) {
+pub fn setup_heartbeat(framed: ArcFrame, store: Arc<Store>, info: Arc<ServerInfo>) {
     tokio::spawn(async move {
-        _ = heartbeat::send_heartbeat(framed, store).await;
+        _ = heartbeat::send_heartbeat(framed, store, info).await;
     });
 }
 
-pub fn setup_master_listener(framed: ArcFrame, store: Arc<Store>, info: Arc<ServerInfo>) {
+pub fn setup_master_listener(
+    framed: ArcFrame,
+    store: Arc<Store>,
+    info: Arc<ServerInfo>,
+) -> JoinHandle<io::Result<()>> {
     tokio::spawn(async move {
         let mut guard = framed.lock().await;
 
         handle_replication_connection(&mut guard, store, info)
             .await
             .map_err(|e| invalid_data_err(format!("Replication Listener had error, {e}")))
-    });
+    })
 }
\ No newline at end of file
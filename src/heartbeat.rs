@@ -9,21 +9,24 @@ use tokio::{
 use tokio_util::codec::Framed;
 
 use crate::{
+    handlers::replication::replica_offset,
+    log,
     resp::{RespCodec, RespValue},
+    server_info::ServerInfo,
     shared_store::shared_store::Store,
 };
 
 pub async fn send_heartbeat(
     framed: Arc<Mutex<Framed<TcpStream, RespCodec>>>,
     store: Arc<Store>,
+    info: Arc<ServerInfo>,
 ) -> io::Result<()> {
-
     let mut ticker = interval(Duration::from_millis(200));
     loop {
-
         ticker.tick().await;
 
-        let offset = store.get_offset().await;
+        let offset = replica_offset(&store, &info).await;
+        log::debug!("Sending heartbeat ACK {offset}");
 
         let ack_command = RespValue::Array(vec![
             RespValue::BulkString(Some(b"REPLCONF".to_vec())),
@@ -0,0 +1,93 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Redis keeps this many samples per event.
+const HISTORY_LEN: usize = 160;
+
+#[derive(Debug, Default)]
+struct EventHistory {
+    /// (unix seconds, milliseconds), oldest first. Spikes within the same second are merged.
+    samples: VecDeque<(i64, u64)>,
+    max_ms: u64,
+}
+
+/// LATENCY's record of operations slower than `latency-monitor-threshold`, grouped by
+/// event name. A threshold of 0 disables recording, as in Redis.
+#[derive(Debug)]
+pub struct LatencyMonitor {
+    threshold_ms: u64,
+    events: Mutex<HashMap<String, EventHistory>>,
+}
+
+impl LatencyMonitor {
+    pub fn new(threshold_ms: u64) -> Self {
+        Self {
+            threshold_ms,
+            events: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records `elapsed` under `event` when it reaches the threshold.
+    pub fn observe(&self, event: &str, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        if self.threshold_ms == 0 || ms < self.threshold_ms {
+            return;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut events = self.events.lock().unwrap();
+        let history = events.entry(event.to_string()).or_default();
+        history.max_ms = history.max_ms.max(ms);
+        match history.samples.back_mut() {
+            Some((time, latest)) if *time == now => *latest = (*latest).max(ms),
+            _ => {
+                if history.samples.len() == HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back((now, ms));
+            }
+        }
+    }
+
+    /// (event, time of the latest spike, its latency, all-time max) for every event.
+    pub fn latest(&self) -> Vec<(String, i64, u64, u64)> {
+        let events = self.events.lock().unwrap();
+        let mut latest: Vec<_> = events
+            .iter()
+            .filter_map(|(event, history)| {
+                let (time, ms) = history.samples.back()?;
+                Some((event.clone(), *time, *ms, history.max_ms))
+            })
+            .collect();
+        latest.sort();
+        latest
+    }
+
+    pub fn history(&self, event: &str) -> Vec<(i64, u64)> {
+        let events = self.events.lock().unwrap();
+        events
+            .get(event)
+            .map(|history| history.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drops the named events, or all of them when `names` is empty. Returns how many
+    /// had samples.
+    pub fn reset(&self, names: &[String]) -> usize {
+        let mut events = self.events.lock().unwrap();
+        if names.is_empty() {
+            let count = events.len();
+            events.clear();
+            return count;
+        }
+        names
+            .iter()
+            .filter(|name| events.remove(name.as_str()).is_some())
+            .count()
+    }
+}
@@ -1,51 +1,77 @@
+mod acl;
+mod aof;
 mod command;
+mod command_table;
 mod error_helpers;
+mod glob;
 mod handlers;
 mod heartbeat;
+mod latency;
+mod log;
+mod random;
 mod rdb_parser;
+mod reaper;
 mod replication_manager;
 mod resp;
+mod scripting;
 mod server_info;
 mod shared_store;
 mod server_context;
+mod sha1;
+mod sha256;
 
 use std::{
+    io,
+    os::unix::fs::FileTypeExt,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Result;
-use tokio::{net::TcpListener, sync::Mutex};
+use tokio::{
+    net::{TcpListener, UnixListener},
+    sync::Mutex,
+};
 use tokio_util::codec::Framed;
 
 use crate::{
+    acl::Acl,
+    aof::Aof,
     error_helpers::invalid_data_err,
     handlers::{
+        connection::Transport,
         master::handle_master_connection,
-        replication::handle_replication_connection,
+        replication::{handle_replication_connection, replica_offset},
         slave::{setup_heartbeat, setup_master_listener},
     },
+    latency::LatencyMonitor,
     rdb_parser::{config::RdbConfig, length_encoded_values::LengthEncodedValue},
-    replication_manager::manager::ReplicationManager,
+    replication_manager::manager::{spawn_keepalive, ReplicationManager},
+    scripting::ScriptCache,
     server_info::ServerInfo,
     shared_store::shared_store::Store,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("Logs from your program will appear here!");
+    log::info!("Logs from your program will appear here!");
 
     let server_info = Arc::new(ServerInfo::new()?);
     let store = Arc::new(Store::new());
     let rdb = Arc::new(RdbConfig::new());
 
-    load_database(&rdb, &store).await?;
+    let replayed_aof = rdb.appendonly && aof::replay(&rdb, &store, &server_info).await?;
+    if !replayed_aof {
+        load_database(&rdb, &store).await?;
+    }
+    store.set_replica(server_info.role.eq_ignore_ascii_case("slave"));
+    reaper::setup_active_expire(store.clone());
 
     match server_info.role.to_ascii_lowercase().as_str() {
         "master" => run_master(server_info, store, rdb).await?,
         "slave" => run_slave(server_info, store).await?,
         _ => {
-            eprintln!("Unknown role: {}", server_info.role);
+            log::warning!("Unknown role: {}", server_info.role);
             std::process::exit(1);
         }
     }
@@ -84,65 +110,164 @@ async fn run_master(
 ) -> Result<()> {
     let listener =
         TcpListener::bind(format!("127.0.0.1:{}", server_info.tcp_port)).await?;
-    let replication_manager = Arc::new(Mutex::new(ReplicationManager::new()));
+    let replication_manager = ReplicationManager::new(server_info.clone());
+    let pending_dels = replication_manager.pending_dels();
+    let replication_manager = Arc::new(Mutex::new(replication_manager));
+    spawn_keepalive(replication_manager.clone());
+    store.set_replication(replication_manager.clone(), pending_dels);
+    let aof = if rdb.appendonly {
+        Some(Arc::new(Aof::open(&rdb.aof_path()).await?))
+    } else {
+        None
+    };
+    let acl = Arc::new(Acl::new(rdb.requirepass.as_deref()));
+    let scripts = Arc::new(ScriptCache::new());
+    let latency = Arc::new(LatencyMonitor::new(rdb.latency_monitor_threshold));
+    let server_context = server_context::ServerContext::new(
+        store,
+        rdb,
+        replication_manager,
+        server_info.clone(),
+        aof,
+        acl,
+        scripts,
+        latency,
+    );
+
+    if let Some(path) = &server_info.unixsocket {
+        let unix_listener = bind_unix_socket(path)?;
+        let client_addr = unix_client_addr(path);
+        let server_context = server_context.clone();
+        tokio::spawn(async move {
+            loop {
+                match unix_listener.accept().await {
+                    Ok((socket, _)) => {
+                        spawn_master_connection(socket, client_addr.clone(), server_context.clone())
+                    }
+                    Err(e) => log::warning!("Unix socket accept failed: {e}"),
+                }
+            }
+        });
+    }
 
     loop {
         let (socket, addr) = listener.accept().await?;
-        println!("New connection from {addr}");
-        let store_clone = store.clone();
-        let rdb_clone = rdb.clone();
-        let info_clone = server_info.clone();
-        let replication_manager_clone = replication_manager.clone();
+        log::debug!("New connection from {addr}");
+        spawn_master_connection(socket, addr.to_string(), server_context.clone());
+    }
+}
+
+fn spawn_master_connection(
+    socket: impl Transport,
+    addr: String,
+    server_context: server_context::ServerContext,
+) {
+    tokio::spawn(async move {
+        if let Err(e) = handle_master_connection(socket, addr.clone(), server_context).await {
+            log::warning!("Error handling {addr}: {e:?}");
+        }
+    });
+}
 
-        let server_context = server_context::ServerContext::new(
-            store_clone,
-            rdb_clone,
-            replication_manager_clone,
-            info_clone,
-        );
+/// Unix socket clients have no peer address; Redis lists them as `<socket path>:0`.
+fn unix_client_addr(path: &str) -> String {
+    format!("{path}:0")
+}
 
-        tokio::spawn(async move {
-            if let Err(e) = handle_master_connection(
-                socket,
-                server_context,
-            )
-            .await
-            {
-                eprintln!("Error handling {addr}: {e:?}");
-            }
-        });
+/// Binds the `--unixsocket` path, first removing a socket file an earlier run left behind.
+fn bind_unix_socket(path: &str) -> io::Result<UnixListener> {
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
     }
+    let listener = UnixListener::bind(path)?;
+    log::info!("Accepting connections at {path}");
+    Ok(listener)
 }
 
 async fn run_slave(server_info: Arc<ServerInfo>, store: Arc<Store>) -> Result<()> {
     let listener =
         TcpListener::bind(format!("127.0.0.1:{}", server_info.tcp_port)).await?;
-    println!("Slave listening on 127.0.0.1:{}", server_info.tcp_port);
+    log::info!("Slave listening on 127.0.0.1:{}", server_info.tcp_port);
 
     let info_clone_for_handshake = server_info.clone();
     let store_clone_for_handshake = store.clone();
 
     tokio::spawn(async move {
-        match info_clone_for_handshake.handshake().await {
-            Ok(Some((socket, _))) => {
-                println!("Handshake successful, connected to master.");
-                let store_for_heartbeat = store_clone_for_handshake.clone();
-                let framed = Arc::new(Mutex::new(socket));
-                setup_heartbeat(framed.clone(), store_for_heartbeat);
-                setup_master_listener(
-                    framed.clone(),
-                    store_clone_for_handshake.clone(),
-                    info_clone_for_handshake.clone(),
-                );
+        let mut resume_offset = None;
+        loop {
+            match info_clone_for_handshake.handshake(resume_offset).await {
+                Ok(Some((socket, full_resync))) => {
+                    log::info!("Handshake successful, connected to master.");
+                    if full_resync {
+                        store_clone_for_handshake.clear_log().await;
+                    }
+                    info_clone_for_handshake.set_slave_repl_offset(
+                        replica_offset(&store_clone_for_handshake, &info_clone_for_handshake)
+                            .await,
+                    );
+                    let store_for_heartbeat = store_clone_for_handshake.clone();
+                    let framed = Arc::new(Mutex::new(socket));
+                    setup_heartbeat(
+                        framed.clone(),
+                        store_for_heartbeat,
+                        info_clone_for_handshake.clone(),
+                    );
+                    let listener = setup_master_listener(
+                        framed.clone(),
+                        store_clone_for_handshake.clone(),
+                        info_clone_for_handshake.clone(),
+                    );
+                    if let Ok(Err(e)) = listener.await {
+                        log::warning!("{e}");
+                    }
+                    // Ask to continue from what we already applied when we reconnect.
+                    resume_offset = Some(
+                        replica_offset(&store_clone_for_handshake, &info_clone_for_handshake)
+                            .await,
+                    );
+                }
+                Ok(None) => {
+                    log::warning!("Handshake returned Ok(None) - no socket available.");
+                    break;
+                }
+                Err(e) => log::warning!("Handshake with master failed with error: {e:?}"),
             }
-            Ok(None) => eprintln!("Handshake returned Ok(None) - no socket available."),
-            Err(e) => eprintln!("Handshake with master failed with error: {e:?}"),
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
         }
     });
 
+    if let Some(path) = &server_info.unixsocket {
+        let unix_listener = bind_unix_socket(path)?;
+        let client_addr = unix_client_addr(path);
+        let store = store.clone();
+        let server_info = server_info.clone();
+        tokio::spawn(async move {
+            loop {
+                let socket = match unix_listener.accept().await {
+                    Ok((socket, _)) => socket,
+                    Err(e) => {
+                        log::warning!("Unix socket accept failed: {e}");
+                        continue;
+                    }
+                };
+                let store_clone = store.clone();
+                let info_clone = server_info.clone();
+                let client_addr = client_addr.clone();
+                tokio::spawn(async move {
+                    let mut framed = Framed::new(socket, resp::RespCodec);
+                    if let Err(e) =
+                        handle_replication_connection(&mut framed, store_clone, info_clone).await
+                    {
+                        log::warning!("Error handling {client_addr}: {e:?}");
+                    }
+                });
+            }
+        });
+    }
+
     loop {
         let (socket, addr) = listener.accept().await?;
-        println!("New connection from {addr}");
+        log::debug!("New connection from {addr}");
         let store_clone = store.clone();
         let info_clone = server_info.clone();
 
@@ -151,7 +276,7 @@ async fn run_slave(server_info: Arc<ServerInfo>, store: Arc<Store>) -> Result<()
             if let Err(e) =
                 handle_replication_connection(&mut framed, store_clone, info_clone).await
             {
-                eprintln!("Error handling {addr}: {e:?}");
+                log::warning!("Error handling {addr}: {e:?}");
             }
         });
     }
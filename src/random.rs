@@ -0,0 +1,35 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Cheap non-cryptographic randomness: every `RandomState` is freshly keyed, so hashing
+/// nothing with it still yields an unpredictable value without an extra dependency.
+pub fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+pub fn random_index(len: usize) -> usize {
+    (random_u64() % len as u64) as usize
+}
+
+/// Picks up to `count` distinct positions out of `len` via a partial Fisher-Yates shuffle.
+pub fn distinct_indexes(len: usize, count: usize) -> Vec<usize> {
+    let mut indexes: Vec<usize> = (0..len).collect();
+    let count = count.min(len);
+    for i in 0..count {
+        let j = i + random_index(len - i);
+        indexes.swap(i, j);
+    }
+    indexes.truncate(count);
+    indexes
+}
+
+/// A fresh 40-character hex identifier, the shape Redis uses for replication IDs.
+pub fn random_hex_id() -> String {
+    let bytes: Vec<u8> = (0..3)
+        .flat_map(|_| random_u64().to_be_bytes())
+        .take(20)
+        .collect();
+    hex::encode(bytes)
+}
@@ -2,19 +2,35 @@
 
 use std::{
     io::{self},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug, Clone)]
 pub struct RdbConfig {
     pub dir: String,
     pub dbfilename: String,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    /// Positive: most entries a list keeps packed. Negative: a byte budget (-1 = 4kb ... -5 = 64kb).
+    pub list_max_listpack_size: i64,
+    /// Password clients must AUTH with before running commands. `None` leaves the server open.
+    pub requirepass: Option<String>,
+    /// Milliseconds an operation must take before LATENCY records it. 0 disables the monitor.
+    pub latency_monitor_threshold: u64,
+    /// Most channels one client may SUBSCRIBE to at once. 0 leaves it unlimited.
+    pub max_subscriptions_per_client: usize,
 }
 
 impl RdbConfig {
     pub fn new() -> Self {
         let mut dir = "/tmp/redis-files".to_string();
         let mut dbfilename = "dump.rdb".to_string();
+        let mut appendonly = false;
+        let mut appendfilename = "appendonly.aof".to_string();
+        let mut list_max_listpack_size = -2;
+        let mut requirepass = None;
+        let mut latency_monitor_threshold = 0;
+        let mut max_subscriptions_per_client = 0;
         let mut args = std::env::args().peekable();
         while let Some(arg) = args.next() {
             match arg.as_str() {
@@ -28,10 +44,51 @@ impl RdbConfig {
                         dbfilename = dbfilename_str
                     }
                 }
+                "--appendonly" => {
+                    if let Some(flag) = args.next() {
+                        appendonly = flag.eq_ignore_ascii_case("yes")
+                    }
+                }
+                "--appendfilename" => {
+                    if let Some(appendfilename_str) = args.next() {
+                        appendfilename = appendfilename_str
+                    }
+                }
+                "--list-max-listpack-size" => {
+                    if let Some(size) = args.next().and_then(|s| s.parse().ok()) {
+                        list_max_listpack_size = size
+                    }
+                }
+                "--requirepass" => {
+                    requirepass = args.next().filter(|password| !password.is_empty())
+                }
+                "--latency-monitor-threshold" => {
+                    if let Some(ms) = args.next().and_then(|s| s.parse().ok()) {
+                        latency_monitor_threshold = ms
+                    }
+                }
+                "--max-subscriptions-per-client" => {
+                    if let Some(limit) = args.next().and_then(|s| s.parse().ok()) {
+                        max_subscriptions_per_client = limit
+                    }
+                }
                 _ => {}
             }
         }
-        Self { dir, dbfilename }
+        Self {
+            dir,
+            dbfilename,
+            appendonly,
+            appendfilename,
+            list_max_listpack_size,
+            requirepass,
+            latency_monitor_threshold,
+            max_subscriptions_per_client,
+        }
+    }
+
+    pub fn aof_path(&self) -> PathBuf {
+        Path::new(&self.dir).join(&self.appendfilename)
     }
 
     fn dir(&self) -> &String {
@@ -46,6 +103,12 @@ impl RdbConfig {
         match key {
             "dir" => Some(self.dir.clone()),
             "dbfilename" => Some(self.dbfilename.clone()),
+            "appendonly" => Some(if self.appendonly { "yes" } else { "no" }.to_string()),
+            "appendfilename" => Some(self.appendfilename.clone()),
+            "list-max-listpack-size" => Some(self.list_max_listpack_size.to_string()),
+            "requirepass" => Some(self.requirepass.clone().unwrap_or_default()),
+            "latency-monitor-threshold" => Some(self.latency_monitor_threshold.to_string()),
+            "max-subscriptions-per-client" => Some(self.max_subscriptions_per_client.to_string()),
             _ => None,
         }
     }
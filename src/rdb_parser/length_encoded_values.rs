@@ -25,7 +25,6 @@ pub enum ValueEncoding {
     Int8,
     Int16,
     Int32,
-    #[allow(dead_code)]
     CompressedString {
         compressed_len: usize,
         original_len: usize,
@@ -38,12 +37,12 @@ impl LengthEncodedValue {
         match length {
             ValueEncoding::String(size) => {
                 let mut value = vec![0u8; size];
-                let _ = reader.read_exact(&mut value);
+                reader.read_exact(&mut value)?;
                 Ok(LengthEncodedValue::String(value))
             }
             ValueEncoding::Int8 => {
                 let mut value = vec![0u8; 1];
-                let _ = reader.read_exact(&mut value);
+                reader.read_exact(&mut value)?;
                 Ok(LengthEncodedValue::Integer(value[0] as u64))
             }
             ValueEncoding::Int16 => {
@@ -56,8 +55,13 @@ impl LengthEncodedValue {
                 reader.read_exact(&mut buf)?;
                 Ok(LengthEncodedValue::Integer(u32::from_be_bytes(buf) as u64))
             }
-            _ => {
-                Err(invalid_data_err("Compressed String".to_string()))
+            ValueEncoding::CompressedString {
+                compressed_len,
+                original_len,
+            } => {
+                let mut compressed = vec![0u8; compressed_len];
+                reader.read_exact(&mut compressed)?;
+                Ok(LengthEncodedValue::String(lzf_decompress(&compressed, original_len)?))
             }
         }
     }
@@ -74,6 +78,106 @@ impl LengthEncodedValue {
         }
     }
 
+    /// Reads past a value of a type the loader doesn't decode, leaving the reader on the
+    /// next opcode. Strings inside are read in full; only their bytes are thrown away.
+    pub fn skip_value<R: Read>(reader: &mut R, type_code: u8) -> io::Result<()> {
+        match type_code {
+            // List and set: a count, then that many strings.
+            0x01 | 0x02 => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_value(reader)?;
+                }
+            }
+            // Sorted set: member strings, each followed by a score written as a
+            // length-prefixed decimal. 253-255 stand for NaN, +inf and -inf on their own.
+            0x03 => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_value(reader)?;
+                    let mut len = [0u8; 1];
+                    reader.read_exact(&mut len)?;
+                    if len[0] < 253 {
+                        reader.read_exact(&mut vec![0u8; len[0] as usize])?;
+                    }
+                }
+            }
+            // Hash: field and value strings in pairs.
+            0x04 => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_value(reader)?;
+                    Self::parse_value(reader)?;
+                }
+            }
+            // Sorted set with binary scores: member strings, each followed by an 8-byte double.
+            0x05 => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_value(reader)?;
+                    reader.read_exact(&mut [0u8; 8])?;
+                }
+            }
+            // Zipmap, ziplist, intset, the ziplist sorted set and hash, and the listpack
+            // hash, sorted set and set: one string blob.
+            0x09..=0x0D | 0x10 | 0x11 | 0x14 => {
+                Self::parse_value(reader)?;
+            }
+            // Quicklist: a count of ziplist blobs.
+            0x0E => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_value(reader)?;
+                }
+            }
+            // Quicklist 2: each node is a container kind followed by its blob.
+            0x12 => {
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    Self::parse_length_encoded_int(reader)?;
+                    Self::parse_value(reader)?;
+                }
+            }
+            0x0F | 0x13 | 0x15 => Self::skip_stream(reader, type_code)?,
+            _ => return Err(invalid_data_err(format!("invalid type {type_code}"))),
+        }
+        Ok(())
+    }
+
+    /// Streams in their three RDB versions: 0x13 adds the first and max-deleted IDs and
+    /// per-group read counters, 0x15 adds each consumer's active time.
+    fn skip_stream<R: Read>(reader: &mut R, type_code: u8) -> io::Result<()> {
+        let skip_lengths = |reader: &mut R, count: usize| -> io::Result<()> {
+            for _ in 0..count {
+                Self::parse_length_encoded_int(reader)?;
+            }
+            Ok(())
+        };
+        // Listpack nodes, each keyed by its master entry ID.
+        for _ in 0..Self::parse_length_encoded_int(reader)? {
+            Self::parse_value(reader)?;
+            Self::parse_value(reader)?;
+        }
+        // Length and last ID, then first ID, max deleted ID and entries added.
+        skip_lengths(reader, if type_code >= 0x13 { 8 } else { 3 })?;
+        for _ in 0..Self::parse_length_encoded_int(reader)? {
+            Self::parse_value(reader)?;
+            // Last delivered ID, then entries read.
+            skip_lengths(reader, if type_code >= 0x13 { 3 } else { 2 })?;
+            // Pending entries: a raw 16-byte ID, delivery time and delivery count.
+            for _ in 0..Self::parse_length_encoded_int(reader)? {
+                reader.read_exact(&mut [0u8; 16 + 8])?;
+                Self::parse_length_encoded_int(reader)?;
+            }
+            // Consumers: name, seen time, active time, then the raw IDs they own.
+            for _ in 0..Self::parse_length_encoded_int(reader)? {
+                Self::parse_value(reader)?;
+                reader.read_exact(&mut [0u8; 8])?;
+                if type_code >= 0x15 {
+                    reader.read_exact(&mut [0u8; 8])?;
+                }
+                for _ in 0..Self::parse_length_encoded_int(reader)? {
+                    reader.read_exact(&mut [0u8; 16])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn _parse_int<R: Read>(reader: &mut R) -> io::Result<usize> {
         let length = Self::parse_value(reader)?;
         match length {
@@ -109,14 +213,23 @@ impl LengthEncodedValue {
                 let length = ((b & 0x3F) as usize) << 8 | (next_byte[0] as usize);
                 Ok(ValueEncoding::String(length))
             }
-            0x80..=0xBF => {
+            0x80 => {
                 let mut buf = [0u8; 4];
                 reader.read_exact(&mut buf)?;
                 Ok(ValueEncoding::String(u32::from_be_bytes(buf) as usize))
             }
+            0x81 => {
+                let mut buf = [0u8; 8];
+                reader.read_exact(&mut buf)?;
+                Ok(ValueEncoding::String(u64::from_be_bytes(buf) as usize))
+            }
             0xC0 => Ok(ValueEncoding::Int8),
             0xC1 => Ok(ValueEncoding::Int16),
             0xC2 => Ok(ValueEncoding::Int32),
+            0xC3 => Ok(ValueEncoding::CompressedString {
+                compressed_len: Self::parse_length_encoded_int(reader)?,
+                original_len: Self::parse_length_encoded_int(reader)?,
+            }),
             _ => {
                 Err(invalid_data_err(format!(
                     "unknown integer encoding prefix: {b}"
@@ -126,6 +239,126 @@ impl LengthEncodedValue {
     }
 }
 
+/// Expands an LZF block: a control byte below 32 starts a run of that many plus one
+/// literal bytes, anything else copies from earlier output.
+fn lzf_decompress(input: &[u8], original_len: usize) -> io::Result<Vec<u8>> {
+    let corrupt = || invalid_data_err("corrupt LZF compressed string");
+    let mut output = Vec::with_capacity(original_len);
+    let mut bytes = input.iter().copied();
+    while let Some(ctrl) = bytes.next() {
+        let ctrl = ctrl as usize;
+        if ctrl < 32 {
+            for _ in 0..=ctrl {
+                output.push(bytes.next().ok_or_else(corrupt)?);
+            }
+            continue;
+        }
+        let mut len = ctrl >> 5;
+        if len == 7 {
+            len += bytes.next().ok_or_else(corrupt)? as usize;
+        }
+        let back = ((ctrl & 0x1F) << 8) + bytes.next().ok_or_else(corrupt)? as usize + 1;
+        let start = output.len().checked_sub(back).ok_or_else(corrupt)?;
+        // The source may overlap what is being written, so copy a byte at a time.
+        for i in start..start + len + 2 {
+            output.push(output[i]);
+        }
+    }
+    if output.len() != original_len {
+        return Err(corrupt());
+    }
+    Ok(output)
+}
+
 fn invalid_data_err<S: Into<String>>(msg: S) -> io::Error {
     io::Error::new(io::ErrorKind::InvalidData, msg.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Skips `value`, then checks the reader stopped right before the trailing marker.
+    fn assert_skips(type_code: u8, value: &[u8]) {
+        let mut bytes = value.to_vec();
+        bytes.push(0xFF);
+        let mut reader = bytes.as_slice();
+        LengthEncodedValue::skip_value(&mut reader, type_code).unwrap();
+        assert_eq!(reader, [0xFF]);
+    }
+
+    #[test]
+    fn decompresses_lzf_strings() {
+        // "abc" as literals, then a six byte copy from three bytes back.
+        let mut reader = &[0xC3, 6, 9, 0x02, b'a', b'b', b'c', 0x80, 0x02][..];
+        match LengthEncodedValue::parse_value(&mut reader).unwrap() {
+            LengthEncodedValue::String(value) => assert_eq!(value, b"abcabcabc"),
+            other => panic!("expected a string, got {other}"),
+        }
+        let mut truncated = &[0xC3, 2, 9, 0x02, b'a'][..];
+        assert!(LengthEncodedValue::parse_value(&mut truncated).is_err());
+    }
+
+    #[test]
+    fn reads_64_bit_lengths() {
+        let mut reader = &[0x81, 0, 0, 1, 0x8C, 0x4F, 0x2A, 0x10, 0x00][..];
+        assert_eq!(
+            LengthEncodedValue::parse_length_encoded_int(&mut reader).unwrap(),
+            0x0000_018C_4F2A_1000
+        );
+    }
+
+    #[test]
+    fn skips_binary_score_sorted_sets() {
+        let mut value = vec![2];
+        for member in [b"a", b"b"] {
+            value.extend([1, member[0]]);
+            value.extend(1.5f64.to_le_bytes());
+        }
+        assert_skips(0x05, &value);
+    }
+
+    #[test]
+    fn skips_listpack_and_quicklist_encodings() {
+        for type_code in [0x10, 0x11, 0x14] {
+            assert_skips(type_code, &[3, b'x', b'y', b'z']);
+        }
+        assert_skips(0x0E, &[2, 1, b'a', 0xC3, 4, 3, 0x02, b'a', b'b', b'c']);
+        assert_skips(0x12, &[2, 2, 1, b'a', 1, 2, b'b', b'c']);
+    }
+
+    #[test]
+    fn skips_streams_in_every_version() {
+        let ms = [0x81, 0, 0, 1, 0x8C, 0x4F, 0x2A, 0x10, 0x00];
+        for type_code in [0x0F, 0x13, 0x15] {
+            let extra_ids = if type_code >= 0x13 { 5 } else { 0 };
+            // One listpack node keyed by a raw 16-byte ID.
+            let mut value = vec![1, 16];
+            value.extend([0; 16]);
+            value.extend([2, b'l', b'p']);
+            // Length and last ID.
+            value.push(1);
+            value.extend(ms);
+            value.push(0);
+            value.extend(vec![0; extra_ids]);
+            // One group with one pending entry and one consumer owning it.
+            value.extend([1, 1, b'g']);
+            value.extend(ms);
+            value.push(0);
+            if type_code >= 0x13 {
+                value.push(1);
+            }
+            value.push(1);
+            value.extend([0; 16 + 8]);
+            value.push(1);
+            value.extend([1, 1, b'c']);
+            value.extend([0; 8]);
+            if type_code >= 0x15 {
+                value.extend([0; 8]);
+            }
+            value.push(1);
+            value.extend([0; 16]);
+            assert_skips(type_code, &value);
+        }
+    }
+}
@@ -5,14 +5,20 @@ pub enum RdbOpcode {
     ExpireTimeMs,
     ExpireTimeSec,
     KeyValue(u8), // generic, maybe further specify by type
+    /// LRU idle time of the next key, written under an LRU maxmemory-policy.
+    Idle,
+    /// LFU access frequency of the next key, written under an LFU maxmemory-policy.
+    Freq,
     End,
     Unknown,
 }
 
 pub fn parse_opcode(opcode: u8) -> RdbOpcode {
     match opcode {
-        0x00..=0x04 => RdbOpcode::KeyValue(opcode),
-        0x09..=0x0D => RdbOpcode::KeyValue(opcode),
+        0x00..=0x05 => RdbOpcode::KeyValue(opcode),
+        0x09..=0x15 => RdbOpcode::KeyValue(opcode),
+        0xF8 => RdbOpcode::Idle,
+        0xF9 => RdbOpcode::Freq,
         0xFA => RdbOpcode::Aux,
         0xFB => RdbOpcode::ResizeDb,
         0xFC => RdbOpcode::ExpireTimeMs,
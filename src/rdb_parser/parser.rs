@@ -2,10 +2,13 @@ use std::{
     collections::{HashMap, HashSet}, fs::File, io::{self, BufRead, BufReader, Read}, path::Path
 };
 
-use crate::rdb_parser::{
-    config::RdbConfig,
-    length_encoded_values::LengthEncodedValue,
-    optcode::{RdbOpcode, parse_opcode},
+use crate::{
+    log::{self, LogLevel},
+    rdb_parser::{
+        config::RdbConfig,
+        length_encoded_values::LengthEncodedValue,
+        optcode::{RdbOpcode, parse_opcode},
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -23,18 +26,20 @@ impl RdbConfig {
         }
         let mut dbs = HashSet::new();
         let mut key_values = HashMap::new();
-        let raw = std::fs::read(&path)?;
-
-        eprintln!("--- full RDB dump ({} bytes) ---", raw.len());
-        for (i, chunk) in raw.chunks(16).enumerate() {
-            // print a hex offset
-            eprint!("{:08X}: ", i * 16);
-            for byte in chunk {
-                eprint!("{byte:02X} ");
+        // The hex dump is only for debugging; a large RDB would otherwise stall startup on I/O.
+        if log::enabled(LogLevel::Debug) {
+            let raw = std::fs::read(&path)?;
+            eprintln!("--- full RDB dump ({} bytes) ---", raw.len());
+            for (i, chunk) in raw.chunks(16).enumerate() {
+                // print a hex offset
+                eprint!("{:08X}: ", i * 16);
+                for byte in chunk {
+                    eprint!("{byte:02X} ");
+                }
+                eprintln!();
             }
-            eprintln!();
+            eprintln!("--------------------------------");
         }
-        eprintln!("--------------------------------");
         let file = File::open(path)?;
         let mut reader = BufReader::new(file);
 
@@ -67,29 +72,32 @@ impl RdbConfig {
                     let _key = LengthEncodedValue::parse_string(&mut reader)?;
                     let _value = LengthEncodedValue::parse_value(&mut reader)?;
                 }
-                RdbOpcode::KeyValue(type_code) => {
+                RdbOpcode::KeyValue(0x00) => {
                     let key = LengthEncodedValue::parse_string(&mut reader)?;
                     let value = LengthEncodedValue::parse_value(&mut reader)?;
-                    let value_type = match type_code {
-                        0x00 => "string",
-                        0x01 => "list",
-                        0x02 => "set",
-                        0x03 => "sorted_set",
-                        0x04 => "hash",
-                        0x0A => "ziplist",
-                        0x0B => "set",
-                        0x0D => "hash",
-
-                        _ => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("invalid type {type_code}"),
-                            ));
-                        }
-                    };
-                    key_values.insert(key, (value, value_type.to_string(), expiry));
+                    key_values.insert(key, (value, "string".to_string(), expiry));
+                    expiry = None;
+                }
+                // Only strings are decoded. Other types are read past so the keys after
+                // them still load, rather than being misread as strings.
+                RdbOpcode::KeyValue(type_code) => {
+                    let key = LengthEncodedValue::parse_string(&mut reader)?;
+                    LengthEncodedValue::skip_value(&mut reader, type_code)?;
+                    log::warning!(
+                        "Skipping RDB key '{}' of unsupported type {type_code}",
+                        String::from_utf8_lossy(&key)
+                    );
                     expiry = None;
                 }
+                // Eviction metadata for the key that follows. Nothing here evicts, so it
+                // is read past and dropped.
+                RdbOpcode::Idle => {
+                    let _idle = LengthEncodedValue::parse_length_encoded_int(&mut reader)?;
+                }
+                RdbOpcode::Freq => {
+                    let mut _freq = [0u8; 1];
+                    reader.read_exact(&mut _freq)?;
+                }
                 RdbOpcode::ExpireTimeSec => {
                     let mut secs = [0u8; 4];
                     reader.read_exact(&mut secs)?;
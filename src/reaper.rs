@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use tokio::time::{interval, Duration};
+
+use crate::shared_store::shared_store::Store;
+
+pub fn setup_active_expire(store: Arc<Store>) {
+    tokio::spawn(async move {
+        active_expire(store).await;
+    });
+}
+
+async fn active_expire(store: Arc<Store>) {
+    let mut ticker = interval(Duration::from_millis(100));
+    loop {
+        ticker.tick().await;
+
+        // DEBUG SET-ACTIVE-EXPIRE 0 leaves expired keys for the lazy path to catch, and a
+        // replica leaves them for the master to delete.
+        if !store.active_expire_enabled() || store.is_replica() {
+            continue;
+        }
+        store.purge_expired().await;
+    }
+}
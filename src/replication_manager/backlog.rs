@@ -0,0 +1,46 @@
+use std::collections::VecDeque;
+
+const DEFAULT_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// A bounded window over the most recent bytes of the replication stream, so a replica
+/// that drops briefly can pick up where it left off instead of doing a full resync.
+#[derive(Debug)]
+pub struct Backlog {
+    buffer: VecDeque<u8>,
+    capacity: usize,
+    /// Replication offset of the first byte still held in `buffer`.
+    start_offset: u64,
+}
+
+impl Backlog {
+    pub fn new() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            capacity: DEFAULT_BACKLOG_SIZE,
+            start_offset: 0,
+        }
+    }
+
+    pub fn append(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes);
+        let overflow = self.buffer.len().saturating_sub(self.capacity);
+        if overflow > 0 {
+            self.buffer.drain(..overflow);
+            self.start_offset += overflow as u64;
+        }
+    }
+
+    /// Offset just past the last byte ever written, i.e. the master replication offset.
+    pub fn end_offset(&self) -> u64 {
+        self.start_offset + self.buffer.len() as u64
+    }
+
+    /// Everything from `offset` onwards, or `None` once those bytes have been evicted.
+    pub fn since(&self, offset: u64) -> Option<Vec<u8>> {
+        if offset < self.start_offset || offset > self.end_offset() {
+            return None;
+        }
+        let skip = (offset - self.start_offset) as usize;
+        Some(self.buffer.range(skip..).copied().collect())
+    }
+}
@@ -1,53 +1,211 @@
 use std::collections::HashMap;
 use std::io;
-use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::tcp::OwnedWriteHalf;
+use std::time::Duration;
+use bytes::BytesMut;
+use tokio::io::AsyncWrite;
 use tokio::sync::Mutex;
+use tokio_util::codec::Encoder;
 
-use crate::command::RespCommand;
+use crate::log;
+use crate::replication_manager::backlog::Backlog;
 use crate::replication_manager::replica::Replica;
+use crate::resp::{RespCodec, RespValue};
+use crate::server_info::ServerInfo;
+
+/// How often the master asks replicas for an ACK, like Redis's `repl-ping-replica-period`.
+const REPLICA_PING_PERIOD: Duration = Duration::from_secs(10);
+
+/// The encoded `REPLCONF GETACK *` sent to prompt replicas for their offset.
+pub fn getack_bytes() -> Vec<u8> {
+    let command = RespValue::Array(vec![
+        RespValue::BulkString(Some(b"REPLCONF".to_vec())),
+        RespValue::BulkString(Some(b"GETACK".to_vec())),
+        RespValue::BulkString(Some(b"*".to_vec())),
+    ]);
+    let mut bytes = BytesMut::new();
+    // Encoding an array of bulk strings cannot fail.
+    let _ = RespCodec.encode(command, &mut bytes);
+    bytes.to_vec()
+}
+
+/// Periodically prompts replicas for an ACK and drops the ones that stopped answering.
+pub fn spawn_keepalive(manager: Arc<Mutex<ReplicationManager>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REPLICA_PING_PERIOD);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let mut guard = manager.lock().await;
+            let pruned = guard.prune_offline().await;
+            for address in pruned {
+                log::warning!("Replica {address} timed out, dropping it");
+            }
+            if guard.has_replicas().await {
+                let _ = guard.request_acks(getack_bytes()).await;
+            }
+        }
+    });
+}
+
+impl std::fmt::Debug for ReplicationManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicationManager")
+            .field("write_offset", &self.write_offset)
+            .finish_non_exhaustive()
+    }
+}
+
+/// DELs for keys that expired on the master, waiting to enter the replication stream.
+/// Expiry queues them while it holds the keyspace lock, without waiting on replicas, and
+/// they go out ahead of the next propagated write, so a write that recreates a key
+/// always follows its DEL.
+pub type PendingDels = Arc<std::sync::Mutex<Vec<u8>>>;
 
 pub struct ReplicationManager {
     replicas: Arc<Mutex<HashMap<String, Replica>>>, // Keyed by host:port
+    backlog: Backlog,
+    pending_dels: PendingDels,
+    /// Offset just past the last propagated write; what WAIT needs replicas to reach.
+    write_offset: u64,
+    info: Arc<ServerInfo>,
 }
 
 impl ReplicationManager {
-    pub fn new() -> Self {
+    pub fn new(info: Arc<ServerInfo>) -> Self {
         let replicas = Arc::new(Mutex::new(HashMap::new()));
-        Self { replicas }
+        Self {
+            replicas,
+            backlog: Backlog::new(),
+            pending_dels: PendingDels::default(),
+            write_offset: 0,
+            info,
+        }
+    }
+
+    pub fn pending_dels(&self) -> PendingDels {
+        self.pending_dels.clone()
     }
 
+    /// Registers a replica and queues `pending` ahead of anything propagated later, so a
+    /// resumed replica sees the backlog tail before new writes.
     pub async fn add_replica(
         &mut self,
         addr: &str,
-        socket: SocketAddr,
-        writer: OwnedWriteHalf,
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+        pending: Vec<u8>,
     ) -> io::Result<()> {
-
-        let replica = Replica::new(socket, writer);
-        self.replicas.lock().await.insert(addr.to_string(), replica);
+        let replica = Replica::new(addr.to_string(), writer);
+        if !pending.is_empty() {
+            replica.send(pending).await?;
+        }
+        let mut guard = self.replicas.lock().await;
+        guard.insert(addr.to_string(), replica);
+        self.info.set_connected_slaves(guard.len());
         Ok(())
     }
 
+    /// Forgets a replica whose connection has closed.
+    pub async fn remove_replica(&mut self, addr: &str) {
+        let mut guard = self.replicas.lock().await;
+        guard.remove(addr);
+        self.info.set_connected_slaves(guard.len());
+    }
+
     pub async fn update_offset(&mut self, addr: &String, offset: u64) -> io::Result<()> {
         if let Some(replica) = self.replicas.lock().await.get_mut(addr) {
-            replica.acknowledged_offset = offset
+            replica.acknowledge(offset)
         }
         Ok(())
     }
 
     pub async fn replica_count(&self, offset: u64) -> io::Result<usize> {
         let guard = self.replicas.lock().await;
-        let len = guard.values().filter(|r| r.acknowledged_offset >= offset).count();
+        let len = guard
+            .values()
+            .filter(|r| r.is_online() && r.acknowledged_offset >= offset)
+            .count();
         Ok(len)
     }
 
-    pub async fn send_to_replicas(&self, command: RespCommand) -> io::Result<()> {
-        let replicas_guard = self.replicas.lock().await; // Lock the mutex asynchronously
-        for (_key, replica) in replicas_guard.iter() {
-            replica.send(command.clone()).await?;        }
+    async fn has_replicas(&self) -> bool {
+        !self.replicas.lock().await.is_empty()
+    }
+
+    /// Removes replicas that have not acknowledged within the timeout, returning their keys.
+    pub async fn prune_offline(&mut self) -> Vec<String> {
+        let mut guard = self.replicas.lock().await;
+        let offline: Vec<String> = guard
+            .iter()
+            .filter(|(_, replica)| !replica.is_online())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &offline {
+            guard.remove(key);
+        }
+        self.info.set_connected_slaves(guard.len());
+        offline
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.backlog.end_offset()
+    }
+
+    pub fn write_offset(&self) -> u64 {
+        self.write_offset
+    }
+
+    /// The bytes a replica needs to continue from `offset`, if the backlog still has them.
+    pub fn backlog_since(&self, offset: u64) -> Option<Vec<u8>> {
+        self.backlog.since(offset)
+    }
+
+    /// Propagates an already-encoded write command.
+    pub async fn send_to_replicas(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        self.append_to_stream(bytes).await?;
+        self.write_offset = self.backlog.end_offset();
         Ok(())
     }
 
+    /// Sends any queued expiry DELs without waiting for the next write.
+    pub async fn flush_pending_dels(&mut self) -> io::Result<()> {
+        self.send_to_replicas(vec![]).await
+    }
+
+    /// Sends REPLCONF GETACK. It still occupies the stream but is not a write to wait on.
+    pub async fn request_acks(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        self.append_to_stream(bytes).await
+    }
+
+    async fn append_to_stream(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        let bytes = {
+            let mut pending = self.pending_dels.lock().unwrap();
+            if pending.is_empty() {
+                bytes
+            } else {
+                let mut queued = std::mem::take(&mut *pending);
+                queued.extend(bytes);
+                queued
+            }
+        };
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.backlog.append(&bytes);
+        self.info.set_repl_offset(self.backlog.end_offset());
+        let mut replicas_guard = self.replicas.lock().await; // Lock the mutex asynchronously
+        // A closed channel only means that replica is gone; keep feeding the others.
+        let mut failed = Vec::new();
+        for (key, replica) in replicas_guard.iter().filter(|(_, r)| r.is_online()) {
+            if let Err(e) = replica.send(bytes.clone()).await {
+                log::warning!("{e}");
+                failed.push(key.clone());
+            }
+        }
+        for key in &failed {
+            replicas_guard.remove(key);
+        }
+        self.info.set_connected_slaves(replicas_guard.len());
+        Ok(())
+    }
 }
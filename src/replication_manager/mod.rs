@@ -1,2 +1,3 @@
+mod backlog;
 pub mod manager;
-mod replica;
\ No newline at end of file
+mod replica;
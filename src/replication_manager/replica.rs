@@ -1,53 +1,36 @@
-use futures::{io, SinkExt};
-use std::net::SocketAddr;
+use futures::io;
+use std::time::Duration;
 use tokio::{
-    net::tcp::OwnedWriteHalf,
+    io::{AsyncWrite, AsyncWriteExt},
     sync::mpsc::{self, Sender},
+    time::Instant,
 };
-use tokio_util::codec::FramedWrite;
 
-use crate::{
-    command::{ReplconfCommand, RespCommand},
-    error_helpers::invalid_data_err,
-    resp::{RespCodec, RespValue},
-};
+use crate::error_helpers::invalid_data_err;
+
+/// How long a replica may go without a REPLCONF ACK before it is considered offline,
+/// matching Redis's default `repl-timeout`.
+pub const REPLICA_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug)]
 pub struct Replica {
-    pub address: SocketAddr,
-    pub tx: Sender<RespCommand>,
+    pub address: String,
+    pub tx: Sender<Vec<u8>>,
     pub acknowledged_offset: u64,
+    pub last_ack: Instant,
 }
 
 impl Replica {
-    pub fn new(address: SocketAddr, stream: OwnedWriteHalf) -> Self {
-        let (tx, mut rx) = mpsc::channel::<RespCommand>(32);
+    pub fn new(address: String, mut stream: impl AsyncWrite + Unpin + Send + 'static) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(32);
 
+        // The replication stream is forwarded byte for byte so replica offsets line up
+        // with the master's backlog.
         tokio::spawn(async move {
-            let mut framed  = FramedWrite::new(stream, RespCodec);
-
-            while let Some(command) = rx.recv().await {
-                match command {
-                    
-                    RespCommand::Set { key, value, px: _ } => {
-                        let values = vec![
-                            RespValue::BulkString(Some(b"SET".to_vec())),
-                            RespValue::BulkString(Some(key.into())),
-                            RespValue::BulkString(Some(value)),
-                        ];
-                        let request = RespValue::Array(values);
-                        let _ = framed.send(request).await;
-                    }
-                    RespCommand::ReplconfCommand(ReplconfCommand::Getack(_)) => {
-                        let values = vec![
-                            RespValue::BulkString(Some(b"REPLCONF".to_vec())),
-                            RespValue::BulkString(Some(b"GETACK".to_vec())),
-                            RespValue::BulkString(Some(b"*".to_vec())),
-                        ];
-                        let request = RespValue::Array(values);
-                        let _ = framed.send(request).await;
-                    }
-                    _ => {}
+            while let Some(bytes) = rx.recv().await {
+                // Dropping the receiver closes the channel, so later sends report the failure.
+                if stream.write_all(&bytes).await.is_err() {
+                    break;
                 }
             }
         });
@@ -55,11 +38,21 @@ impl Replica {
             address,
             tx,
             acknowledged_offset: 0,
+            last_ack: Instant::now(),
         }
     }
 
-    pub async fn send(&self, command: RespCommand) -> io::Result<()> {
-        self.tx.send(command).await.map_err(|e| {
+    pub fn acknowledge(&mut self, offset: u64) {
+        self.acknowledged_offset = offset;
+        self.last_ack = Instant::now();
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.last_ack.elapsed() < REPLICA_TIMEOUT
+    }
+
+    pub async fn send(&self, bytes: Vec<u8>) -> io::Result<()> {
+        self.tx.send(bytes).await.map_err(|e| {
             invalid_data_err(format!(
                 "Failed to send command to replica {}: {}",
                 self.address, e
@@ -3,6 +3,7 @@ use std::io;
 use tokio_util::codec::{Decoder, Encoder};
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum RespValue {
     SimpleString(String),
     Error(String),
@@ -10,6 +11,10 @@ pub enum RespValue {
     BulkString(Option<Vec<u8>>),
     RDB(Option<Vec<u8>>), // None = $-1 // None = $-1
     Array(Vec<RespValue>),
+    /// Out-of-band data such as pub/sub messages, sent to RESP3 clients as `>`.
+    Push(Vec<RespValue>),
+    /// A RESP3 map, written as `%`. Only RESP3 clients are sent one.
+    Map(Vec<(RespValue, RespValue)>),
     NullArray,
 }
 
@@ -20,17 +25,15 @@ impl Decoder for RespCodec {
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Check if we have a full line ending in \r\n
-        let mut shadow = src.clone();
-        let before_len = shadow.len();
-
         if src.is_empty() {
             return Ok(None);
         }
-        if let Some(resp) = self.parse_bytes(src)? {
-            let used_len = before_len - src.len();
+        // Parse a copy so a frame that hasn't fully arrived is left for the next read.
+        let mut shadow = src.clone();
+        if let Some(resp) = self.parse_bytes(&mut shadow)? {
+            let used_len = src.len() - shadow.len();
 
-            let raw_bytes = shadow.split_to(used_len).to_vec(); // advance past the frame and extract only what's used
+            let raw_bytes = src.split_to(used_len).to_vec(); // advance past the frame and extract only what's used
             return Ok(Some((resp, raw_bytes)));
         }
         Ok(None)
@@ -146,9 +149,10 @@ impl RespCodec {
     pub fn write_array(
         &mut self,
         dst: &mut BytesMut,
+        prefix: u8,
         values: Vec<RespValue>,
     ) -> Result<(), io::Error> {
-        dst.put_u8(b'*');
+        dst.put_u8(prefix);
         dst.extend_from_slice(format!("{}\r\n", values.len()).as_bytes());
         for value in values {
             self.encode(value, dst)?
@@ -188,7 +192,17 @@ impl Encoder<RespValue> for RespCodec {
             RespValue::Error(e) => write_line(dst, b'-', &e),
             RespValue::Integer(i) => write_line(dst, b':', (i.to_string()).as_str()),
             RespValue::BulkString(c) => write_bulk_string(dst, c),
-            RespValue::Array(values) => self.write_array(dst, values),
+            RespValue::Array(values) => self.write_array(dst, b'*', values),
+            RespValue::Push(values) => self.write_array(dst, b'>', values),
+            RespValue::Map(pairs) => {
+                dst.put_u8(b'%');
+                dst.extend_from_slice(format!("{}\r\n", pairs.len()).as_bytes());
+                for (key, value) in pairs {
+                    self.encode(key, dst)?;
+                    self.encode(value, dst)?;
+                }
+                Ok(())
+            }
             RespValue::NullArray => {
                 dst.extend_from_slice(b"*-1\r\n");
                 Ok(())
@@ -0,0 +1,892 @@
+//! A small Lua subset for EVAL: enough for the usual check-and-set scripts without
+//! embedding a Lua engine. Supported are `local`, assignment to locals, `if`/`elseif`/
+//! `else`, `return`, table constructors, `KEYS`/`ARGV` indexing, arithmetic, comparison,
+//! `..`, `and`/`or`/`not`, `#`, `tonumber`, `tostring`, and `redis.call`, `redis.pcall`,
+//! `redis.status_reply` and `redis.error_reply`. Loops and function definitions are not.
+
+use std::{collections::HashMap, future::Future, pin::Pin, sync::RwLock};
+
+use crate::{resp::RespValue, sha1};
+
+/// Scripts seen by EVAL or SCRIPT LOAD, keyed by their lowercase SHA-1.
+#[derive(Debug, Default)]
+pub struct ScriptCache {
+    scripts: RwLock<HashMap<String, String>>,
+}
+
+impl ScriptCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caches `source` and returns its SHA-1.
+    pub fn load(&self, source: &str) -> String {
+        let sha = sha1::hex_digest(source.as_bytes());
+        self.scripts
+            .write()
+            .unwrap()
+            .entry(sha.clone())
+            .or_insert_with(|| source.to_string());
+        sha
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.scripts
+            .read()
+            .unwrap()
+            .contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.scripts
+            .read()
+            .unwrap()
+            .get(&sha.to_ascii_lowercase())
+            .cloned()
+    }
+}
+
+/// Runs Redis commands on behalf of a script.
+pub trait ScriptHost: Sync {
+    fn call<'a>(
+        &'a self,
+        args: Vec<Vec<u8>>,
+    ) -> Pin<Box<dyn Future<Output = RespValue> + Send + 'a>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Str(Vec<u8>),
+    Int(i64),
+    Sym(&'static str),
+}
+
+const SYMBOLS: [&str; 20] = [
+    "==", "~=", "<=", ">=", "..", "<", ">", "=", "(", ")", "[", "]", "{", "}", ",", ".", "+", "-",
+    "*", "#",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_ascii_whitespace() || c == b';' => i += 1,
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'\'' | b'"' => {
+                let quote = c;
+                let mut value = vec![];
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None | Some(b'\n') => {
+                            return Err(format!("user_script:{line}: unfinished string"))
+                        }
+                        Some(&b) if b == quote => break,
+                        Some(b'\\') => {
+                            let escaped = match bytes.get(i + 1) {
+                                Some(b'n') => b'\n',
+                                Some(b'r') => b'\r',
+                                Some(b't') => b'\t',
+                                Some(b'0') => 0,
+                                Some(&other @ (b'\\' | b'"' | b'\'')) => other,
+                                _ => {
+                                    return Err(format!(
+                                        "user_script:{line}: invalid escape sequence"
+                                    ))
+                                }
+                            };
+                            value.push(escaped);
+                            i += 2;
+                        }
+                        Some(&b) => {
+                            value.push(b);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push((Token::Str(value), line));
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let number = source[start..i].parse::<i64>().map_err(|_| {
+                    format!(
+                        "user_script:{line}: unsupported number '{}'",
+                        &source[start..i]
+                    )
+                })?;
+                tokens.push((Token::Int(number), line));
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push((Token::Name(source[start..i].to_string()), line));
+            }
+            _ => {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| source[i..].starts_with(**symbol))
+                    .ok_or_else(|| {
+                        format!("user_script:{line}: unexpected symbol near '{}'", c as char)
+                    })?;
+                tokens.push((Token::Sym(symbol), line));
+                i += symbol.len();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Call,
+    PCall,
+    StatusReply,
+    ErrorReply,
+    ToNumber,
+    ToString,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Str(Vec<u8>),
+    Name(String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    Table(Vec<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Len(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Local(String, Expr),
+    Assign(String, Expr),
+    If(Vec<(Expr, Vec<Stmt>)>, Vec<Stmt>),
+    Return(Expr),
+    Call(Expr),
+}
+
+/// A parsed script, ready to run any number of times.
+#[derive(Debug, Clone)]
+pub struct Program {
+    body: Vec<Stmt>,
+}
+
+/// How deeply blocks and expressions may nest, matching Lua's LUAI_MAXCCALLS.
+const MAX_SYNTAX_LEVELS: usize = 200;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: impl std::fmt::Display) -> Result<T, String> {
+        Err(format!("user_script:{}: {message}", self.line()))
+    }
+
+    /// Runs a recursive production one level deeper, so a hostile script can't
+    /// exhaust the stack.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth >= MAX_SYNTAX_LEVELS {
+            return self.error("chunk has too many syntax levels");
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn is_sym(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Sym(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(name)) if name == keyword)
+    }
+
+    fn expect_sym(&mut self, symbol: &str) -> Result<(), String> {
+        if self.is_sym(symbol) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(format!("'{symbol}' expected"))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(format!("'{keyword}' expected"))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            _ => {
+                self.pos -= 1;
+                self.error("<name> expected")
+            }
+        }
+    }
+
+    /// Statements up to (not including) one of the closing keywords.
+    fn block(&mut self, closers: &[&str]) -> Result<Vec<Stmt>, String> {
+        let mut body = vec![];
+        while self.peek().is_some() && !closers.iter().any(|closer| self.is_keyword(closer)) {
+            let stmt = self.nested(Self::statement)?;
+            let returned = matches!(stmt, Stmt::Return(_));
+            body.push(stmt);
+            if returned {
+                break;
+            }
+        }
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        match self.peek() {
+            Some(Token::Name(keyword)) => match keyword.as_str() {
+                "local" => {
+                    self.pos += 1;
+                    let name = self.name()?;
+                    let value = if self.is_sym("=") {
+                        self.pos += 1;
+                        self.expression(0)?
+                    } else {
+                        Expr::Nil
+                    };
+                    Ok(Stmt::Local(name, value))
+                }
+                "return" => {
+                    self.pos += 1;
+                    let value = if self.peek().is_none()
+                        || ["end", "else", "elseif"].iter().any(|k| self.is_keyword(k))
+                    {
+                        Expr::Nil
+                    } else {
+                        self.expression(0)?
+                    };
+                    Ok(Stmt::Return(value))
+                }
+                "if" => {
+                    self.pos += 1;
+                    let mut branches = vec![];
+                    let condition = self.expression(0)?;
+                    self.expect_keyword("then")?;
+                    branches.push((condition, self.block(&["elseif", "else", "end"])?));
+                    let mut otherwise = vec![];
+                    loop {
+                        if self.is_keyword("elseif") {
+                            self.pos += 1;
+                            let condition = self.expression(0)?;
+                            self.expect_keyword("then")?;
+                            branches.push((condition, self.block(&["elseif", "else", "end"])?));
+                        } else if self.is_keyword("else") {
+                            self.pos += 1;
+                            otherwise = self.block(&["end"])?;
+                        } else {
+                            self.expect_keyword("end")?;
+                            break;
+                        }
+                    }
+                    Ok(Stmt::If(branches, otherwise))
+                }
+                "while" | "for" | "repeat" | "function" | "do" | "goto" | "break" => {
+                    self.error(format!("'{keyword}' is not supported"))
+                }
+                _ => {
+                    let start = self.pos;
+                    let name = self.name()?;
+                    if self.is_sym("=") {
+                        self.pos += 1;
+                        return Ok(Stmt::Assign(name, self.expression(0)?));
+                    }
+                    self.pos = start;
+                    match self.expression(0)? {
+                        call @ Expr::Call(..) => Ok(Stmt::Call(call)),
+                        _ => self.error("syntax error near statement"),
+                    }
+                }
+            },
+            _ => self.error("unexpected symbol"),
+        }
+    }
+
+    fn binary_op(&self) -> Option<(BinOp, u8)> {
+        let op = match self.peek()? {
+            Token::Name(name) if name == "or" => (BinOp::Or, 1),
+            Token::Name(name) if name == "and" => (BinOp::And, 2),
+            Token::Sym("==") => (BinOp::Eq, 3),
+            Token::Sym("~=") => (BinOp::Ne, 3),
+            Token::Sym("<") => (BinOp::Lt, 3),
+            Token::Sym("<=") => (BinOp::Le, 3),
+            Token::Sym(">") => (BinOp::Gt, 3),
+            Token::Sym(">=") => (BinOp::Ge, 3),
+            Token::Sym("..") => (BinOp::Concat, 4),
+            Token::Sym("+") => (BinOp::Add, 5),
+            Token::Sym("-") => (BinOp::Sub, 5),
+            Token::Sym("*") => (BinOp::Mul, 6),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Precedence climbing; `..` is the only right-associative operator handled.
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some((op, precedence)) = self.binary_op() {
+            if precedence <= min_precedence
+                && !(op == BinOp::Concat && precedence == min_precedence)
+            {
+                break;
+            }
+            self.pos += 1;
+            let next_min = if op == BinOp::Concat {
+                precedence - 1
+            } else {
+                precedence
+            };
+            let right = self.expression(next_min)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.nested(Self::unary_inner)
+    }
+
+    fn unary_inner(&mut self) -> Result<Expr, String> {
+        if self.is_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.is_sym("-") {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.is_sym("#") {
+            self.pos += 1;
+            return Ok(Expr::Len(Box::new(self.unary()?)));
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.is_sym("[") {
+            self.pos += 1;
+            let index = self.expression(0)?;
+            self.expect_sym("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, String> {
+        self.expect_sym("(")?;
+        let mut args = vec![];
+        if !self.is_sym(")") {
+            loop {
+                args.push(self.expression(0)?);
+                if !self.is_sym(",") {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+        self.expect_sym(")")?;
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Int(n)) => Ok(Expr::Int(n)),
+            Some(Token::Str(s)) => Ok(Expr::Str(s)),
+            Some(Token::Sym("(")) => {
+                let expr = self.expression(0)?;
+                self.expect_sym(")")?;
+                Ok(expr)
+            }
+            Some(Token::Sym("{")) => {
+                let mut items = vec![];
+                while !self.is_sym("}") {
+                    items.push(self.expression(0)?);
+                    if !self.is_sym(",") {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                self.expect_sym("}")?;
+                Ok(Expr::Table(items))
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "nil" => Ok(Expr::Nil),
+                "true" => Ok(Expr::Bool(true)),
+                "false" => Ok(Expr::Bool(false)),
+                "redis" => {
+                    self.expect_sym(".")?;
+                    let function = match self.name()?.as_str() {
+                        "call" => Function::Call,
+                        "pcall" => Function::PCall,
+                        "status_reply" => Function::StatusReply,
+                        "error_reply" => Function::ErrorReply,
+                        other => return self.error(format!("redis.{other} is not supported")),
+                    };
+                    Ok(Expr::Call(function, self.arguments()?))
+                }
+                "tonumber" => Ok(Expr::Call(Function::ToNumber, self.arguments()?)),
+                "tostring" => Ok(Expr::Call(Function::ToString, self.arguments()?)),
+                _ if self.is_sym("(") => self.error(format!("function '{name}' is not supported")),
+                _ => Ok(Expr::Name(name)),
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("unexpected symbol")
+            }
+        }
+    }
+}
+
+pub fn compile(source: &str) -> Result<Program, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        depth: 0,
+    };
+    let body = parser.block(&[])?;
+    if parser.peek().is_some() {
+        return parser.error("'<eof>' expected");
+    }
+    Ok(Program { body })
+}
+
+/// Lua values as scripts see them. Status and error replies keep their RESP meaning.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Str(Vec<u8>),
+    Table(Vec<Value>),
+    Status(String),
+    Error(String),
+}
+
+impl Value {
+    fn from_resp(value: RespValue) -> Self {
+        match value {
+            RespValue::Integer(n) => Value::Int(n),
+            RespValue::BulkString(Some(bytes)) => Value::Str(bytes),
+            // Redis converts nil replies to false, not nil, so they survive in tables.
+            RespValue::BulkString(None) | RespValue::NullArray => Value::Bool(false),
+            RespValue::Array(items) | RespValue::Push(items) => {
+                Value::Table(items.into_iter().map(Value::from_resp).collect())
+            }
+            // Scripts speak RESP2, where a map is its pairs flattened.
+            RespValue::Map(pairs) => Value::Table(
+                pairs
+                    .into_iter()
+                    .flat_map(|(key, value)| [Value::from_resp(key), Value::from_resp(value)])
+                    .collect(),
+            ),
+            RespValue::SimpleString(status) => Value::Status(status),
+            RespValue::Error(error) => Value::Error(error),
+            RespValue::RDB(_) => Value::Nil,
+        }
+    }
+
+    fn into_resp(self) -> RespValue {
+        match self {
+            Value::Nil | Value::Bool(false) => RespValue::BulkString(None),
+            Value::Bool(true) => RespValue::Integer(1),
+            Value::Int(n) => RespValue::Integer(n),
+            Value::Str(bytes) => RespValue::BulkString(Some(bytes)),
+            // Like Lua's length operator, a table ends at its first nil.
+            Value::Table(items) => RespValue::Array(
+                items
+                    .into_iter()
+                    .take_while(|item| *item != Value::Nil)
+                    .map(Value::into_resp)
+                    .collect(),
+            ),
+            Value::Status(status) => RespValue::SimpleString(status),
+            Value::Error(error) => RespValue::Error(error),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Int(_) => "number",
+            Value::Str(_) => "string",
+            Value::Table(_) | Value::Status(_) | Value::Error(_) => "table",
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Lua coerces numeric strings in arithmetic.
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            Value::Str(bytes) => std::str::from_utf8(bytes).ok()?.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Strings and numbers both work as command arguments and in `..`.
+    fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Value::Int(n) => Some(n.to_string().into_bytes()),
+            Value::Str(bytes) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// How a script stops early: `Return` carries its value, `Raise` an error reply.
+enum Flow {
+    Return(Value),
+    Raise(String),
+}
+
+type Eval<'a, T> = Pin<Box<dyn Future<Output = Result<T, Flow>> + Send + 'a>>;
+
+struct Interpreter<'h> {
+    host: &'h dyn ScriptHost,
+    variables: HashMap<String, Value>,
+}
+
+fn runtime_error(message: impl std::fmt::Display) -> Flow {
+    Flow::Raise(format!("ERR user_script: {message}"))
+}
+
+impl<'h> Interpreter<'h> {
+    fn run_block<'a>(&'a mut self, body: &'a [Stmt]) -> Eval<'a, ()> {
+        Box::pin(async move {
+            for stmt in body {
+                match stmt {
+                    Stmt::Local(name, expr) => {
+                        let value = self.eval(expr).await?;
+                        self.variables.insert(name.clone(), value);
+                    }
+                    Stmt::Assign(name, expr) => {
+                        let value = self.eval(expr).await?;
+                        match self.variables.get_mut(name) {
+                            Some(slot) => *slot = value,
+                            None => {
+                                return Err(runtime_error(format!(
+                                    "Attempt to modify a readonly table or create global variable '{name}'"
+                                )))
+                            }
+                        }
+                    }
+                    Stmt::If(branches, otherwise) => {
+                        let mut taken = None;
+                        for (condition, branch) in branches {
+                            if self.eval(condition).await?.is_truthy() {
+                                taken = Some(branch);
+                                break;
+                            }
+                        }
+                        self.run_block(taken.unwrap_or(otherwise)).await?;
+                    }
+                    Stmt::Return(expr) => return Err(Flow::Return(self.eval(expr).await?)),
+                    Stmt::Call(expr) => {
+                        self.eval(expr).await?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn eval<'a>(&'a mut self, expr: &'a Expr) -> Eval<'a, Value> {
+        Box::pin(async move {
+            let value = match expr {
+                Expr::Nil => Value::Nil,
+                Expr::Bool(b) => Value::Bool(*b),
+                Expr::Int(n) => Value::Int(*n),
+                Expr::Str(s) => Value::Str(s.clone()),
+                Expr::Name(name) => self.variables.get(name).cloned().ok_or_else(|| {
+                    runtime_error(format!(
+                        "Script attempted to access nonexistent global variable '{name}'"
+                    ))
+                })?,
+                Expr::Index(table, index) => {
+                    let table = self.eval(table).await?;
+                    let index = self.eval(index).await?;
+                    let Value::Table(items) = table else {
+                        return Err(runtime_error(format!(
+                            "attempt to index a {} value",
+                            table.type_name()
+                        )));
+                    };
+                    // Lua tables are 1-based.
+                    index
+                        .as_int()
+                        .filter(|i| *i >= 1)
+                        .and_then(|i| items.get(i as usize - 1).cloned())
+                        .unwrap_or(Value::Nil)
+                }
+                Expr::Table(items) => {
+                    let mut values = Vec::with_capacity(items.len());
+                    for item in items {
+                        values.push(self.eval(item).await?);
+                    }
+                    Value::Table(values)
+                }
+                Expr::Not(inner) => Value::Bool(!self.eval(inner).await?.is_truthy()),
+                Expr::Neg(inner) => {
+                    let value = self.eval(inner).await?;
+                    let n = value.as_int().ok_or_else(|| {
+                        runtime_error(format!(
+                            "attempt to perform arithmetic on a {} value",
+                            value.type_name()
+                        ))
+                    })?;
+                    Value::Int(n.checked_neg().ok_or_else(|| runtime_error("integer overflow"))?)
+                }
+                Expr::Len(inner) => match self.eval(inner).await? {
+                    Value::Str(bytes) => Value::Int(bytes.len() as i64),
+                    Value::Table(items) => Value::Int(items.len() as i64),
+                    other => {
+                        return Err(runtime_error(format!(
+                            "attempt to get length of a {} value",
+                            other.type_name()
+                        )))
+                    }
+                },
+                Expr::Binary(BinOp::And, left, right) => {
+                    let left = self.eval(left).await?;
+                    if left.is_truthy() {
+                        self.eval(right).await?
+                    } else {
+                        left
+                    }
+                }
+                Expr::Binary(BinOp::Or, left, right) => {
+                    let left = self.eval(left).await?;
+                    if left.is_truthy() {
+                        left
+                    } else {
+                        self.eval(right).await?
+                    }
+                }
+                Expr::Binary(op, left, right) => {
+                    let left = self.eval(left).await?;
+                    let right = self.eval(right).await?;
+                    binary(*op, left, right)?
+                }
+                Expr::Call(function, args) => {
+                    let mut values = Vec::with_capacity(args.len());
+                    for arg in args {
+                        values.push(self.eval(arg).await?);
+                    }
+                    self.call(*function, values).await?
+                }
+            };
+            Ok(value)
+        })
+    }
+
+    async fn call(&self, function: Function, args: Vec<Value>) -> Result<Value, Flow> {
+        let first = args.first().cloned().unwrap_or(Value::Nil);
+        match function {
+            Function::Call | Function::PCall => {
+                if args.is_empty() {
+                    return Err(runtime_error(
+                        "Please specify at least one argument for this redis lib call",
+                    ));
+                }
+                let argv = args
+                    .iter()
+                    .map(Value::as_bytes)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        runtime_error("Lua redis lib command arguments must be strings or integers")
+                    })?;
+                match Value::from_resp(self.host.call(argv).await) {
+                    Value::Error(error) if matches!(function, Function::Call) => {
+                        Err(Flow::Raise(error))
+                    }
+                    value => Ok(value),
+                }
+            }
+            Function::StatusReply => match first.as_bytes() {
+                Some(bytes) => Ok(Value::Status(String::from_utf8_lossy(&bytes).into_owned())),
+                None => Err(runtime_error("wrong number or type of arguments")),
+            },
+            Function::ErrorReply => match first.as_bytes() {
+                Some(bytes) => {
+                    let message = String::from_utf8_lossy(&bytes).into_owned();
+                    // Replies without an error code get the generic one, as in Redis.
+                    if message
+                        .split_whitespace()
+                        .next()
+                        .is_some_and(|code| code.chars().all(|c| c.is_ascii_uppercase()))
+                    {
+                        Ok(Value::Error(message))
+                    } else {
+                        Ok(Value::Error(format!("ERR {message}")))
+                    }
+                }
+                None => Err(runtime_error("wrong number or type of arguments")),
+            },
+            Function::ToNumber => Ok(first.as_int().map_or(Value::Nil, Value::Int)),
+            Function::ToString => Ok(Value::Str(match first {
+                Value::Nil => b"nil".to_vec(),
+                Value::Bool(b) => b.to_string().into_bytes(),
+                Value::Int(n) => n.to_string().into_bytes(),
+                Value::Str(bytes) => bytes,
+                _ => b"table".to_vec(),
+            })),
+        }
+    }
+}
+
+fn binary(op: BinOp, left: Value, right: Value) -> Result<Value, Flow> {
+    let arithmetic = |f: fn(i64, i64) -> Option<i64>| match (left.as_int(), right.as_int()) {
+        (Some(a), Some(b)) => f(a, b)
+            .map(Value::Int)
+            .ok_or_else(|| runtime_error("integer overflow")),
+        _ => {
+            let bad = if left.as_int().is_none() {
+                &left
+            } else {
+                &right
+            };
+            Err(runtime_error(format!(
+                "attempt to perform arithmetic on a {} value",
+                bad.type_name()
+            )))
+        }
+    };
+    let ordering = || match (&left, &right) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::Str(a), Value::Str(b)) => Ok(a.cmp(b)),
+        _ => Err(runtime_error(format!(
+            "attempt to compare {} with {}",
+            left.type_name(),
+            right.type_name()
+        ))),
+    };
+    let value = match op {
+        BinOp::Eq => Value::Bool(left == right),
+        BinOp::Ne => Value::Bool(left != right),
+        BinOp::Lt => Value::Bool(ordering()?.is_lt()),
+        BinOp::Le => Value::Bool(ordering()?.is_le()),
+        BinOp::Gt => Value::Bool(ordering()?.is_gt()),
+        BinOp::Ge => Value::Bool(ordering()?.is_ge()),
+        BinOp::Add => arithmetic(i64::checked_add)?,
+        BinOp::Sub => arithmetic(i64::checked_sub)?,
+        BinOp::Mul => arithmetic(i64::checked_mul)?,
+        BinOp::Concat => match (left.as_bytes(), right.as_bytes()) {
+            (Some(mut a), Some(b)) => {
+                a.extend(b);
+                Value::Str(a)
+            }
+            _ => {
+                let bad = if left.as_bytes().is_none() {
+                    &left
+                } else {
+                    &right
+                };
+                return Err(runtime_error(format!(
+                    "attempt to concatenate a {} value",
+                    bad.type_name()
+                )));
+            }
+        },
+        BinOp::And | BinOp::Or => unreachable!("short-circuit operators are evaluated lazily"),
+    };
+    Ok(value)
+}
+
+/// Runs `program` with the given KEYS and ARGV, turning its return value into a reply.
+pub async fn run(
+    program: &Program,
+    keys: Vec<Vec<u8>>,
+    argv: Vec<Vec<u8>>,
+    host: &dyn ScriptHost,
+) -> RespValue {
+    let table = |items: Vec<Vec<u8>>| Value::Table(items.into_iter().map(Value::Str).collect());
+    let mut interpreter = Interpreter {
+        host,
+        variables: HashMap::from([
+            ("KEYS".to_string(), table(keys)),
+            ("ARGV".to_string(), table(argv)),
+        ]),
+    };
+    match interpreter.run_block(&program.body).await {
+        Ok(()) => RespValue::BulkString(None),
+        Err(Flow::Return(value)) => value.into_resp(),
+        Err(Flow::Raise(error)) => RespValue::Error(error),
+    }
+}
@@ -1,32 +1,54 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::{
+    acl::Acl,
+    aof::Aof,
+    latency::LatencyMonitor,
     rdb_parser::config::RdbConfig,
     replication_manager::manager::ReplicationManager,
+    scripting::ScriptCache,
     server_info::ServerInfo,
     shared_store::shared_store::Store,
 };
 
+#[derive(Clone)]
 pub struct ServerContext {
     pub store: Arc<Store>,
     pub rdb: Arc<RdbConfig>,
     pub manager: Arc<Mutex<ReplicationManager>>,
     pub info: Arc<ServerInfo>,
+    pub aof: Option<Arc<Aof>>,
+    pub acl: Arc<Acl>,
+    pub scripts: Arc<ScriptCache>,
+    pub latency: Arc<LatencyMonitor>,
+    /// Held exclusively while a script runs and shared by every other command, so a
+    /// script's calls see no other client's writes in between.
+    pub script_lock: Arc<RwLock<()>>,
 }
 
 impl ServerContext {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         store: Arc<Store>,
         rdb: Arc<RdbConfig>,
         manager: Arc<Mutex<ReplicationManager>>,
         info: Arc<ServerInfo>,
+        aof: Option<Arc<Aof>>,
+        acl: Arc<Acl>,
+        scripts: Arc<ScriptCache>,
+        latency: Arc<LatencyMonitor>,
     ) -> Self {
         Self {
             store,
             rdb,
             manager,
             info,
+            aof,
+            acl,
+            scripts,
+            latency,
+            script_lock: Arc::new(RwLock::new(())),
         }
     }
 }
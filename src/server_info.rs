@@ -1,4 +1,10 @@
-use std::io::{self};
+use std::{
+    io::{self},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+        RwLock,
+    },
+};
 
 use futures::{SinkExt, StreamExt};
 use tokio::{
@@ -9,10 +15,12 @@ use tokio_util::codec::Framed;
 
 use crate::{
     error_helpers,
+    log::{self, LogLevel},
+    random,
     resp::{RespCodec, RespValue},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ServerInfo {
     pub redis_version: String,
     pub redis_mode: String,
@@ -26,19 +34,30 @@ pub struct ServerInfo {
     pub executable: String,
     pub config_file: Option<String>,
     pub tcp_port: u16,
+    /// Path of the Unix domain socket to accept clients on besides TCP, if any.
+    pub unixsocket: Option<String>,
     pub role: String,
     pub repl_host: Option<String>,
     pub repl_port: Option<u16>, // <- add this
-    pub master_replid: String,
-    pub master_repl_offset: u64,
+    master_replid: RwLock<String>,
+    master_replid2: RwLock<String>,
+    second_repl_offset: AtomicI64,
+    master_repl_offset: AtomicU64,
+    /// On a replica, how far into the master's replication stream it has applied.
+    slave_repl_offset: AtomicU64,
+    connected_slaves: AtomicUsize,
 }
 
+const EMPTY_REPLID: &str = "0000000000000000000000000000000000000000";
+
 impl ServerInfo {
     pub fn new() -> io::Result<Self> {
         let mut tcp_port = 6379u16;
+        let mut unixsocket = None;
         let mut role = "master";
         let mut repl_host = None;
         let mut repl_port = None;
+        let mut loglevel = LogLevel::Notice;
         let mut args = std::env::args().peekable();
         while let Some(arg) = args.next() {
             match arg.as_str() {
@@ -47,14 +66,22 @@ impl ServerInfo {
                         tcp_port = port_str.parse().unwrap_or(6379u16)
                     }
                 }
+                "--unixsocket" => unixsocket = args.next().filter(|path| !path.is_empty()),
                 "--replicaof" => {
                     role = "slave";
                     parse_repl_instance(&mut args, &mut repl_host, &mut repl_port)?;
                 }
+                "--loglevel" => {
+                    let name = args.next().unwrap_or_default();
+                    loglevel = LogLevel::parse(&name).ok_or_else(|| {
+                        error_helpers::invalid_data_err(format!("Invalid loglevel '{name}'"))
+                    })?;
+                }
 
                 _ => {}
             }
         }
+        log::set_level(loglevel);
         Ok(Self {
             redis_version: "7.2.0".into(),
             redis_mode: "standalone".into(),
@@ -68,16 +95,73 @@ impl ServerInfo {
             executable: std::env::args().next().unwrap_or_default(),
             config_file: None,
             tcp_port,
+            unixsocket,
             role: role.into(),
-            master_replid: "8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb".into(),
-            master_repl_offset: 0,
+            master_replid: RwLock::new("8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb".into()),
+            master_replid2: RwLock::new(EMPTY_REPLID.into()),
+            second_repl_offset: AtomicI64::new(-1),
+            master_repl_offset: AtomicU64::new(0),
+            slave_repl_offset: AtomicU64::new(0),
+            connected_slaves: AtomicUsize::new(0),
             repl_host,
             repl_port, // <- default role }
         })
     }
 
+    pub fn master_replid(&self) -> String {
+        self.master_replid.read().unwrap().clone()
+    }
+
+    /// Swaps in a freshly generated replication ID so replicas must fully resync.
+    pub fn change_replid(&self) {
+        *self.master_replid.write().unwrap() = random::random_hex_id();
+        *self.master_replid2.write().unwrap() = EMPTY_REPLID.into();
+        self.second_repl_offset.store(-1, Ordering::Relaxed);
+    }
+
+    /// Adopts a master's replication ID, keeping the previous one as the second ID so
+    /// history up to `offset` stays valid for partial resyncs.
+    pub fn shift_replid(&self, replid: &str, offset: u64) {
+        let mut current = self.master_replid.write().unwrap();
+        if *current == replid {
+            return;
+        }
+        *self.master_replid2.write().unwrap() = std::mem::replace(&mut *current, replid.into());
+        self.second_repl_offset
+            .store(offset as i64 + 1, Ordering::Relaxed);
+    }
+
+    pub fn set_master_replid(&self, replid: &str) {
+        *self.master_replid.write().unwrap() = replid.into();
+    }
+
+    /// Whether a replica asking for `psync_offset` under `replid` shares our history.
+    pub fn can_continue(&self, replid: &str, psync_offset: u64) -> bool {
+        if *self.master_replid.read().unwrap() == replid {
+            return true;
+        }
+        *self.master_replid2.read().unwrap() == replid
+            && psync_offset as i64 <= self.second_repl_offset.load(Ordering::Relaxed)
+    }
+
+    pub fn repl_offset(&self) -> u64 {
+        self.master_repl_offset.load(Ordering::Relaxed)
+    }
+
+    pub fn set_repl_offset(&self, offset: u64) {
+        self.master_repl_offset.store(offset, Ordering::Relaxed);
+    }
+
+    pub fn set_slave_repl_offset(&self, offset: u64) {
+        self.slave_repl_offset.store(offset, Ordering::Relaxed);
+    }
+
+    pub fn set_connected_slaves(&self, count: usize) {
+        self.connected_slaves.store(count, Ordering::Relaxed);
+    }
+
     pub fn info_section(&self) -> String {
-        format!(
+        let mut section = format!(
             "# Server\n\
             redis_version:{}\n\
             redis_mode:{}\n\
@@ -92,8 +176,11 @@ impl ServerInfo {
             config_file:{}\n\
             tcp_port:{}\n\
             role:{}\n\
+            connected_slaves:{}\n\
             master_replid:{}\n\
-            master_repl_offset:{}\n",
+            master_replid2:{}\n\
+            master_repl_offset:{}\n\
+            second_repl_offset:{}\n",
             self.redis_version,
             self.redis_mode,
             self.os,
@@ -107,15 +194,31 @@ impl ServerInfo {
             self.config_file.clone().unwrap_or_default(),
             self.tcp_port,
             self.role,
-            self.master_replid,
-            self.master_repl_offset
-        )
+            self.connected_slaves.load(Ordering::Relaxed),
+            self.master_replid(),
+            self.master_replid2.read().unwrap(),
+            self.repl_offset(),
+            self.second_repl_offset.load(Ordering::Relaxed)
+        );
+        // Comparing this with the master's master_repl_offset shows how far behind it is.
+        if let (Some(host), Some(port)) = (&self.repl_host, self.repl_port) {
+            section.push_str(&format!(
+                "master_host:{host}\n\
+                master_port:{port}\n\
+                slave_repl_offset:{}\n",
+                self.slave_repl_offset.load(Ordering::Relaxed)
+            ));
+        }
+        section
     }
 
+    /// Connects to the master and negotiates a sync. With `resume_offset` set, asks to
+    /// continue from there; the returned flag is true when the master chose a full resync.
     pub async fn handshake(
         &self,
+        resume_offset: Option<u64>,
     ) -> Result<
-        Option<(Framed<TcpStream, RespCodec>, Vec<u8>)>,
+        Option<(Framed<TcpStream, RespCodec>, bool)>,
         Box<dyn std::error::Error + Send + Sync>,
     > {
         if self.role.as_str() == "master" {
@@ -151,23 +254,37 @@ impl ServerInfo {
                 .await?;
             let _ = framed.next().await;
 
+            let (replid, offset) = match resume_offset {
+                Some(offset) => (self.master_replid(), (offset + 1).to_string()),
+                None => ("?".to_string(), "-1".to_string()),
+            };
             framed
                 .send(RespValue::Array(vec![
                     RespValue::BulkString(Some("PSYNC".into())),
-                    RespValue::BulkString(Some("?".into())),
-                    RespValue::BulkString(Some("-1".into())),
+                    RespValue::BulkString(Some(replid.into_bytes())),
+                    RespValue::BulkString(Some(offset.into_bytes())),
                 ]))
                 .await?;
-            if let Some(Ok((RespValue::SimpleString(fullresync_line), _))) = framed.next().await {
-                if !fullresync_line.starts_with("FULLRESYNC") {
-                    return Err("Expected +FULLRESYNC line".into());
+            let Some(Ok((RespValue::SimpleString(reply), _))) = framed.next().await else {
+                return Err("Expected +FULLRESYNC or +CONTINUE line".into());
+            };
+            let parts: Vec<&str> = reply.split_whitespace().collect();
+            match parts.as_slice() {
+                ["FULLRESYNC", replid, offset] => {
+                    log::info!("Got FULLRESYNC: {reply}");
+                    self.set_master_replid(replid);
+                    self.set_repl_offset(offset.parse()?);
+                    return Ok(Some((framed, true)));
                 }
-                println!("Got FULLRESYNC: {fullresync_line}");
-            } else {
-                return Err("Expected +FULLRESYNC line".into());
+                ["CONTINUE", rest @ ..] => {
+                    log::info!("Got CONTINUE: {reply}");
+                    if let (Some(replid), Some(offset)) = (rest.first(), resume_offset) {
+                        self.shift_replid(replid, offset);
+                    }
+                    return Ok(Some((framed, false)));
+                }
+                _ => return Err("Expected +FULLRESYNC or +CONTINUE line".into()),
             }
-
-            return Ok(Some((framed, vec![])));
         }
         Ok(None)
     }
@@ -1,90 +1,154 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::collections::HashMap;
 
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{error::TrySendError, Sender};
 
-use crate::{
-    resp::RespValue,
-    shared_store::shared_store::{Entry, RedisValue, Store},
-};
+use crate::{glob, log, resp::RespValue, shared_store::shared_store::Store};
 
-#[derive(Debug, Clone)]
+/// Subscriptions are connection state rather than data, so they live beside the keyspace
+/// keyed by the raw channel name or pattern bytes. Subscribers are keyed by client id.
+pub type Subscriptions = HashMap<Vec<u8>, Channel>;
+
+#[derive(Debug, Clone, Default)]
 pub struct Channel {
-    #[allow(dead_code)]
-    name: String,
-    pub clients: HashMap<SocketAddr, Sender<RespValue>>,
+    pub clients: HashMap<u64, Sender<RespValue>>,
 }
 
-impl Channel {
-    pub fn new(name: String) -> Self {
-        Self {
-            name,
-            clients: HashMap::new(),
-        }
+impl Store {
+    pub async fn subscribe(&self, channel_name: Vec<u8>, client: u64, tx: Sender<RespValue>) {
+        let mut channels = self.channels.write().await;
+        channels
+            .entry(channel_name)
+            .or_default()
+            .clients
+            .insert(client, tx);
     }
-}
 
-impl Store {
-    pub async fn subscribe(&self, channel_name: String, client: SocketAddr, tx: Sender<RespValue>) {
-        let channel_name = format!("channel-{channel_name}");
-        let mut keyspace = self.keyspace.write().await;
-        if let Some(entry) = keyspace.get_mut(&channel_name) {
-            if let RedisValue::Channel(channel) = &mut entry.value {
-                channel.clients.insert(client, tx);
-            }
-        } else {
-            let mut channel = Channel::new(channel_name.clone());
-            channel.clients.insert(client, tx);
-            let entry: Entry = Entry::new(RedisValue::Channel(channel), None);
-            keyspace.insert(channel_name, entry);
-        }
+    pub async fn psubscribe(&self, pattern: Vec<u8>, client: u64, tx: Sender<RespValue>) {
+        let mut patterns = self.patterns.write().await;
+        patterns.entry(pattern).or_default().clients.insert(client, tx);
     }
 
+    pub async fn ssubscribe(&self, channel_name: Vec<u8>, client: u64, tx: Sender<RespValue>) {
+        let mut shard_channels = self.shard_channels.write().await;
+        shard_channels
+            .entry(channel_name)
+            .or_default()
+            .clients
+            .insert(client, tx);
+    }
+
+    /// Delivers `msg` to the channel's subscribers and to every pattern subscriber whose
+    /// pattern matches, returning how many deliveries were made in total. Delivery never
+    /// waits on a subscriber: the publisher may be one of them, and it is busy publishing
+    /// rather than draining its queue.
     pub async fn send_to_channel(
         &self,
-        channel_name: String,
-        msg: String,
+        channel_name: Vec<u8>,
+        msg: Vec<u8>,
     ) -> anyhow::Result<usize> {
-        let called_name = channel_name.clone();
-        let channel_name = format!("channel-{channel_name}");
-        let mut keyspace = self.keyspace.write().await;
-        if let Some(entry) = keyspace.get_mut(&channel_name) {
-            match &mut entry.value {
-                RedisValue::Channel(channel) => {
-                    let size = channel.clients.len();
-                    for tx in channel.clients.values() {
-                        let response = vec![
-                            RespValue::BulkString(Some("message".into())),
-                            RespValue::BulkString(Some(called_name.clone().into())),
-                            RespValue::BulkString(Some(msg.clone().into())),
-                        ];
-                        tx.send(RespValue::Array(response)).await?;
-                    }
-                    Ok(size)
-                }
-                _ => Ok(0),
+        let mut size = 0;
+        let channels = self.channels.read().await;
+        if let Some(channel) = channels.get(&channel_name) {
+            for tx in channel.clients.values() {
+                let response = vec![
+                    RespValue::BulkString(Some("message".into())),
+                    RespValue::BulkString(Some(channel_name.clone())),
+                    RespValue::BulkString(Some(msg.clone())),
+                ];
+                size += deliver(tx, response) as usize;
             }
-        } else {
-            Ok(0)
         }
+        drop(channels);
+
+        let patterns = self.patterns.read().await;
+        for (pattern, channel) in patterns.iter() {
+            if !glob::glob_match(pattern, &channel_name) {
+                continue;
+            }
+            for tx in channel.clients.values() {
+                let response = vec![
+                    RespValue::BulkString(Some("pmessage".into())),
+                    RespValue::BulkString(Some(pattern.clone())),
+                    RespValue::BulkString(Some(channel_name.clone())),
+                    RespValue::BulkString(Some(msg.clone())),
+                ];
+                size += deliver(tx, response) as usize;
+            }
+        }
+        Ok(size)
     }
 
+    /// Removes `client` from the channel, dropping the channel entirely once nobody is left.
     pub async fn unsubscribe(
         &self,
-        channel_name: String,
-        addr: SocketAddr
+        channel_name: Vec<u8>,
+        client: u64
     ) -> anyhow::Result<()> {
-        let channel_name = format!("channel-{channel_name}");
-        let mut keyspace = self.keyspace.write().await;
-        if let Some(entry) = keyspace.get_mut(&channel_name) {
-            match &mut entry.value {
-                RedisValue::Channel(channel) => {
-                    channel.clients.remove(&addr);
-                    Ok(())
-                }
-                _ => Ok(()),
-            }
-        } else {
-            Ok(())
+        let mut channels = self.channels.write().await;
+        remove_subscriber(&mut channels, &channel_name, client);
+        Ok(())
+    }
+
+    pub async fn punsubscribe(&self, pattern: Vec<u8>, client: u64) {
+        let mut patterns = self.patterns.write().await;
+        remove_subscriber(&mut patterns, &pattern, client);
+    }
+
+    pub async fn sunsubscribe(&self, channel_name: Vec<u8>, client: u64) {
+        let mut shard_channels = self.shard_channels.write().await;
+        remove_subscriber(&mut shard_channels, &channel_name, client);
+    }
+
+    /// SPUBLISH: delivers `msg` as an `smessage` to the shard channel's subscribers only.
+    pub async fn send_to_shard_channel(&self, channel_name: Vec<u8>, msg: Vec<u8>) -> usize {
+        let shard_channels = self.shard_channels.read().await;
+        let Some(channel) = shard_channels.get(&channel_name) else {
+            return 0;
+        };
+        let mut size = 0;
+        for tx in channel.clients.values() {
+            let response = vec![
+                RespValue::BulkString(Some("smessage".into())),
+                RespValue::BulkString(Some(channel_name.clone())),
+                RespValue::BulkString(Some(msg.clone())),
+            ];
+            size += deliver(tx, response) as usize;
+        }
+        size
+    }
+
+    /// Channels with at least one subscriber, optionally filtered by a glob pattern.
+    pub async fn active_channels(&self, pattern: Option<&str>) -> Vec<Vec<u8>> {
+        let channels = self.channels.read().await;
+        channels
+            .iter()
+            .filter(|(_, channel)| !channel.clients.is_empty())
+            .map(|(name, _)| name)
+            .filter(|name| pattern.is_none_or(|p| glob::glob_match(p.as_bytes(), name)))
+            .cloned()
+            .collect()
+    }
+}
+
+fn remove_subscriber(subscriptions: &mut Subscriptions, name: &[u8], client: u64) {
+    let Some(channel) = subscriptions.get_mut(name) else {
+        return;
+    };
+    channel.clients.remove(&client);
+    if channel.clients.is_empty() {
+        subscriptions.remove(name);
+    }
+}
+
+/// Queues a message for one subscriber. A subscriber whose queue is full is too far
+/// behind to keep up and misses the message, as does one that has gone away.
+fn deliver(tx: &Sender<RespValue>, message: Vec<RespValue>) -> bool {
+    match tx.try_send(RespValue::Array(message)) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            log::warning!("Dropping a pub/sub message for a subscriber that is not reading");
+            false
         }
+        Err(TrySendError::Closed(_)) => false,
     }
 }
@@ -0,0 +1,196 @@
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::command::{ExpireCondition, SortOptions};
+use crate::error_helpers::invalid_data_err;
+use crate::shared_store::keyspace_events::GENERIC;
+use crate::shared_store::shared_store::{wrong_type, RedisValue, Store};
+
+impl Store {
+    /// Copies `source` (value and TTL) to `destination`. Returns false when the source is
+    /// missing or the destination already exists and `replace` is off.
+    pub async fn copy(&self, source: &str, destination: String, replace: bool) -> io::Result<bool> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get(source).filter(|entry| !entry.is_expired()) else {
+            return Ok(false);
+        };
+        let destination_taken = keyspace
+            .get(&destination)
+            .is_some_and(|entry| !entry.is_expired());
+        if destination_taken && !replace {
+            return Ok(false);
+        }
+
+        let mut copy = entry.clone();
+        // Blocked readers wait on a per-key notifier, so the copy must not share the source's.
+        let notify = self.notifier_for(&destination).await;
+        match &mut copy.value {
+            RedisValue::List(list) => list.notify = notify.clone(),
+            RedisValue::Stream(stream) => stream.notify = notify.clone(),
+            _ => {}
+        }
+        keyspace.insert(destination.clone(), copy);
+        notify.notify_waiters();
+        drop(keyspace);
+        self.notify_keyspace_event(GENERIC, "copy_to", &destination).await;
+        Ok(true)
+    }
+
+    /// Moves `key`'s value and TTL to `newkey`, replacing whatever was there. Returns false
+    /// when `key` is missing.
+    pub async fn rename(&self, key: &str, newkey: String) -> bool {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(mut entry) = keyspace.remove(key).filter(|entry| !entry.is_expired()) else {
+            return false;
+        };
+        let notify = self.notifier_for(&newkey).await;
+        match &mut entry.value {
+            RedisValue::List(list) => list.notify = notify.clone(),
+            RedisValue::Stream(stream) => stream.notify = notify.clone(),
+            _ => {}
+        }
+        keyspace.insert(newkey.clone(), entry);
+        notify.notify_waiters();
+        self.notifier_for(key).await.notify_waiters();
+        drop(keyspace);
+        self.notify_keyspace_event(GENERIC, "rename_from", key).await;
+        self.notify_keyspace_event(GENERIC, "rename_to", &newkey).await;
+        true
+    }
+
+    /// Sorts the elements of a list, set or sorted set, numerically unless ALPHA is given.
+    pub async fn sort(&self, key: &str, options: &SortOptions) -> io::Result<Vec<Vec<u8>>> {
+        let elements: Vec<Vec<u8>> = {
+            let keyspace = self.keyspace.read().await;
+            match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+                None => return Ok(vec![]),
+                Some(entry) => match &entry.value {
+                    RedisValue::List(list) => list.entries.clone(),
+                    RedisValue::Set(set) => set.iter().cloned().collect(),
+                    RedisValue::ZRank(zrank) => zrank
+                        .reverse_map
+                        .keys()
+                        .map(|member| member.as_bytes().to_vec())
+                        .collect(),
+                    _ => return Err(wrong_type()),
+                },
+            }
+        };
+
+        let mut sorted = if options.alpha {
+            let mut elements = elements;
+            elements.sort();
+            elements
+        } else {
+            let mut scored = elements
+                .into_iter()
+                .map(|element| {
+                    let score = std::str::from_utf8(&element)
+                        .ok()
+                        .and_then(|s| s.trim().parse::<f64>().ok())
+                        .filter(|score| !score.is_nan())
+                        .ok_or_else(|| {
+                            invalid_data_err("ERR One or more scores can't be converted into double")
+                        })?;
+                    Ok((score, element))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            // Ties fall back to byte order so the output is deterministic.
+            scored.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+            scored.into_iter().map(|(_, element)| element).collect()
+        };
+        if options.desc {
+            sorted.reverse();
+        }
+
+        if let Some((offset, count)) = options.limit {
+            let start = (offset.max(0) as usize).min(sorted.len());
+            let end = if count < 0 {
+                sorted.len()
+            } else {
+                start.saturating_add(count as usize).min(sorted.len())
+            };
+            sorted = sorted.drain(start..end).collect();
+        }
+        Ok(sorted)
+    }
+
+    /// Sets an absolute expiry in Unix milliseconds, honouring NX/XX/GT/LT. A deadline
+    /// already in the past deletes the key. Returns whether the expiry was applied.
+    pub async fn expire_at(
+        &self,
+        key: &str,
+        unix_ms: i64,
+        condition: ExpireCondition,
+    ) -> io::Result<bool> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(false);
+        };
+        let deadline = instant_from_unix_ms(unix_ms);
+        if !condition.allows(entry.expires_at(), deadline) {
+            return Ok(false);
+        }
+        let event = match deadline {
+            Some(deadline) => {
+                entry.set_expires_at(Some(deadline));
+                "expire"
+            }
+            None => {
+                keyspace.remove(key);
+                "del"
+            }
+        };
+        drop(keyspace);
+        self.notify_keyspace_event(GENERIC, event, key).await;
+        Ok(true)
+    }
+
+    /// Makes `key` permanent. Returns whether it existed and had a TTL to remove.
+    pub async fn persist(&self, key: &str) -> bool {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return false;
+        };
+        if entry.expires_at().is_none() {
+            return false;
+        }
+        entry.set_expires_at(None);
+        drop(keyspace);
+        self.notify_keyspace_event(GENERIC, "persist", key).await;
+        true
+    }
+
+    /// Remaining time to live in milliseconds: -2 for a missing key, -1 for one that
+    /// never expires.
+    pub async fn pttl(&self, key: &str) -> i64 {
+        let keyspace = self.keyspace.read().await;
+        match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            None => -2,
+            Some(entry) => entry.expires_at().map_or(-1, |deadline| {
+                deadline.saturating_duration_since(Instant::now()).as_millis() as i64
+            }),
+        }
+    }
+
+    async fn notifier_for(&self, key: &str) -> Arc<Notify> {
+        self.get_notifiers(&[key.to_string()]).await.remove(0)
+    }
+}
+
+/// Maps a Unix timestamp onto the monotonic clock, or `None` if it has already passed.
+pub fn instant_from_unix_ms(unix_ms: i64) -> Option<Instant> {
+    let remaining = unix_ms.checked_sub(unix_now_ms()).filter(|ms| *ms > 0)?;
+    Some(Instant::now() + Duration::from_millis(remaining as u64))
+}
+
+pub fn unix_now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::Ordering;
+
+use crate::{log, shared_store::shared_store::Store};
+
+/// Event classes selected by `notify-keyspace-events`, one bit per flag character.
+pub const KEYSPACE: u32 = 1 << 0; // K
+pub const KEYEVENT: u32 = 1 << 1; // E
+pub const GENERIC: u32 = 1 << 2; // g
+pub const STRING: u32 = 1 << 3; // $
+pub const LIST: u32 = 1 << 4; // l
+pub const SET: u32 = 1 << 5; // s
+pub const HASH: u32 = 1 << 6; // h
+pub const ZSET: u32 = 1 << 7; // z
+pub const EXPIRED: u32 = 1 << 8; // x
+pub const EVICTED: u32 = 1 << 9; // e
+pub const STREAM: u32 = 1 << 10; // t
+pub const KEY_MISS: u32 = 1 << 11; // m
+pub const MODULE: u32 = 1 << 12; // d
+pub const NEW: u32 = 1 << 13; // n
+/// What `A` stands for: every class except key misses and new keys.
+const ALL: u32 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM | MODULE;
+
+/// Every flag character and its class, in the order CONFIG GET spells them.
+const FLAGS: [(char, u32); 14] = [
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+    ('t', STREAM),
+    ('d', MODULE),
+    ('K', KEYSPACE),
+    ('E', KEYEVENT),
+    ('m', KEY_MISS),
+    ('n', NEW),
+];
+
+/// Parses a `notify-keyspace-events` value such as `KEA` or `Elg`, or `None` when it
+/// contains a character that is not a class.
+pub fn parse_flags(value: &str) -> Option<u32> {
+    value.chars().try_fold(0, |flags, c| {
+        let class = match c {
+            'A' => ALL,
+            c => FLAGS.iter().find(|(flag, _)| *flag == c)?.1,
+        };
+        Some(flags | class)
+    })
+}
+
+/// The canonical spelling CONFIG GET reports, with the classes folded into `A` when
+/// they are all set.
+pub fn flags_to_string(flags: u32) -> String {
+    let all = flags & ALL == ALL;
+    let mut value = if all { "A".to_string() } else { String::new() };
+    for (flag, class) in FLAGS {
+        if flags & class != 0 && !(all && class & ALL != 0) {
+            value.push(flag);
+        }
+    }
+    value
+}
+
+impl Store {
+    pub fn set_keyspace_events(&self, flags: u32) {
+        self.keyspace_events.store(flags, Ordering::Relaxed);
+    }
+
+    pub fn keyspace_events(&self) -> u32 {
+        self.keyspace_events.load(Ordering::Relaxed)
+    }
+
+    /// Publishes `event` on `key` to `__keyspace@0__:<key>` and `__keyevent@0__:<event>`,
+    /// as far as `notify-keyspace-events` asks for it.
+    pub(crate) async fn notify_keyspace_event(&self, class: u32, event: &str, key: &str) {
+        let flags = self.keyspace_events();
+        if flags & class == 0 {
+            return;
+        }
+        if flags & KEYSPACE != 0 {
+            let channel = format!("__keyspace@0__:{key}").into_bytes();
+            if let Err(e) = self.send_to_channel(channel, event.as_bytes().to_vec()).await {
+                log::warning!("Keyspace notification failed: {e}");
+            }
+        }
+        if flags & KEYEVENT != 0 {
+            let channel = format!("__keyevent@0__:{event}").into_bytes();
+            if let Err(e) = self.send_to_channel(channel, key.as_bytes().to_vec()).await {
+                log::warning!("Keyspace notification failed: {e}");
+            }
+        }
+    }
+}
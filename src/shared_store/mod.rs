@@ -1,6 +1,14 @@
+#[allow(clippy::module_inception)]
 pub mod shared_store;
 pub mod redis_stream;
+pub mod stream_group;
 pub mod stream_id;
+pub mod redis_hash;
 pub mod redis_list;
+pub mod redis_set;
 pub mod channel;
-pub mod zrank;
\ No newline at end of file
+pub mod zrank;
+pub mod keyspace;
+pub mod object;
+pub mod redis_string;
+pub mod keyspace_events;
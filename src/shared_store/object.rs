@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+use std::io;
+use std::sync::atomic::Ordering;
+
+use crate::shared_store::redis_list::List;
+use crate::shared_store::redis_stream::StreamEntry;
+use crate::shared_store::zrank::Zrank;
+use crate::shared_store::shared_store::{Entry, RedisValue, Store};
+
+/// Byte budgets behind the negative `list-max-listpack-size` settings, -1 through -5.
+const LIST_LISTPACK_BYTES: [usize; 5] = [4096, 8192, 16384, 32768, 65536];
+
+/// Strings up to this many bytes are allocated with their object header (`embstr`);
+/// longer ones get a separate buffer (`raw`).
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// Redis's defaults for `set-max-intset-entries`, `set-max-listpack-entries` and
+/// `set-max-listpack-value`.
+const SET_MAX_INTSET_ENTRIES: usize = 512;
+const SET_MAX_LISTPACK_ENTRIES: usize = 128;
+const SET_MAX_LISTPACK_VALUE: usize = 64;
+
+/// Redis's defaults for `zset-max-listpack-entries` and `zset-max-listpack-value`.
+pub const ZSET_MAX_LISTPACK_ENTRIES: usize = 128;
+const ZSET_MAX_LISTPACK_VALUE: usize = 64;
+
+/// Rough allocation costs MEMORY USAGE adds on top of the bytes themselves: the object
+/// header every value carries, the keyspace slot holding a key, and the node, pointer
+/// and length header each element of a collection needs.
+const OBJECT_OVERHEAD: usize = 16;
+const KEY_OVERHEAD: usize = 24;
+const ELEMENT_OVERHEAD: usize = 16;
+
+impl Store {
+    /// Elements at least this large force a list out of its packed encoding.
+    pub fn set_list_packed_threshold(&self, bytes: usize) {
+        self.list_packed_threshold.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn set_zset_max_listpack_entries(&self, entries: usize) {
+        self.zset_max_listpack_entries.store(entries, Ordering::Relaxed);
+    }
+
+    pub fn zset_max_listpack_entries(&self) -> usize {
+        self.zset_max_listpack_entries.load(Ordering::Relaxed)
+    }
+
+    /// The encoding Redis would report for `key`. Values are always held in the same
+    /// structures here; the name is derived from their contents.
+    pub async fn object_encoding(
+        &self,
+        key: &str,
+        list_max_listpack_size: i64,
+    ) -> io::Result<Option<&'static str>> {
+        let keyspace = self.keyspace.read().await;
+        let Some(entry) = keyspace.get(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(None);
+        };
+        Ok(self.encoding_of(entry, list_max_listpack_size))
+    }
+
+    /// DEBUG OBJECT: the encoding and RDB size of `key`, or `None` when it is missing.
+    pub async fn debug_object(&self, key: &str, list_max_listpack_size: i64) -> Option<DebugObject> {
+        let keyspace = self.keyspace.read().await;
+        let entry = keyspace.get(key).filter(|entry| !entry.is_expired())?;
+        let encoding = self.encoding_of(entry, list_max_listpack_size)?;
+        let quicklist = match &entry.value {
+            RedisValue::List(list) if encoding == "quicklist" => {
+                Some(self.quicklist_stats(list, list_max_listpack_size))
+            }
+            _ => None,
+        };
+        let zset_length = match &entry.value {
+            RedisValue::ZRank(zrank) => Some(zrank.reverse_map.len()),
+            _ => None,
+        };
+        Some(DebugObject {
+            encoding,
+            serialized_len: serialized_len(&entry.value),
+            quicklist,
+            zset_length,
+        })
+    }
+
+    /// MEMORY USAGE: an estimate of the bytes `key` and its value occupy, or `None` when
+    /// it is missing. Collections are sized from their first `samples` elements and
+    /// scaled up to the full length; 0 sizes every element.
+    pub async fn memory_usage(&self, key: &str, samples: usize) -> Option<usize> {
+        let keyspace = self.keyspace.read().await;
+        let entry = keyspace.get(key).filter(|entry| !entry.is_expired())?;
+        Some(KEY_OVERHEAD + key.len() + OBJECT_OVERHEAD + value_usage(&entry.value, samples))
+    }
+
+    fn encoding_of(&self, entry: &Entry, list_max_listpack_size: i64) -> Option<&'static str> {
+        let encoding = match &entry.value {
+            RedisValue::Text(_) if entry.raw => "raw",
+            RedisValue::Text(value) if is_integer_encodable(value) => "int",
+            RedisValue::Text(value) if value.len() <= EMBSTR_SIZE_LIMIT => "embstr",
+            RedisValue::Text(_) => "raw",
+            RedisValue::List(list) => self.list_encoding(list, list_max_listpack_size),
+            RedisValue::Set(set) => set_encoding(set),
+            RedisValue::Hash(_) => "hashtable",
+            RedisValue::ZRank(zrank) => self.zset_encoding(zrank),
+            RedisValue::Stream(_) => "stream",
+            RedisValue::Queue(_) => return None,
+        };
+        Some(encoding)
+    }
+
+    fn list_encoding(&self, list: &List, list_max_listpack_size: i64) -> &'static str {
+        let packed_threshold = self.list_packed_threshold.load(Ordering::Relaxed);
+        if list.entries.iter().any(|entry| entry.len() >= packed_threshold) {
+            return "quicklist";
+        }
+        let bytes: usize = list.entries.iter().map(|entry| entry.len()).sum();
+        if listpack_fits(list.entries.len(), bytes, list_max_listpack_size) {
+            "listpack"
+        } else {
+            "quicklist"
+        }
+    }
+
+    fn zset_encoding(&self, zrank: &Zrank) -> &'static str {
+        if zrank.reverse_map.len() <= self.zset_max_listpack_entries()
+            && zrank.reverse_map.keys().all(|member| member.len() <= ZSET_MAX_LISTPACK_VALUE)
+        {
+            "listpack"
+        } else {
+            "skiplist"
+        }
+    }
+
+    /// Lays the list out the way a quicklist would: entries fill a node until the
+    /// listpack limit is reached, and each entry over the packed threshold gets a plain
+    /// node of its own.
+    fn quicklist_stats(&self, list: &List, list_max_listpack_size: i64) -> QuicklistStats {
+        let packed_threshold = self.list_packed_threshold.load(Ordering::Relaxed);
+        let mut nodes = 0;
+        let (mut node_entries, mut node_bytes) = (0, 0);
+        for entry in &list.entries {
+            if entry.len() >= packed_threshold {
+                nodes += (node_entries > 0) as usize + 1;
+                (node_entries, node_bytes) = (0, 0);
+                continue;
+            }
+            if node_entries > 0
+                && !listpack_fits(node_entries + 1, node_bytes + entry.len(), list_max_listpack_size)
+            {
+                nodes += 1;
+                (node_entries, node_bytes) = (0, 0);
+            }
+            node_entries += 1;
+            node_bytes += entry.len();
+        }
+        nodes += (node_entries > 0) as usize;
+        QuicklistStats {
+            nodes,
+            avg_node: list.entries.len() as f64 / nodes.max(1) as f64,
+            listpack_max: list_max_listpack_size,
+            uncompressed_size: list.entries.iter().map(|entry| entry.len()).sum(),
+        }
+    }
+}
+
+/// What DEBUG OBJECT reports for a key.
+#[derive(Debug)]
+pub struct DebugObject {
+    pub encoding: &'static str,
+    pub serialized_len: usize,
+    /// Node statistics, for lists that would be held in a quicklist.
+    pub quicklist: Option<QuicklistStats>,
+    /// Member count, for sorted sets.
+    pub zset_length: Option<usize>,
+}
+
+/// The `ql_*` fields of DEBUG OBJECT. Lists are a flat `Vec` here, so these describe
+/// the nodes Redis would have split the list into rather than anything allocated.
+#[derive(Debug)]
+pub struct QuicklistStats {
+    pub nodes: usize,
+    pub avg_node: f64,
+    pub listpack_max: i64,
+    pub uncompressed_size: usize,
+}
+
+/// Whether `entries` elements totalling `bytes` fit one listpack under
+/// `list-max-listpack-size`: an entry count when positive, a byte budget when negative.
+fn listpack_fits(entries: usize, bytes: usize, list_max_listpack_size: i64) -> bool {
+    if list_max_listpack_size > 0 {
+        entries <= list_max_listpack_size as usize
+    } else {
+        let index = (list_max_listpack_size.unsigned_abs() as usize).clamp(1, 5) - 1;
+        bytes <= LIST_LISTPACK_BYTES[index]
+    }
+}
+
+fn value_usage(value: &RedisValue, samples: usize) -> usize {
+    match value {
+        RedisValue::Text(value) if is_integer_encodable(value) => 0,
+        RedisValue::Text(value) => value.len(),
+        RedisValue::List(list) => sampled_usage(list.entries.iter(), samples, |entry| entry.len()),
+        RedisValue::Queue(queue) => sampled_usage(queue.iter(), samples, |entry| entry.len()),
+        RedisValue::Set(set) => sampled_usage(set.iter(), samples, |member| member.len()),
+        RedisValue::Hash(hash) => sampled_usage(hash.fields.iter(), samples, |(field, value)| {
+            field.len() + value.len() + ELEMENT_OVERHEAD
+        }),
+        // A member lives in both the score index and the member map, next to its score.
+        RedisValue::ZRank(zset) => sampled_usage(zset.reverse_map.keys(), samples, |member| {
+            2 * member.len() + 8 + ELEMENT_OVERHEAD
+        }),
+        RedisValue::Stream(stream) => {
+            let entries = stream.get_range(None, None);
+            sampled_usage(entries.iter(), samples, |(_, StreamEntry::Data { fields, .. })| {
+                16 + fields
+                    .iter()
+                    .map(|(field, value)| field.len() + value.len() + ELEMENT_OVERHEAD)
+                    .sum::<usize>()
+            })
+        }
+    }
+}
+
+/// Sizes the first `samples` elements (all of them when 0) and extrapolates to the rest,
+/// adding the per-element overhead to each.
+fn sampled_usage<I: ExactSizeIterator>(
+    elements: I,
+    samples: usize,
+    size: impl Fn(I::Item) -> usize,
+) -> usize {
+    let len = elements.len();
+    let sampled = if samples == 0 { len } else { samples.min(len) };
+    if sampled == 0 {
+        return 0;
+    }
+    let bytes: usize = elements.take(sampled).map(|element| size(element) + ELEMENT_OVERHEAD).sum();
+    (bytes as f64 / sampled as f64 * len as f64).round() as usize
+}
+
+fn set_encoding(set: &HashSet<Vec<u8>>) -> &'static str {
+    if set.len() <= SET_MAX_INTSET_ENTRIES && set.iter().all(|member| is_integer_encodable(member)) {
+        "intset"
+    } else if set.len() <= SET_MAX_LISTPACK_ENTRIES
+        && set.iter().all(|member| member.len() <= SET_MAX_LISTPACK_VALUE)
+    {
+        "listpack"
+    } else {
+        "hashtable"
+    }
+}
+
+/// The bytes `value` would take in an RDB file, excluding its key and type byte. Every
+/// type is sized in its plain encoding (length-prefixed strings, 8-byte binary scores)
+/// rather than the packed listpack/intset blobs Redis may write, so small collections
+/// come out larger than real Redis reports.
+pub fn serialized_len(value: &RedisValue) -> usize {
+    match value {
+        RedisValue::Text(value) => string_len(value),
+        RedisValue::List(list) => length_len(list.entries.len()) + strings_len(&list.entries),
+        RedisValue::Queue(queue) => length_len(queue.len()) + queue.iter().map(|v| string_len(v)).sum::<usize>(),
+        RedisValue::Set(set) => length_len(set.len()) + set.iter().map(|m| string_len(m)).sum::<usize>(),
+        RedisValue::Hash(hash) => {
+            length_len(hash.fields.len())
+                + hash
+                    .fields
+                    .iter()
+                    .map(|(field, value)| string_len(field) + string_len(value))
+                    .sum::<usize>()
+        }
+        RedisValue::ZRank(zset) => {
+            length_len(zset.reverse_map.len())
+                + zset
+                    .reverse_map
+                    .keys()
+                    .map(|member| string_len(member.as_bytes()) + 8)
+                    .sum::<usize>()
+        }
+        RedisValue::Stream(stream) => {
+            let entries = stream.get_range(None, None);
+            // Each entry is a 16-byte ID followed by its field/value pairs.
+            length_len(entries.len())
+                + entries
+                    .iter()
+                    .map(|(_, StreamEntry::Data { fields, .. })| {
+                        16 + length_len(fields.len())
+                            + fields
+                                .iter()
+                                .map(|(field, value)| {
+                                    string_len(field.as_bytes()) + string_len(value.as_bytes())
+                                })
+                                .sum::<usize>()
+                    })
+                    .sum::<usize>()
+        }
+    }
+}
+
+fn strings_len(values: &[Vec<u8>]) -> usize {
+    values.iter().map(|value| string_len(value)).sum()
+}
+
+/// RDB length encoding: 6, 14, 32 or 64 bits plus the type prefix.
+fn length_len(len: usize) -> usize {
+    match len {
+        0..=0x3f => 1,
+        0x40..=0x3fff => 2,
+        _ if len <= u32::MAX as usize => 5,
+        _ => 9,
+    }
+}
+
+/// Integers that fit 8, 16 or 32 bits are stored as such; anything else is
+/// length-prefixed raw bytes.
+fn string_len(value: &[u8]) -> usize {
+    let number = is_integer_encodable(value)
+        .then(|| std::str::from_utf8(value).ok()?.parse::<i64>().ok())
+        .flatten();
+    match number {
+        Some(n) if i8::try_from(n).is_ok() => 2,
+        Some(n) if i16::try_from(n).is_ok() => 3,
+        Some(n) if i32::try_from(n).is_ok() => 5,
+        _ => length_len(value.len()) + value.len(),
+    }
+}
+
+/// Whether Redis would keep `value` as a shared integer: the canonical decimal form of an
+/// `i64`, so no sign prefix, leading zeros or whitespace.
+fn is_integer_encodable(value: &[u8]) -> bool {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|text| text.parse::<i64>().ok())
+        .is_some_and(|number| number.to_string().as_bytes() == value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| i.to_string().into_bytes()).collect()
+    }
+
+    #[tokio::test]
+    async fn lists_within_the_listpack_size_are_listpack() {
+        let store = Store::new();
+        store.rpush("list".into(), items(128)).await.unwrap();
+        assert_eq!(store.object_encoding("list", 128).await.unwrap(), Some("listpack"));
+        let debug = store.debug_object("list", 128).await.unwrap();
+        assert_eq!(debug.encoding, "listpack");
+        assert!(debug.quicklist.is_none());
+    }
+
+    #[tokio::test]
+    async fn lists_past_the_listpack_size_become_quicklist() {
+        let store = Store::new();
+        store.rpush("list".into(), items(129)).await.unwrap();
+        assert_eq!(store.object_encoding("list", 128).await.unwrap(), Some("quicklist"));
+        let quicklist = store.debug_object("list", 128).await.unwrap().quicklist.unwrap();
+        assert_eq!(quicklist.nodes, 2);
+        assert_eq!(quicklist.listpack_max, 128);
+    }
+
+    #[tokio::test]
+    async fn lists_past_the_byte_budget_become_quicklist() {
+        let store = Store::new();
+        store.rpush("small".into(), vec![vec![b'x'; 4000]]).await.unwrap();
+        store.rpush("large".into(), vec![vec![b'x'; 4000]; 3]).await.unwrap();
+        assert_eq!(store.object_encoding("small", -2).await.unwrap(), Some("listpack"));
+        assert_eq!(store.object_encoding("large", -2).await.unwrap(), Some("quicklist"));
+    }
+
+    #[tokio::test]
+    async fn one_element_over_the_packed_threshold_makes_a_quicklist() {
+        let store = Store::new();
+        store.set_list_packed_threshold(10);
+        store.rpush("list".into(), vec![b"short".to_vec()]).await.unwrap();
+        assert_eq!(store.object_encoding("list", 128).await.unwrap(), Some("listpack"));
+        store.rpush("list".into(), vec![b"ten bytes!".to_vec()]).await.unwrap();
+        assert_eq!(store.object_encoding("list", 128).await.unwrap(), Some("quicklist"));
+        let quicklist = store.debug_object("list", 128).await.unwrap().quicklist.unwrap();
+        assert_eq!(quicklist.nodes, 2);
+    }
+
+    async fn zset(store: &Store, key: &str, members: usize) {
+        let members = (0..members).map(|i| (i as f64, format!("m{i}"))).collect();
+        store
+            .zadd_members(key.into(), &Default::default(), members)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sorted_sets_within_the_entry_limit_are_listpack() {
+        let store = Store::new();
+        zset(&store, "zset", ZSET_MAX_LISTPACK_ENTRIES).await;
+        let debug = store.debug_object("zset", 128).await.unwrap();
+        assert_eq!(debug.encoding, "listpack");
+        assert_eq!(debug.zset_length, Some(ZSET_MAX_LISTPACK_ENTRIES));
+    }
+
+    #[tokio::test]
+    async fn sorted_sets_past_the_entry_limit_become_skiplist() {
+        let store = Store::new();
+        store.set_zset_max_listpack_entries(4);
+        zset(&store, "small", 4).await;
+        zset(&store, "large", 5).await;
+        assert_eq!(store.object_encoding("small", 128).await.unwrap(), Some("listpack"));
+        let debug = store.debug_object("large", 128).await.unwrap();
+        assert_eq!(debug.encoding, "skiplist");
+        assert_eq!(debug.zset_length, Some(5));
+    }
+
+    #[tokio::test]
+    async fn a_long_member_makes_a_skiplist() {
+        let store = Store::new();
+        let member = "m".repeat(ZSET_MAX_LISTPACK_VALUE + 1);
+        store.zadd("zset".into(), 1.0, member).await.unwrap();
+        assert_eq!(store.object_encoding("zset", 128).await.unwrap(), Some("skiplist"));
+    }
+}
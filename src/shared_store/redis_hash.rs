@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::io;
+
+use tokio::time::Instant;
+
+use crate::command::{ExpireCondition, FieldExpiry};
+use crate::random;
+use crate::shared_store::keyspace::instant_from_unix_ms;
+use crate::shared_store::keyspace_events::{GENERIC, HASH};
+use crate::shared_store::shared_store::{wrong_type, Entry, RedisValue, Store};
+
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    pub(crate) fields: HashMap<Vec<u8>, Vec<u8>>,
+    /// Deadlines set by HEXPIRE and friends. Expired fields linger until the next write
+    /// or active-expire pass drops them, so reads go through `live_fields`.
+    pub(crate) field_expiry: HashMap<Vec<u8>, Instant>,
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn is_field_expired(&self, field: &[u8]) -> bool {
+        matches!(self.field_expiry.get(field), Some(expiry) if Instant::now() >= *expiry)
+    }
+
+    pub fn live_fields(&self) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+        self.fields
+            .iter()
+            .filter(|(field, _)| !self.is_field_expired(field))
+    }
+
+    /// Drops every field whose TTL has passed.
+    pub fn drop_expired(&mut self) {
+        if self.field_expiry.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let fields = &mut self.fields;
+        self.field_expiry.retain(|field, expiry| {
+            let live = now < *expiry;
+            if !live {
+                fields.remove(field);
+            }
+            live
+        });
+    }
+
+    /// Sets a field's value, clearing any TTL it had. Returns whether the field is new.
+    pub fn insert(&mut self, field: Vec<u8>, value: Vec<u8>) -> bool {
+        self.field_expiry.remove(&field);
+        self.fields.insert(field, value).is_none()
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<Vec<u8>> {
+        self.field_expiry.remove(field);
+        self.fields.remove(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live_fields().next().is_none()
+    }
+}
+
+impl Store {
+    pub async fn hset(&self, key: String, fields: Vec<(Vec<u8>, Vec<u8>)>) -> io::Result<usize> {
+        let mut keyspace = self.keyspace.write().await;
+        if keyspace.get(&key).is_some_and(|entry| entry.is_expired()) {
+            keyspace.remove(&key);
+        }
+        let entry = keyspace
+            .entry(key.clone())
+            .or_insert_with(|| Entry::new(RedisValue::Hash(Hash::new()), None));
+        let added = match &mut entry.value {
+            RedisValue::Hash(hash) => {
+                hash.drop_expired();
+                fields
+                    .into_iter()
+                    .filter(|(field, value)| hash.insert(field.clone(), value.clone()))
+                    .count()
+            }
+            _ => return Err(wrong_type()),
+        };
+        drop(keyspace);
+        self.notify_keyspace_event(HASH, "hset", &key).await;
+        Ok(added)
+    }
+
+    /// Sets `field` only when the hash doesn't already have it. Returns whether it was set.
+    pub async fn hsetnx(&self, key: String, field: Vec<u8>, value: Vec<u8>) -> io::Result<bool> {
+        let mut keyspace = self.keyspace.write().await;
+        if keyspace.get(&key).is_some_and(|entry| entry.is_expired()) {
+            keyspace.remove(&key);
+        }
+        let entry = keyspace
+            .entry(key.clone())
+            .or_insert_with(|| Entry::new(RedisValue::Hash(Hash::new()), None));
+        let set = match &mut entry.value {
+            RedisValue::Hash(hash) => {
+                hash.drop_expired();
+                match hash.fields.entry(field) {
+                    std::collections::hash_map::Entry::Occupied(_) => false,
+                    std::collections::hash_map::Entry::Vacant(slot) => {
+                        slot.insert(value);
+                        true
+                    }
+                }
+            }
+            _ => return Err(wrong_type()),
+        };
+        drop(keyspace);
+        if set {
+            self.notify_keyspace_event(HASH, "hset", &key).await;
+        }
+        Ok(set)
+    }
+
+    /// Returns each requested field's value and removes it, deleting the key if the hash
+    /// ends up empty.
+    pub async fn hgetdel(&self, key: &str, fields: &[Vec<u8>]) -> io::Result<Vec<Option<Vec<u8>>>> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(vec![None; fields.len()]);
+        };
+        let RedisValue::Hash(hash) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        hash.drop_expired();
+        let values: Vec<_> = fields.iter().map(|field| hash.remove(field)).collect();
+        let emptied = hash.is_empty();
+        if emptied {
+            keyspace.remove(key);
+        }
+        drop(keyspace);
+        if values.iter().any(Option::is_some) {
+            self.notify_keyspace_event(HASH, "hdel", key).await;
+        }
+        if emptied {
+            self.notify_keyspace_event(GENERIC, "del", key).await;
+        }
+        Ok(values)
+    }
+
+    /// Returns each requested field's value, then applies `expiry` to the fields that
+    /// exist. A deadline already in the past deletes them.
+    pub async fn hgetex(
+        &self,
+        key: &str,
+        fields: &[Vec<u8>],
+        expiry: Option<FieldExpiry>,
+    ) -> io::Result<Vec<Option<Vec<u8>>>> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(vec![None; fields.len()]);
+        };
+        let RedisValue::Hash(hash) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        hash.drop_expired();
+        let values: Vec<_> = fields
+            .iter()
+            .map(|field| hash.fields.get(field).cloned())
+            .collect();
+        let (mut expired, mut persisted, mut deleted) = (false, false, false);
+        for (field, _) in fields.iter().zip(&values).filter(|(_, value)| value.is_some()) {
+            match expiry {
+                None => {}
+                Some(FieldExpiry::Persist) => {
+                    persisted |= hash.field_expiry.remove(field).is_some();
+                }
+                Some(FieldExpiry::At(unix_ms)) => match instant_from_unix_ms(unix_ms) {
+                    Some(deadline) => {
+                        hash.field_expiry.insert(field.clone(), deadline);
+                        expired = true;
+                    }
+                    None => {
+                        hash.remove(field);
+                        deleted = true;
+                    }
+                },
+            }
+        }
+        let emptied = hash.is_empty();
+        if emptied {
+            keyspace.remove(key);
+        }
+        drop(keyspace);
+        if expired {
+            self.notify_keyspace_event(HASH, "hexpire", key).await;
+        }
+        if persisted {
+            self.notify_keyspace_event(HASH, "hpersist", key).await;
+        }
+        if deleted {
+            self.notify_keyspace_event(HASH, "hdel", key).await;
+        }
+        if emptied {
+            self.notify_keyspace_event(GENERIC, "del", key).await;
+        }
+        Ok(values)
+    }
+
+    /// Sets an absolute expiry on each field, honouring NX/XX/GT/LT. Per field the reply
+    /// is -2 (no such field), 0 (condition not met), 1 (set) or 2 (deadline already
+    /// passed, field deleted).
+    pub async fn hexpire(
+        &self,
+        key: &str,
+        unix_ms: i64,
+        condition: ExpireCondition,
+        fields: &[Vec<u8>],
+    ) -> io::Result<Vec<i64>> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(vec![-2; fields.len()]);
+        };
+        let RedisValue::Hash(hash) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        hash.drop_expired();
+        let deadline = instant_from_unix_ms(unix_ms);
+        let mut codes = Vec::with_capacity(fields.len());
+        for field in fields {
+            if !hash.fields.contains_key(field) {
+                codes.push(-2);
+            } else if !condition.allows(hash.field_expiry.get(field).copied(), deadline) {
+                codes.push(0);
+            } else if let Some(deadline) = deadline {
+                hash.field_expiry.insert(field.clone(), deadline);
+                codes.push(1);
+            } else {
+                hash.remove(field);
+                codes.push(2);
+            }
+        }
+        let emptied = hash.is_empty();
+        if emptied {
+            keyspace.remove(key);
+        }
+        drop(keyspace);
+        if codes.contains(&1) {
+            self.notify_keyspace_event(HASH, "hexpire", key).await;
+        }
+        if codes.contains(&2) {
+            self.notify_keyspace_event(HASH, "hdel", key).await;
+        }
+        if emptied {
+            self.notify_keyspace_event(GENERIC, "del", key).await;
+        }
+        Ok(codes)
+    }
+
+    /// Remaining time to live per field in `unit_ms` units, rounded up: -2 for a missing
+    /// field, -1 for one without a TTL.
+    pub async fn httl(&self, key: &str, fields: &[Vec<u8>], unit_ms: u128) -> io::Result<Vec<i64>> {
+        let keyspace = self.keyspace.read().await;
+        let Some(entry) = keyspace.get(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(vec![-2; fields.len()]);
+        };
+        let RedisValue::Hash(hash) = &entry.value else {
+            return Err(wrong_type());
+        };
+        let now = Instant::now();
+        Ok(fields
+            .iter()
+            .map(|field| {
+                if !hash.fields.contains_key(field) || hash.is_field_expired(field) {
+                    return -2;
+                }
+                match hash.field_expiry.get(field) {
+                    None => -1,
+                    Some(expiry) => {
+                        let remaining = expiry.saturating_duration_since(now).as_millis();
+                        remaining.div_ceil(unit_ms) as i64
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// Every live field and its value, in no particular order.
+    pub async fn hgetall(&self, key: &str) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let keyspace = self.keyspace.read().await;
+        match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            Some(entry) => match &entry.value {
+                RedisValue::Hash(hash) => Ok(hash
+                    .live_fields()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect()),
+                _ => Err(wrong_type()),
+            },
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Picks random fields: a positive `count` returns distinct fields, a negative one
+    /// samples with replacement and always returns exactly `|count|` entries.
+    pub async fn hrandfield(&self, key: &str, count: i64) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let keyspace = self.keyspace.read().await;
+        let hash = match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            Some(entry) => match &entry.value {
+                RedisValue::Hash(hash) => hash,
+                _ => return Err(wrong_type()),
+            },
+            None => return Ok(vec![]),
+        };
+        let fields: Vec<_> = hash.live_fields().collect();
+        if fields.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let picked = if count >= 0 {
+            random::distinct_indexes(fields.len(), count as usize)
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| random::random_index(fields.len()))
+                .collect()
+        };
+        Ok(picked
+            .into_iter()
+            .map(|i| (fields[i].0.clone(), fields[i].1.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::resp::RespValue;
+    use crate::shared_store::keyspace::unix_now_ms;
+    use crate::shared_store::keyspace_events::{parse_flags, KEYEVENT};
+
+    async fn watch(store: &Store) -> Receiver<RespValue> {
+        store.set_keyspace_events(KEYEVENT | parse_flags("A").unwrap());
+        let (tx, rx) = mpsc::channel(16);
+        store.psubscribe(b"__keyevent@0__:*".to_vec(), 1, tx).await;
+        rx
+    }
+
+    /// The events published so far, as `event key`.
+    fn events(rx: &mut Receiver<RespValue>) -> Vec<String> {
+        let mut events = vec![];
+        while let Ok(RespValue::Array(message)) = rx.try_recv() {
+            let [_, _, RespValue::BulkString(Some(channel)), RespValue::BulkString(Some(key))] =
+                &message[..]
+            else {
+                panic!("unexpected message: {message:?}");
+            };
+            let channel = String::from_utf8_lossy(channel);
+            let event = channel.trim_start_matches("__keyevent@0__:");
+            events.push(format!("{event} {}", String::from_utf8_lossy(key)));
+        }
+        events
+    }
+
+    async fn hash(store: &Store, fields: &[&str]) {
+        let fields = fields
+            .iter()
+            .map(|field| (field.as_bytes().to_vec(), b"v".to_vec()))
+            .collect();
+        store.hset("h".into(), fields).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn hgetdel_notifies_hdel_then_del_when_emptied() {
+        let store = Store::new();
+        hash(&store, &["a", "b"]).await;
+        let mut rx = watch(&store).await;
+        store.hgetdel("h", &[b"a".to_vec()]).await.unwrap();
+        assert_eq!(events(&mut rx), ["hdel h"]);
+        store.hgetdel("h", &[b"missing".to_vec()]).await.unwrap();
+        assert!(events(&mut rx).is_empty());
+        store.hgetdel("h", &[b"b".to_vec()]).await.unwrap();
+        assert_eq!(events(&mut rx), ["hdel h", "del h"]);
+    }
+
+    #[tokio::test]
+    async fn hgetex_notifies_hexpire_hpersist_and_hdel() {
+        let store = Store::new();
+        hash(&store, &["a"]).await;
+        let mut rx = watch(&store).await;
+        let field = [b"a".to_vec()];
+        store.hgetex("h", &field, None).await.unwrap();
+        assert!(events(&mut rx).is_empty());
+        let later = FieldExpiry::At(unix_now_ms() + 60_000);
+        store.hgetex("h", &field, Some(later)).await.unwrap();
+        assert_eq!(events(&mut rx), ["hexpire h"]);
+        store.hgetex("h", &field, Some(FieldExpiry::Persist)).await.unwrap();
+        assert_eq!(events(&mut rx), ["hpersist h"]);
+        store.hgetex("h", &field, Some(FieldExpiry::Persist)).await.unwrap();
+        assert!(events(&mut rx).is_empty());
+        let past = FieldExpiry::At(unix_now_ms() - 1);
+        store.hgetex("h", &field, Some(past)).await.unwrap();
+        assert_eq!(events(&mut rx), ["hdel h", "del h"]);
+    }
+
+    #[tokio::test]
+    async fn hexpire_notifies_hexpire_or_hdel() {
+        let store = Store::new();
+        hash(&store, &["a", "b"]).await;
+        let mut rx = watch(&store).await;
+        let condition = ExpireCondition::default();
+        let later = unix_now_ms() + 60_000;
+        store.hexpire("h", later, condition, &[b"a".to_vec()]).await.unwrap();
+        assert_eq!(events(&mut rx), ["hexpire h"]);
+        let past = unix_now_ms() - 1;
+        store.hexpire("h", past, condition, &[b"a".to_vec(), b"b".to_vec()]).await.unwrap();
+        assert_eq!(events(&mut rx), ["hdel h", "del h"]);
+    }
+
+    #[tokio::test]
+    async fn flush_all_notifies_flushall() {
+        let store = Store::new();
+        hash(&store, &["a"]).await;
+        let mut rx = watch(&store).await;
+        store.flush_all().await;
+        assert_eq!(events(&mut rx), ["flushall "]);
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::sync::Arc;
 
 use tokio::sync::Notify;
 
+use crate::shared_store::keyspace_events::{GENERIC, LIST};
+use crate::shared_store::shared_store::{wrong_type, RedisValue, Store};
+
 #[derive(Debug, Clone)]
 pub struct List {
     pub notify: Arc<Notify>,
@@ -20,7 +24,7 @@ impl List {
         if self.entries.is_empty() {
             return Ok(None);
         }
-        let values = self.entries.drain(..amount).collect();
+        let values = self.entries.drain(..amount.min(self.entries.len())).collect();
         self.notify.notify_waiters();
         Ok(Some(values))
     }
@@ -39,3 +43,73 @@ impl List {
         }
     }
 }
+
+/// The clients blocked in BLPOP, as tickets queued per key in the order they blocked.
+#[derive(Debug, Default)]
+pub struct ListWaiters {
+    next_ticket: u64,
+    queues: HashMap<String, VecDeque<u64>>,
+}
+
+impl Store {
+    /// Queues a blocking pop on every key in `keys`, returning its ticket.
+    pub fn join_list_queue(&self, keys: &[String]) -> u64 {
+        let mut waiters = self.list_waiters.lock().unwrap();
+        let ticket = waiters.next_ticket;
+        waiters.next_ticket += 1;
+        for key in keys {
+            waiters.queues.entry(key.clone()).or_default().push_back(ticket);
+        }
+        ticket
+    }
+
+    pub fn leave_list_queue(&self, keys: &[String], ticket: u64) {
+        let mut waiters = self.list_waiters.lock().unwrap();
+        for key in keys {
+            if let Some(queue) = waiters.queues.get_mut(key) {
+                queue.retain(|queued| *queued != ticket);
+                if queue.is_empty() {
+                    waiters.queues.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Pops the head of `key` for the waiter holding `ticket`, but only when it is first in
+    /// line. A push thus goes to the client that has been blocked longest, whichever of
+    /// them wakes first; each pop wakes the rest again so the next in line can take the
+    /// following element.
+    pub async fn lpop_in_turn(&self, key: &str, ticket: u64) -> io::Result<Option<Vec<u8>>> {
+        let mut map = self.keyspace.write().await;
+        let Some(entry) = map.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(None);
+        };
+        let RedisValue::List(list) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        let first_in_line = {
+            let waiters = self.list_waiters.lock().unwrap();
+            waiters
+                .queues
+                .get(key)
+                .and_then(|queue| queue.front())
+                .is_none_or(|first| *first == ticket)
+        };
+        if !first_in_line {
+            return Ok(None);
+        }
+        let popped = list.lpop(1)?.and_then(|mut values| values.pop());
+        let emptied = list.entries.is_empty();
+        if emptied {
+            map.remove(key);
+        }
+        drop(map);
+        if popped.is_some() {
+            self.notify_keyspace_event(LIST, "lpop", key).await;
+        }
+        if emptied {
+            self.notify_keyspace_event(GENERIC, "del", key).await;
+        }
+        Ok(popped)
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use std::io;
+
+use crate::random;
+use crate::shared_store::keyspace_events::SET;
+use crate::shared_store::shared_store::{wrong_type, Entry, RedisValue, Store};
+
+impl Store {
+    pub async fn sadd(&self, key: String, members: Vec<Vec<u8>>) -> io::Result<usize> {
+        let mut keyspace = self.keyspace.write().await;
+        let added = if let Some(entry) = keyspace.get_mut(&key).filter(|entry| !entry.is_expired()) {
+            match &mut entry.value {
+                RedisValue::Set(set) => members.into_iter().filter(|m| set.insert(m.clone())).count(),
+                _ => return Err(wrong_type()),
+            }
+        } else {
+            let set: HashSet<Vec<u8>> = members.into_iter().collect();
+            let added = set.len();
+            keyspace.insert(key.clone(), Entry::new(RedisValue::Set(set), None));
+            added
+        };
+        drop(keyspace);
+        if added > 0 {
+            self.notify_keyspace_event(SET, "sadd", &key).await;
+        }
+        Ok(added)
+    }
+
+    pub async fn smismember(&self, key: &str, members: &[Vec<u8>]) -> io::Result<Vec<bool>> {
+        let keyspace = self.keyspace.read().await;
+        match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            Some(entry) => match &entry.value {
+                RedisValue::Set(set) => Ok(members.iter().map(|m| set.contains(m)).collect()),
+                _ => Err(wrong_type()),
+            },
+            None => Ok(vec![false; members.len()]),
+        }
+    }
+
+    /// Counts the members shared by every set, stopping early once `limit` is reached.
+    /// A `limit` of zero means no limit.
+    pub async fn sintercard(&self, keys: &[String], limit: usize) -> io::Result<usize> {
+        let keyspace = self.keyspace.read().await;
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+                Some(entry) => match &entry.value {
+                    RedisValue::Set(set) => sets.push(set),
+                    _ => return Err(wrong_type()),
+                },
+                None => return Ok(0),
+            }
+        }
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return Ok(0);
+        };
+
+        let mut count = 0;
+        for member in smallest.iter() {
+            if rest.iter().all(|set| set.contains(member)) {
+                count += 1;
+                if count == limit {
+                    break;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Picks random members. A positive `count` returns at most `count` distinct members,
+    /// while a negative one samples with replacement and returns exactly `|count|`.
+    pub async fn srandmember(&self, key: &str, count: i64) -> io::Result<Vec<Vec<u8>>> {
+        let keyspace = self.keyspace.read().await;
+        let set = match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            Some(entry) => match &entry.value {
+                RedisValue::Set(set) => set,
+                _ => return Err(wrong_type()),
+            },
+            None => return Ok(vec![]),
+        };
+        let members: Vec<&Vec<u8>> = set.iter().collect();
+        if members.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let picked = if count >= 0 {
+            random::distinct_indexes(members.len(), count as usize)
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| random::random_index(members.len()))
+                .collect()
+        };
+        Ok(picked.into_iter().map(|i| members[i].clone()).collect())
+    }
+}
@@ -6,7 +6,8 @@ use std::sync::Arc;
 use tokio::sync::Notify;
 
 use crate::error_helpers::invalid_data_err;
-use crate::shared_store::stream_id::StreamID;
+use crate::shared_store::stream_group::ConsumerGroup;
+use crate::shared_store::stream_id::{not_after_top, StreamID};
 
 type Fields = Vec<(String, String)>;
 pub type StreamEntries = Vec<(StreamID, StreamEntry)>;
@@ -16,16 +17,11 @@ pub enum StreamEntry {
     Data { id: StreamID, fields: Fields },
 }
 
-#[derive(Debug, Clone)]
-pub enum _ControlType {
-    GroupCreate { group_name: String },
-    Ack { consumer: String, ids: Vec<String> },
-}
-
 #[derive(Debug, Clone)]
 pub struct Stream {
     pub notify: Arc<Notify>,
     entries: BTreeMap<StreamID, StreamEntry>, // ID as key
+    pub groups: BTreeMap<String, ConsumerGroup>,
 }
 
 impl Stream {
@@ -51,8 +47,13 @@ impl Stream {
         Self {
             notify,
             entries: BTreeMap::new(),
+            groups: BTreeMap::new(),
         }
     }
+
+    pub fn get(&self, id: &StreamID) -> Option<&StreamEntry> {
+        self.entries.get(id)
+    }
     pub fn get_from(&self, start: StreamID) -> StreamEntries {
         self.entries
             .range::<StreamID, _>((Excluded(start), Unbounded))
@@ -85,9 +86,7 @@ impl Stream {
             ));
         }
         if id.ms < previous.ms || id.ms == previous.ms && id.seq <= previous.seq {
-            return Err(invalid_data_err(
-                "ERR The ID specified in XADD is equal or smaller than the target stream top item",
-            ));
+            return Err(not_after_top());
         }
         Ok(true)
     }
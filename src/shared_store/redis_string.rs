@@ -0,0 +1,318 @@
+use std::io;
+
+use crate::command::FieldExpiry;
+use crate::shared_store::keyspace::instant_from_unix_ms;
+use crate::error_helpers::invalid_data_err;
+use crate::shared_store::keyspace_events::{GENERIC, STRING};
+use crate::shared_store::shared_store::{wrong_type, Entry, RedisValue, Store};
+
+/// Redis's default `proto-max-bulk-len`, the longest string SETRANGE may produce.
+const PROTO_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// One run of consecutive matching bytes, as inclusive `(start, end)` offsets in each input.
+#[derive(Debug, Clone)]
+pub struct LcsMatch {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+}
+
+impl LcsMatch {
+    pub fn len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lcs {
+    pub sequence: Vec<u8>,
+    /// Matching runs ordered from the end of the strings backwards, as Redis reports them.
+    pub matches: Vec<LcsMatch>,
+}
+
+impl Store {
+    /// Removes a string key and returns its value, whatever TTL it had.
+    pub async fn getdel(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut keyspace = self.keyspace.write().await;
+        match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            None => Ok(None),
+            Some(entry) if !matches!(entry.value, RedisValue::Text(_)) => Err(wrong_type()),
+            Some(_) => match keyspace.remove(key).map(|entry| entry.value) {
+                Some(RedisValue::Text(value)) => {
+                    drop(keyspace);
+                    self.notify_keyspace_event(GENERIC, "del", key).await;
+                    Ok(Some(value))
+                }
+                _ => Ok(None),
+            },
+        }
+    }
+
+    /// Returns a string key's value and applies `expiry` to it. `None` must leave the TTL
+    /// exactly as it was; a deadline already in the past deletes the key.
+    pub async fn getex(&self, key: &str, expiry: Option<FieldExpiry>) -> io::Result<Option<Vec<u8>>> {
+        let mut keyspace = self.keyspace.write().await;
+        let Some(entry) = keyspace.get_mut(key).filter(|entry| !entry.is_expired()) else {
+            return Ok(None);
+        };
+        let RedisValue::Text(value) = &entry.value else {
+            return Err(wrong_type());
+        };
+        let value = value.clone();
+        let event = match expiry {
+            None => None,
+            Some(FieldExpiry::Persist) => {
+                let had_ttl = entry.expires_at().is_some();
+                entry.set_expires_at(None);
+                had_ttl.then_some("persist")
+            }
+            Some(FieldExpiry::At(unix_ms)) => match instant_from_unix_ms(unix_ms) {
+                Some(deadline) => {
+                    entry.set_expires_at(Some(deadline));
+                    Some("expire")
+                }
+                None => {
+                    keyspace.remove(key);
+                    Some("del")
+                }
+            },
+        };
+        drop(keyspace);
+        if let Some(event) = event {
+            self.notify_keyspace_event(GENERIC, event, key).await;
+        }
+        Ok(Some(value))
+    }
+
+    /// Appends `value` to a string key, creating it when missing, and returns the new
+    /// length. Only a string that already existed is switched to the `raw` encoding.
+    pub async fn append(&self, key: &str, value: &[u8]) -> io::Result<usize> {
+        let mut keyspace = self.keyspace.write().await;
+        let len = match keyspace.get_mut(key).filter(|entry| !entry.is_expired()) {
+            Some(entry) => {
+                let RedisValue::Text(string) = &mut entry.value else {
+                    return Err(wrong_type());
+                };
+                string.extend_from_slice(value);
+                entry.raw = true;
+                string.len()
+            }
+            None => {
+                let entry = Entry::new(RedisValue::Text(value.to_vec()), None);
+                keyspace.insert(key.to_string(), entry);
+                value.len()
+            }
+        };
+        drop(keyspace);
+        self.notify_keyspace_event(STRING, "append", key).await;
+        Ok(len)
+    }
+
+    /// Overwrites a string key from `offset` on, zero-padding it first when it is
+    /// shorter, and returns the new length. A missing key is created unless `value` is
+    /// empty.
+    pub async fn setrange(&self, key: &str, offset: usize, value: &[u8]) -> io::Result<usize> {
+        let mut keyspace = self.keyspace.write().await;
+        if keyspace.get(key).is_some_and(|entry| entry.is_expired()) {
+            keyspace.remove(key);
+        }
+        if keyspace
+            .get(key)
+            .is_some_and(|entry| !matches!(entry.value, RedisValue::Text(_)))
+        {
+            return Err(wrong_type());
+        }
+        if offset.saturating_add(value.len()) > PROTO_MAX_BULK_LEN {
+            return Err(invalid_data_err(
+                "ERR string exceeds maximum allowed size (proto-max-bulk-len)",
+            ));
+        }
+        let Some(entry) = keyspace.get_mut(key) else {
+            if value.is_empty() {
+                return Ok(0);
+            }
+            let mut string = vec![0; offset];
+            string.extend_from_slice(value);
+            let len = string.len();
+            let mut entry = Entry::new(RedisValue::Text(string), None);
+            entry.raw = true;
+            keyspace.insert(key.to_string(), entry);
+            drop(keyspace);
+            self.notify_keyspace_event(STRING, "setrange", key).await;
+            return Ok(len);
+        };
+        let RedisValue::Text(string) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        if value.is_empty() {
+            return Ok(string.len());
+        }
+        if string.len() < offset + value.len() {
+            string.resize(offset + value.len(), 0);
+        }
+        string[offset..offset + value.len()].copy_from_slice(value);
+        let len = string.len();
+        entry.raw = true;
+        drop(keyspace);
+        self.notify_keyspace_event(STRING, "setrange", key).await;
+        Ok(len)
+    }
+
+    /// Longest common subsequence of two string keys; missing keys count as empty strings.
+    pub async fn lcs(&self, key1: &str, key2: &str) -> io::Result<Lcs> {
+        let keyspace = self.keyspace.read().await;
+        let text = |key: &str| match keyspace.get(key).filter(|entry| !entry.is_expired()) {
+            None => Ok(vec![]),
+            Some(entry) => match &entry.value {
+                RedisValue::Text(value) => Ok(value.clone()),
+                _ => Err(wrong_type()),
+            },
+        };
+        let (a, b) = (text(key1)?, text(key2)?);
+        // The table is quadratic, so it is built without holding up other clients.
+        drop(keyspace);
+        longest_common_subsequence(&a, &b)
+    }
+}
+
+/// The classic O(n*m) table, then a walk back from the bottom-right corner that collects
+/// the subsequence and groups diagonal steps into match ranges. Like Redis, refuses
+/// inputs whose table would outgrow `proto-max-bulk-len`.
+fn longest_common_subsequence(a: &[u8], b: &[u8]) -> io::Result<Lcs> {
+    let width = b.len() + 1;
+    let cells = (a.len() + 1)
+        .checked_mul(width)
+        .filter(|cells| *cells <= PROTO_MAX_BULK_LEN / size_of::<u32>())
+        .ok_or_else(|| {
+            invalid_data_err(
+                "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len",
+            )
+        })?;
+    let mut table = vec![0u32; cells];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(table[a.len() * width + b.len()] as usize);
+    let mut matches = vec![];
+    let mut current: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            sequence.push(a[i - 1]);
+            match &mut current {
+                Some(run) if run.a.0 == i && run.b.0 == j => {
+                    run.a.0 -= 1;
+                    run.b.0 -= 1;
+                }
+                _ => {
+                    matches.extend(current.take());
+                    current = Some(LcsMatch {
+                        a: (i - 1, i - 1),
+                        b: (j - 1, j - 1),
+                    });
+                }
+            }
+            i -= 1;
+            j -= 1;
+        } else {
+            matches.extend(current.take());
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+        }
+    }
+    matches.extend(current);
+    sequence.reverse();
+    Ok(Lcs { sequence, matches })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(lcs: &Lcs) -> Vec<((usize, usize), (usize, usize))> {
+        lcs.matches.iter().map(|run| (run.a, run.b)).collect()
+    }
+
+    #[test]
+    fn finds_the_subsequence_and_its_runs() {
+        // The example from the LCS command docs.
+        let lcs = longest_common_subsequence(b"ohmytext", b"mynewtext").unwrap();
+        assert_eq!(lcs.sequence, b"mytext");
+        assert_eq!(ranges(&lcs), vec![((4, 7), (5, 8)), ((2, 3), (0, 1))]);
+        assert_eq!(lcs.matches.iter().map(LcsMatch::len).collect::<Vec<_>>(), vec![4, 2]);
+    }
+
+    #[test]
+    fn empty_or_disjoint_inputs_share_nothing() {
+        for (a, b) in [(&b""[..], &b"abc"[..]), (b"abc", b""), (b"abc", b"xyz")] {
+            let lcs = longest_common_subsequence(a, b).unwrap();
+            assert!(lcs.sequence.is_empty());
+            assert!(lcs.matches.is_empty());
+        }
+    }
+
+    #[test]
+    fn identical_inputs_are_one_run() {
+        let lcs = longest_common_subsequence(b"redis", b"redis").unwrap();
+        assert_eq!(lcs.sequence, b"redis");
+        assert_eq!(ranges(&lcs), vec![((0, 4), (0, 4))]);
+    }
+
+    #[test]
+    fn refuses_tables_larger_than_proto_max_bulk_len() {
+        let long = vec![b'a'; 1 << 16];
+        let err = longest_common_subsequence(&long, &long).unwrap_err();
+        assert!(err.to_string().contains("Insufficient memory"));
+    }
+
+    #[tokio::test]
+    async fn getex_without_options_keeps_the_ttl() {
+        let store = Store::new();
+        store.set("k", b"v".to_vec(), Some(100_000)).await;
+
+        assert_eq!(store.getex("k", None).await.unwrap(), Some(b"v".to_vec()));
+        assert!(store.pttl("k").await > 90_000);
+    }
+
+    #[tokio::test]
+    async fn getex_persist_removes_the_ttl() {
+        let store = Store::new();
+        store.set("k", b"v".to_vec(), Some(100_000)).await;
+
+        assert_eq!(store.getex("k", Some(FieldExpiry::Persist)).await.unwrap(), Some(b"v".to_vec()));
+        assert_eq!(store.pttl("k").await, -1);
+    }
+
+    #[tokio::test]
+    async fn getdel_returns_the_value_and_removes_the_key() {
+        let store = Store::new();
+        store.set("k", b"v".to_vec(), Some(100_000)).await;
+
+        assert_eq!(store.getdel("k").await.unwrap(), Some(b"v".to_vec()));
+        assert_eq!(store.pttl("k").await, -2);
+        assert_eq!(store.getdel("k").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn append_to_a_missing_key_creates_it() {
+        let store = Store::new();
+        assert_eq!(store.append("k", b"hello").await.unwrap(), 5);
+        assert_eq!(store.append("k", b" world").await.unwrap(), 11);
+    }
+
+    #[tokio::test]
+    async fn setrange_on_a_missing_key_pads_with_nul_bytes() {
+        let store = Store::new();
+        assert_eq!(store.setrange("k", 5, b"abc").await.unwrap(), 8);
+        assert_eq!(store.getdel("k").await.unwrap(), Some(b"\0\0\0\0\0abc".to_vec()));
+    }
+}
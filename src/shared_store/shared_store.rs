@@ -1,15 +1,25 @@
+use bytes::BytesMut;
 use futures::io;
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::{Mutex, Notify, RwLock};
 use tokio::time::Instant;
-
-use crate::error_helpers::{invalid_data, invalid_data_err};
-use crate::resp::RespValue;
-use crate::shared_store::channel::Channel;
-use crate::shared_store::redis_list::List;
-use crate::shared_store::redis_stream::{Stream, StreamEntries};
+use tokio_util::codec::Encoder;
+
+use crate::error_helpers::invalid_data_err;
+use crate::glob;
+use crate::log;
+use crate::replication_manager::manager::{PendingDels, ReplicationManager};
+use crate::resp::{RespCodec, RespValue};
+use crate::shared_store::channel::Subscriptions;
+use crate::shared_store::redis_hash::Hash;
+use crate::shared_store::keyspace::unix_now_ms;
+use crate::shared_store::keyspace_events::{EXPIRED, GENERIC, LIST, STREAM, STRING};
+use crate::shared_store::object::ZSET_MAX_LISTPACK_ENTRIES;
+use crate::shared_store::redis_list::{List, ListWaiters};
+use crate::shared_store::redis_stream::{Stream, StreamEntries, StreamEntry};
 use crate::shared_store::stream_id::StreamID;
 use crate::shared_store::zrank::Zrank;
 
@@ -18,7 +28,8 @@ pub enum RedisValue {
     Text(Vec<u8>),
     Stream(Stream),
     List(List),
-    Channel(Channel),
+    Set(HashSet<Vec<u8>>),
+    Hash(Hash),
     ZRank(Zrank),
     #[allow(dead_code)]
     Queue(VecDeque<Vec<u8>>), // Add ZSet, List, etc. as needed
@@ -28,21 +39,64 @@ pub enum RedisValue {
 pub struct Entry {
     pub(crate) value: RedisValue,
     expires_at: Option<Instant>,
+    /// Set once APPEND or SETRANGE has grown a string in place; Redis then reports it
+    /// as `raw` whatever its length or contents.
+    pub(crate) raw: bool,
 }
 
 impl Entry {
     pub fn new(value: RedisValue, expires_at: Option<Instant>) -> Self {
-        Self { value, expires_at }
+        Self {
+            value,
+            expires_at,
+            raw: false,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expiry) if Instant::now() >= expiry)
+    }
+
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: Option<Instant>) {
+        self.expires_at = expires_at;
     }
 }
+
+/// The error every command returns when a key holds a different type than it operates on.
+pub fn wrong_type() -> io::Error {
+    invalid_data_err("WRONGTYPE Operation against a key holding the wrong kind of value")
+}
+
 type SharedStore = Arc<RwLock<HashMap<String, Entry>>>;
 type Log = Arc<RwLock<Vec<u8>>>;
 pub type NotifierStore = Mutex<HashMap<String, Arc<Notify>>>;
 #[derive(Debug)]
 pub struct Store {
     pub(crate) keyspace: SharedStore,
-    notifiers: NotifierStore,
+    pub(crate) notifiers: NotifierStore,
+    pub(crate) channels: RwLock<Subscriptions>,
+    pub(crate) patterns: RwLock<Subscriptions>,
+    /// SSUBSCRIBE channels. Without a cluster there is one shard, but SPUBLISH only
+    /// reaches these, never plain channel or pattern subscribers.
+    pub(crate) shard_channels: RwLock<Subscriptions>,
     log: Log,
+    active_expire: AtomicBool,
+    /// On a replica, expiry belongs to the master: expired keys read as absent but stay
+    /// in place until the master's DEL removes them.
+    replica: AtomicBool,
+    /// Set on a master once it accepts replicas. Keys that expire here are sent to them
+    /// as DELs, since replicas leave expiry to the master.
+    replication: OnceLock<(Arc<Mutex<ReplicationManager>>, PendingDels)>,
+    pub(crate) list_packed_threshold: AtomicUsize,
+    /// `zset-max-listpack-entries`: sorted sets larger than this report `skiplist`.
+    pub(crate) zset_max_listpack_entries: AtomicUsize,
+    pub(crate) list_waiters: std::sync::Mutex<ListWaiters>,
+    /// The `notify-keyspace-events` classes, as set by CONFIG SET.
+    pub(crate) keyspace_events: AtomicU32,
 }
 
 impl Store {
@@ -50,8 +104,221 @@ impl Store {
         Self {
             keyspace: Arc::new(RwLock::new(HashMap::new())),
             notifiers: Mutex::new(HashMap::new()),
+            channels: RwLock::new(HashMap::new()),
+            patterns: RwLock::new(HashMap::new()),
+            shard_channels: RwLock::new(HashMap::new()),
             log: Arc::new(RwLock::new(vec![])),
+            active_expire: AtomicBool::new(true),
+            replica: AtomicBool::new(false),
+            replication: OnceLock::new(),
+            list_packed_threshold: AtomicUsize::new(1 << 30),
+            zset_max_listpack_entries: AtomicUsize::new(ZSET_MAX_LISTPACK_ENTRIES),
+            list_waiters: std::sync::Mutex::new(ListWaiters::default()),
+            keyspace_events: AtomicU32::new(0),
+        }
+    }
+
+    pub fn set_active_expire(&self, enabled: bool) {
+        self.active_expire.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn active_expire_enabled(&self) -> bool {
+        self.active_expire.load(Ordering::Relaxed)
+    }
+
+    pub fn set_replica(&self, replica: bool) {
+        self.replica.store(replica, Ordering::Relaxed);
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.replica.load(Ordering::Relaxed)
+    }
+
+    pub fn set_replication(&self, manager: Arc<Mutex<ReplicationManager>>, pending: PendingDels) {
+        let _ = self.replication.set((manager, pending));
+    }
+
+    /// Queues a DEL for keys that expired on this master. Callers still hold the keyspace
+    /// lock, so a write that recreates one of the keys is queued after its DEL. Nothing
+    /// here waits on replicas; a separate task sends the queue on.
+    fn propagate_expired(&self, keys: &[&String]) {
+        let Some((manager, pending)) = self.replication.get() else {
+            return;
+        };
+        if keys.is_empty() {
+            return;
+        }
+        let mut parts = vec![b"DEL".to_vec()];
+        parts.extend(keys.iter().map(|key| key.as_bytes().to_vec()));
+        let mut bytes = BytesMut::new();
+        // Encoding an array of bulk strings cannot fail.
+        let _ = RespCodec.encode(bulk_command(parts), &mut bytes);
+        pending.lock().unwrap().extend_from_slice(&bytes);
+        let manager = manager.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager.lock().await.flush_pending_dels().await {
+                log::warning!("Failed to propagate expired keys: {e}");
+            }
+        });
+    }
+
+    /// Removes every entry whose TTL has elapsed, returning how many were dropped.
+    pub async fn purge_expired(&self) -> usize {
+        let mut map = self.keyspace.write().await;
+        let before = map.len();
+        let mut expired = vec![];
+        map.retain(|key, entry| {
+            if let RedisValue::Hash(hash) = &mut entry.value {
+                hash.drop_expired();
+                if hash.fields.is_empty() {
+                    return false;
+                }
+            }
+            if entry.is_expired() {
+                expired.push(key.clone());
+                return false;
+            }
+            true
+        });
+        let purged = before - map.len();
+        self.propagate_expired(&expired.iter().collect::<Vec<_>>());
+        drop(map);
+        for key in expired {
+            self.notify_keyspace_event(EXPIRED, "expired", &key).await;
+        }
+        purged
+    }
+
+    /// Drops `key` once a read finds its TTL has elapsed. Whichever of this and
+    /// `purge_expired` actually removes the entry sends the `expired` event, so it goes
+    /// out once per key. A replica never drops it here; the read still sees nothing.
+    async fn expire_lazily(&self, key: &str) {
+        if self.is_replica() {
+            return;
+        }
+        let mut map = self.keyspace.write().await;
+        if !map.get(key).is_some_and(|entry| entry.is_expired()) {
+            return;
+        }
+        map.remove(key);
+        self.propagate_expired(&[&key.to_string()]);
+        drop(map);
+        self.notify_keyspace_event(EXPIRED, "expired", key).await;
+    }
+
+    /// Describes the live keyspace as the smallest set of write commands that rebuilds it,
+    /// one command per key. Used to compact the append-only file.
+    pub async fn rewrite_commands(&self) -> Vec<RespValue> {
+        let map = self.keyspace.read().await;
+        let now = Instant::now();
+        let mut commands = Vec::with_capacity(map.len());
+        for (key, entry) in map.iter().filter(|(_, entry)| !entry.is_expired()) {
+            let key = key.as_bytes().to_vec();
+            let parts = match &entry.value {
+                RedisValue::Text(value) => {
+                    let mut parts = vec![b"SET".to_vec(), key.clone(), value.clone()];
+                    if let Some(expiry) = entry.expires_at {
+                        parts.push(b"PXAT".to_vec());
+                        parts.push(unix_ms_at(expiry, now).to_string().into_bytes());
+                    }
+                    commands.push(bulk_command(parts));
+                    continue;
+                }
+                RedisValue::List(list) if !list.entries.is_empty() => {
+                    let mut parts = vec![b"RPUSH".to_vec(), key.clone()];
+                    parts.extend(list.entries.iter().cloned());
+                    Some(parts)
+                }
+                RedisValue::Set(set) if !set.is_empty() => {
+                    let mut parts = vec![b"SADD".to_vec(), key.clone()];
+                    parts.extend(set.iter().cloned());
+                    Some(parts)
+                }
+                RedisValue::Hash(hash) if !hash.is_empty() => {
+                    let mut parts = vec![b"HSET".to_vec(), key.clone()];
+                    let mut field_ttls = vec![];
+                    for (field, value) in hash.live_fields() {
+                        parts.push(field.clone());
+                        parts.push(value.clone());
+                        if let Some(expiry) = hash.field_expiry.get(field) {
+                            field_ttls.push(bulk_command(vec![
+                                b"HPEXPIREAT".to_vec(),
+                                key.clone(),
+                                unix_ms_at(*expiry, now).to_string().into_bytes(),
+                                b"FIELDS".to_vec(),
+                                b"1".to_vec(),
+                                field.clone(),
+                            ]));
+                        }
+                    }
+                    commands.push(bulk_command(parts));
+                    commands.extend(field_ttls);
+                    None
+                }
+                RedisValue::ZRank(zrank) if !zrank.reverse_map.is_empty() => {
+                    let mut parts = vec![b"ZADD".to_vec(), key.clone()];
+                    for (member, score) in &zrank.reverse_map {
+                        parts.push(score.to_string().into_bytes());
+                        parts.push(member.as_bytes().to_vec());
+                    }
+                    Some(parts)
+                }
+                RedisValue::Stream(stream) => {
+                    // Streams keep their explicit IDs, so each entry replays as its own XADD.
+                    for (id, StreamEntry::Data { fields, .. }) in stream.get_from(StreamID { ms: 0, seq: 0 }) {
+                        let mut parts = vec![b"XADD".to_vec(), key.clone(), id.to_string().into_bytes()];
+                        for (field, value) in fields {
+                            parts.push(field.into_bytes());
+                            parts.push(value.into_bytes());
+                        }
+                        commands.push(bulk_command(parts));
+                    }
+                    for (name, group) in &stream.groups {
+                        commands.push(bulk_command(vec![
+                            b"XGROUP".to_vec(),
+                            b"CREATE".to_vec(),
+                            key.clone(),
+                            name.clone().into_bytes(),
+                            group.last_delivered.to_string().into_bytes(),
+                            b"MKSTREAM".to_vec(),
+                        ]));
+                        // Pending entries come back via XCLAIM FORCE, keeping owner and counts.
+                        for (id, pending) in &group.pending {
+                            let delivered_ms = unix_now_ms()
+                                - pending.delivered_at.elapsed().as_millis() as i64;
+                            commands.push(bulk_command(vec![
+                                b"XCLAIM".to_vec(),
+                                key.clone(),
+                                name.clone().into_bytes(),
+                                pending.consumer.clone().into_bytes(),
+                                b"0".to_vec(),
+                                id.to_string().into_bytes(),
+                                b"TIME".to_vec(),
+                                delivered_ms.to_string().into_bytes(),
+                                b"RETRYCOUNT".to_vec(),
+                                pending.delivery_count.to_string().into_bytes(),
+                                b"FORCE".to_vec(),
+                                b"JUSTID".to_vec(),
+                            ]));
+                        }
+                    }
+                    None
+                }
+                _ => continue,
+            };
+            if let Some(parts) = parts {
+                commands.push(bulk_command(parts));
+            }
+            // Only SET carries its own TTL; everything else gets an absolute PEXPIREAT.
+            if let Some(expiry) = entry.expires_at {
+                commands.push(bulk_command(vec![
+                    b"PEXPIREAT".to_vec(),
+                    key,
+                    unix_ms_at(expiry, now).to_string().into_bytes(),
+                ]));
+            }
         }
+        commands
     }
 
     pub async fn get(&self, key: &str) -> io::Result<RespValue> {
@@ -72,8 +339,9 @@ impl Store {
     pub async fn get_type(&self, key: &str) -> io::Result<RespValue> {
         match self._get(key).await? {
             Some(redis_value) => match redis_value {
-                RedisValue::Channel(_) => Ok(RespValue::SimpleString("channel".into())),
                 RedisValue::List(_) => Ok(RespValue::SimpleString("list".into())),
+                RedisValue::Set(_) => Ok(RespValue::SimpleString("set".into())),
+                RedisValue::Hash(_) => Ok(RespValue::SimpleString("hash".into())),
                 RedisValue::Stream(_) => Ok(RespValue::SimpleString("stream".into())),
                 RedisValue::Text(_) => Ok(RespValue::SimpleString("string".into())),
                 RedisValue::Queue(_) => Ok(RespValue::SimpleString("queue".into())),
@@ -84,35 +352,31 @@ impl Store {
     }
 
     async fn _get(&self, key: &str) -> io::Result<Option<RedisValue>> {
-        let value = {
+        let entry = {
             let map = self.keyspace.read().await;
-            let entry = map.get(key).cloned();
-            if let Some(entry) = entry {
-                match entry.expires_at {
-                    Some(expiry) if Instant::now() >= expiry => None,
-                    _ => Some(entry.value),
-                }
-            } else {
-                None
-            }
+            map.get(key).cloned()
         };
-        Ok(value)
+        match entry {
+            Some(entry) if entry.is_expired() => {
+                self.expire_lazily(key).await;
+                Ok(None)
+            }
+            entry => Ok(entry.map(|entry| entry.value)),
+        }
     }
 
     async fn _get_mut(&self, key: &str) -> io::Result<Option<RedisValue>> {
-        let value = {
+        let entry = {
             let map = self.keyspace.read().await;
-            let entry = map.get(key).cloned();
-            if let Some(entry) = entry {
-                match entry.expires_at {
-                    Some(expiry) if Instant::now() >= expiry => None,
-                    _ => Some(entry.value),
-                }
-            } else {
-                None
-            }
+            map.get(key).cloned()
         };
-        Ok(value)
+        match entry {
+            Some(entry) if entry.is_expired() => {
+                self.expire_lazily(key).await;
+                Ok(None)
+            }
+            entry => Ok(entry.map(|entry| entry.value)),
+        }
     }
 
     pub async fn resolve_stream_ids(
@@ -134,7 +398,7 @@ impl Store {
 
                     match &entry.value {
                         RedisValue::Stream(stream) => ret.push(*stream.previous_id()),
-                        _ => return invalid_data("Key is not for a stream"),
+                        _ => return Err(wrong_type()),
                     }
                 }
                 _ => ret.push(id.as_str().try_into()?),
@@ -144,21 +408,14 @@ impl Store {
         Ok(ret)
     }
 
-    pub async fn keys(&self) -> RespValue {
-        let mut values = vec![];
+    /// KEYS: every live key matching the glob `pattern`.
+    pub async fn keys(&self, pattern: &[u8]) -> RespValue {
         let map = self.keyspace.read().await;
-        for key in map.keys() {
-            let entry = map.get(key).cloned();
-            if let Some(entry) = entry {
-                match entry.expires_at {
-                    Some(expiry) if Instant::now() >= expiry => (),
-                    _ => {
-                        values.push(RespValue::BulkString(Some(key.as_bytes().to_vec())));
-                    }
-                }
-            } 
-        }
-
+        let values = map
+            .iter()
+            .filter(|(key, entry)| !entry.is_expired() && glob::glob_match(pattern, key.as_bytes()))
+            .map(|(key, _)| RespValue::BulkString(Some(key.as_bytes().to_vec())))
+            .collect();
         RespValue::Array(values)
     }
 
@@ -182,17 +439,17 @@ impl Store {
                     number += 1;
                     let new_value = (number).to_string().into_bytes();
                     previous.value = RedisValue::Text(new_value);
+                    previous.raw = false;
+                    self.notify_keyspace_event(STRING, "incrby", key).await;
                     Ok(Some(RespValue::Integer(number)))
                 }
 
-                _ => error,
+                _ => Ok(Some(RespValue::Error(wrong_type().to_string()))),
             }
         } else {
-            let entry = Entry {
-                value: RedisValue::Text("1".as_bytes().into()),
-                expires_at: None,
-            };
+            let entry = Entry::new(RedisValue::Text("1".as_bytes().into()), None);
             map.insert(key.clone(), entry);
+            self.notify_keyspace_event(STRING, "incrby", key).await;
             Ok(Some(RespValue::Integer(1)))
         }
     }
@@ -201,39 +458,54 @@ impl Store {
         let expires_at = px.map(|ms| Instant::now() + Duration::from_millis(ms));
         let entry = Entry::new(RedisValue::Text(value), expires_at);
         map.insert(key.to_string(), entry);
+        drop(map);
+        self.notify_keyspace_event(STRING, "set", key).await;
     }
 
     pub async fn rpush(&self, key: String, values: Vec<Vec<u8>>) -> io::Result<usize> {
         let mut map = self.keyspace.write().await;
-        let len = values.len();
-        match map.get_mut(&key) {
+        let len = match map.get_mut(&key) {
             Some(entry) => match &mut entry.value {
-                RedisValue::List(list) => Ok(list.rpush(values)?),
-                _ => Err(invalid_data_err(
-                    "ERR RPUSH on key holding the wrong kind of value",
-                )),
+                RedisValue::List(list) => list.rpush(values)?,
+                _ => return Err(wrong_type()),
             },
             None => {
+                let len = values.len();
                 let mut guard = self.notifiers.lock().await;
                 let notify = guard.entry(key.clone()).or_insert(Arc::new(Notify::new()));
                 let list = List::new(notify.clone(), values);
                 let entry = Entry::new(RedisValue::List(list), None);
                 map.insert(key.clone(), entry);
                 notify.notify_waiters();
-
-                Ok(len)
+                len
             }
-        }
+        };
+        drop(map);
+        self.notify_keyspace_event(LIST, "rpush", &key).await;
+        Ok(len)
     }
 
     pub async fn lpop(&self, key: String, amount: usize) -> io::Result<Option<Vec<Vec<u8>>>> {
         let mut map = self.keyspace.write().await;
         match map.get_mut(&key) {
             Some(entry) => match &mut entry.value {
-                RedisValue::List(list) => Ok(list.lpop(amount)?),
-                _ => Err(invalid_data_err(
-                    "ERR LPOP on key holding the wrong kind of value",
-                )),
+                RedisValue::List(list) => {
+                    let popped = list.lpop(amount)?;
+                    // An emptied list is removed rather than left behind as a zero-length key.
+                    let emptied = list.entries.is_empty();
+                    if emptied {
+                        map.remove(&key);
+                    }
+                    drop(map);
+                    if popped.is_some() {
+                        self.notify_keyspace_event(LIST, "lpop", &key).await;
+                    }
+                    if emptied {
+                        self.notify_keyspace_event(GENERIC, "del", &key).await;
+                    }
+                    Ok(popped)
+                }
+                _ => Err(wrong_type()),
             },
             None => Ok(None),
         }
@@ -241,39 +513,36 @@ impl Store {
 
     pub async fn lpush(&self, key: String, values: Vec<Vec<u8>>) -> io::Result<usize> {
         let mut map = self.keyspace.write().await;
-        let len = values.len();
-        match map.get_mut(&key) {
+        let len = match map.get_mut(&key) {
             Some(entry) => match &mut entry.value {
-                RedisValue::List(list) => Ok(list.lpush(values)?),
-
-                _ => Err(invalid_data_err(
-                    "ERR LPUSH on key holding the wrong kind of value",
-                )),
+                RedisValue::List(list) => list.lpush(values)?,
+                _ => return Err(wrong_type()),
             },
             None => {
+                let len = values.len();
                 let mut guard = self.notifiers.lock().await;
                 let notify = guard.entry(key.clone()).or_insert(Arc::new(Notify::new()));
                 let list = List::new(notify.clone(), values);
                 let entry = Entry::new(RedisValue::List(list), None);
                 map.insert(key.clone(), entry);
                 notify.notify_waiters();
-
-                Ok(len)
+                len
             }
-        }
+        };
+        drop(map);
+        self.notify_keyspace_event(LIST, "lpush", &key).await;
+        Ok(len)
     }
 
     pub async fn llen(&self, key: String) -> io::Result<usize> {
         let map = self.keyspace.read().await;
-        match map.get(&key) {
+        match map.get(&key).filter(|entry| !entry.is_expired()) {
             Some(entry) => match &entry.value {
                 RedisValue::List(arr) => {
                     let len = arr.entries.len();
                     Ok(len)
                 }
-                _ => Err(invalid_data_err(
-                    "ERR LPUSH on key holding the wrong kind of value",
-                )),
+                _ => Err(wrong_type()),
             },
             None => Ok(0),
         }
@@ -286,9 +555,11 @@ impl Store {
         mut end: isize,
     ) -> io::Result<Vec<Vec<u8>>> {
         let map = self.keyspace.read().await;
-        match map.get(&key) {
+        match map.get(&key).filter(|entry| !entry.is_expired()) {
             Some(entry) => match &entry.value {
                 RedisValue::List(arr) => {
+                    // With an empty list `end` clamps to -1, which the `start > end` check
+                    // below turns into an empty reply.
                     let len = arr.entries.len() as isize;
                     if start < 0 {
                         start += len;
@@ -308,9 +579,7 @@ impl Store {
                     let u_end = (end + 1) as usize;
                     Ok(arr.entries[u_start..u_end].to_vec())
                 }
-                _ => Err(invalid_data_err(
-                    "ERR LPUSH on key holding the wrong kind of value",
-                )),
+                _ => Err(wrong_type()),
             },
             None => Ok(vec![]),
         }
@@ -350,9 +619,7 @@ impl Store {
                     let range = stream.get_range(start, end);
                     Ok(range)
                 }
-                _ => Err(invalid_data_err(
-                    "ERR XRANGE on key holding the wrong kind of value",
-                )),
+                _ => Err(wrong_type()),
             },
             None => Ok(vec![]), // Return empty on missing key
         }
@@ -366,9 +633,7 @@ impl Store {
                     let range = stream.get_from(*start);
                     Ok(range)
                 }
-                _ => Err(invalid_data_err(
-                    "ERR XREAD on key holding the wrong kind of value",
-                )),
+                _ => Err(wrong_type()),
             },
             None => Ok(vec![]), // Return empty on missing key
         }
@@ -382,19 +647,15 @@ impl Store {
     ) -> io::Result<String> {
         let mut map = self.keyspace.write().await;
 
-        if let Some(entry) = map.get_mut(key) {
+        let id = if let Some(entry) = map.get_mut(key) {
             match &mut entry.value {
                 RedisValue::Stream(stream) => {
                     let stream_id: StreamID =
                         StreamID::from_redis_input(Some(*stream.previous_id()), id)?;
                     stream.append(stream_id, fields)?;
-                    Ok(stream_id.to_string())
-                }
-                _ => {
-                    Err(invalid_data_err(
-                        "ERR calling None Stream Value with stream key",
-                    ))
+                    stream_id.to_string()
                 }
+                _ => return Err(wrong_type()),
             }
         } else {
             let stream_id: StreamID = StreamID::from_redis_input(None, id)?;
@@ -406,20 +667,74 @@ impl Store {
             stream.append(stream_id, fields)?;
             let entry = Entry::new(RedisValue::Stream(stream), None);
             map.insert(key.to_string(), entry);
-            Ok(stream_id.to_string())
+            stream_id.to_string()
+        };
+        drop(map);
+        self.notify_keyspace_event(STREAM, "xadd", key).await;
+        Ok(id)
+    }
+
+    /// Drops every key and publishes `flushall`. Blocked clients are woken so they
+    /// re-check their keys.
+    pub async fn flush_all(&self) {
+        let mut map = self.keyspace.write().await;
+        map.clear();
+        drop(map);
+        let notifiers = self.notifiers.lock().await;
+        for notify in notifiers.values() {
+            notify.notify_waiters();
         }
+        drop(notifiers);
+        self.notify_keyspace_event(GENERIC, "flushall", "").await;
+    }
+
+    /// How many of `keys` hold a live value. A key named twice counts twice.
+    pub async fn exists(&self, keys: &[String]) -> i64 {
+        let map = self.keyspace.read().await;
+        keys.iter()
+            .filter(|key| map.get(*key).is_some_and(|entry| !entry.is_expired()))
+            .count() as i64
     }
 
-    // pub async fn del(&self, key: &str) {
-    //     let mut map = self.keyspace.write().await;
-    //     map.remove(key);
-    // }
+    /// Removes `keys`, returning how many existed. Clients blocked on a removed key are
+    /// woken so they re-check it rather than holding on to the old value.
+    pub async fn del(&self, keys: &[String]) -> usize {
+        let mut map = self.keyspace.write().await;
+        let notifiers = self.notifiers.lock().await;
+        let mut removed = vec![];
+        let mut expired = vec![];
+        for key in keys {
+            match map.remove(key) {
+                Some(entry) if entry.is_expired() => expired.push(key),
+                Some(_) => {
+                    removed.push(key);
+                    if let Some(notify) = notifiers.get(key) {
+                        notify.notify_waiters();
+                    }
+                }
+                None => {}
+            }
+        }
+        self.propagate_expired(&expired);
+        drop((map, notifiers));
+        for key in &expired {
+            self.notify_keyspace_event(EXPIRED, "expired", key).await;
+        }
+        for key in &removed {
+            self.notify_keyspace_event(GENERIC, "del", key).await;
+        }
+        removed.len()
+    }
 
     pub async fn append_to_log(&self, bytes: Vec<u8>) {
         let mut log = self.log.write().await;
         log.extend(bytes);
     }
 
+    pub async fn clear_log(&self) {
+        self.log.write().await.clear();
+    }
+
     pub async fn get_offset(&self) -> usize {
         let log = self.log.read().await;
         log.len()
@@ -435,3 +750,57 @@ impl Store {
         ret
     }
 }
+
+/// Converts a monotonic deadline back to Unix milliseconds, never earlier than 1ms out.
+fn unix_ms_at(expiry: Instant, now: Instant) -> u128 {
+    let remaining = expiry.saturating_duration_since(now).as_millis();
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        + remaining.max(1)
+}
+
+fn bulk_command(parts: Vec<Vec<u8>>) -> RespValue {
+    RespValue::Array(
+        parts
+            .into_iter()
+            .map(|part| RespValue::BulkString(Some(part)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn keys(store: &Store, pattern: &str) -> Vec<Vec<u8>> {
+        let RespValue::Array(items) = store.keys(pattern.as_bytes()).await else {
+            panic!("KEYS should reply with an array");
+        };
+        let mut keys: Vec<_> = items
+            .into_iter()
+            .filter_map(|item| match item {
+                RespValue::BulkString(key) => key,
+                _ => None,
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn keys_filters_live_keys_by_glob() {
+        let store = Store::new();
+        for key in ["user:1", "user:2", "user:10", "session"] {
+            store.set(key, b"v".to_vec(), None).await;
+        }
+        store.set("user:3", b"v".to_vec(), Some(0)).await;
+
+        assert_eq!(keys(&store, "*").await.len(), 4);
+        assert_eq!(keys(&store, "user:?").await, vec![b"user:1".to_vec(), b"user:2".to_vec()]);
+        assert_eq!(keys(&store, "user:[^1]").await, vec![b"user:2".to_vec()]);
+        assert_eq!(keys(&store, "s*n").await, vec![b"session".to_vec()]);
+        assert!(keys(&store, "user:3").await.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::command::ClaimOptions;
+use crate::error_helpers::invalid_data_err;
+use crate::shared_store::redis_stream::{Stream, StreamEntries};
+use crate::shared_store::keyspace::unix_now_ms;
+use crate::shared_store::shared_store::{wrong_type, Entry, RedisValue, Store};
+use crate::shared_store::stream_id::StreamID;
+
+/// An entry handed to a consumer that has not been acknowledged yet.
+#[derive(Debug, Clone)]
+pub struct PendingEntry {
+    pub consumer: String,
+    pub delivered_at: Instant,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsumerGroup {
+    pub last_delivered: StreamID,
+    /// The pending entries list (PEL), ordered by entry ID.
+    pub pending: BTreeMap<StreamID, PendingEntry>,
+}
+
+impl ConsumerGroup {
+    fn new(last_delivered: StreamID) -> Self {
+        Self {
+            last_delivered,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Hands out entries after the last delivered one and records them as pending for
+    /// `consumer`, unless `noack` says the caller never intends to XACK them.
+    fn read_new(
+        &mut self,
+        stream: &Stream,
+        consumer: &str,
+        count: usize,
+        noack: bool,
+    ) -> StreamEntries {
+        let entries: StreamEntries = stream
+            .get_from(self.last_delivered)
+            .into_iter()
+            .take(count)
+            .collect();
+        if let Some((id, _)) = entries.last() {
+            self.last_delivered = *id;
+        }
+        if !noack {
+            let now = Instant::now();
+            for (id, _) in &entries {
+                self.pending.insert(
+                    *id,
+                    PendingEntry {
+                        consumer: consumer.to_string(),
+                        delivered_at: now,
+                        delivery_count: 1,
+                    },
+                );
+            }
+        }
+        entries
+    }
+
+    /// Re-reads `consumer`'s own pending entries after `start` without changing them.
+    fn read_history(
+        &self,
+        stream: &Stream,
+        consumer: &str,
+        start: StreamID,
+        count: usize,
+    ) -> StreamEntries {
+        self.pending
+            .range((Excluded(start), Unbounded))
+            .filter(|(_, pending)| pending.consumer == consumer)
+            .filter_map(|(id, _)| stream.get(id).map(|entry| (*id, entry.clone())))
+            .take(count)
+            .collect()
+    }
+    /// Reassigns the pending `ids` idle for at least `min_idle_ms` to `consumer`.
+    fn claim(
+        &mut self,
+        stream: &Stream,
+        consumer: &str,
+        min_idle_ms: u64,
+        ids: &[StreamID],
+        options: &ClaimOptions,
+    ) -> StreamEntries {
+        let now = Instant::now();
+        let delivered_at = match (options.idle_ms, options.time_ms) {
+            (Some(idle_ms), _) => instant_before(now, idle_ms),
+            (None, Some(time_ms)) => {
+                instant_before(now, (unix_now_ms() - time_ms).max(0) as u64)
+            }
+            (None, None) => now,
+        };
+        if let Some(last_id) = options.last_id {
+            self.last_delivered = self.last_delivered.max(last_id);
+        }
+
+        let mut claimed = vec![];
+        for id in ids {
+            let Some(stream_entry) = stream.get(id) else {
+                // The entry is gone from the stream, so nobody can ever process it.
+                self.pending.remove(id);
+                continue;
+            };
+            // A forced entry was never delivered, so it has no idle time to check.
+            let pending = match self.pending.get_mut(id) {
+                Some(pending) if pending.delivered_at.elapsed().as_millis() < min_idle_ms as u128 => {
+                    continue
+                }
+                Some(pending) => pending,
+                None if options.force => self.pending.entry(*id).or_insert(PendingEntry {
+                    consumer: consumer.to_string(),
+                    delivered_at: now,
+                    delivery_count: 0,
+                }),
+                None => continue,
+            };
+            pending.consumer = consumer.to_string();
+            pending.delivered_at = delivered_at;
+            // JUSTID claims are bookkeeping rather than a delivery.
+            if let Some(retry_count) = options.retry_count {
+                pending.delivery_count = retry_count;
+            } else if !options.justid {
+                pending.delivery_count += 1;
+            }
+            claimed.push((*id, stream_entry.clone()));
+        }
+        claimed
+    }
+}
+
+/// The instant that was `idle_ms` ago, clamped to the earliest representable one.
+fn instant_before(now: Instant, idle_ms: u64) -> Instant {
+    now.checked_sub(Duration::from_millis(idle_ms)).unwrap_or(now)
+}
+
+fn no_group(key: &str, group: &str) -> io::Error {
+    invalid_data_err(format!(
+        "NOGROUP No such key '{key}' or consumer group '{group}'"
+    ))
+}
+
+impl Store {
+    /// XGROUP CREATE. `id` is where delivery starts; `$` means only entries added later.
+    pub async fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: &str,
+        mkstream: bool,
+    ) -> io::Result<()> {
+        let mut map = self.keyspace.write().await;
+        if !map.contains_key(key) {
+            if !mkstream {
+                return Err(invalid_data_err(
+                    "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.",
+                ));
+            }
+            let notify = self
+                .notifiers
+                .lock()
+                .await
+                .entry(key.to_string())
+                .or_insert(Arc::new(Notify::new()))
+                .clone();
+            map.insert(
+                key.to_string(),
+                Entry::new(RedisValue::Stream(Stream::new(notify)), None),
+            );
+        }
+        let Some(RedisValue::Stream(stream)) = map.get_mut(key).map(|entry| &mut entry.value)
+        else {
+            return Err(wrong_type());
+        };
+        if stream.groups.contains_key(group) {
+            return Err(invalid_data_err(
+                "BUSYGROUP Consumer Group name already exists",
+            ));
+        }
+        let last_delivered = match id {
+            "$" => *stream.previous_id(),
+            id => StreamID::try_from(id).map_err(|_| {
+                invalid_data_err("ERR Invalid stream ID specified as stream command argument")
+            })?,
+        };
+        stream
+            .groups
+            .insert(group.to_string(), ConsumerGroup::new(last_delivered));
+        Ok(())
+    }
+
+    /// One key's worth of XREADGROUP. `>` reads new entries; an explicit ID replays the
+    /// consumer's pending history after it.
+    pub async fn xreadgroup(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        start: &str,
+        count: Option<usize>,
+        noack: bool,
+    ) -> io::Result<StreamEntries> {
+        let mut map = self.keyspace.write().await;
+        let no_group = || {
+            invalid_data_err(format!(
+                "NOGROUP No such key '{key}' or consumer group '{group}' in XREADGROUP with GROUP option"
+            ))
+        };
+        let Some(entry) = map.get_mut(key).filter(|e| !e.is_expired()) else {
+            return Err(no_group());
+        };
+        let RedisValue::Stream(stream) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        // Take the group out so it can be updated while reading the stream's entries.
+        let Some(mut consumer_group) = stream.groups.remove(group) else {
+            return Err(no_group());
+        };
+        let count = count.unwrap_or(usize::MAX);
+        let entries = match start {
+            ">" => Ok(consumer_group.read_new(stream, consumer, count, noack)),
+            id => StreamID::try_from(id)
+                .map(|id| consumer_group.read_history(stream, consumer, id, count)),
+        };
+        stream.groups.insert(group.to_string(), consumer_group);
+        entries
+    }
+
+    /// Removes `ids` from the group's PEL, returning how many were actually pending.
+    pub async fn xack(&self, key: &str, group: &str, ids: &[StreamID]) -> io::Result<usize> {
+        let mut map = self.keyspace.write().await;
+        let Some(entry) = map.get_mut(key).filter(|e| !e.is_expired()) else {
+            return Ok(0);
+        };
+        let RedisValue::Stream(stream) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        let Some(consumer_group) = stream.groups.get_mut(group) else {
+            return Ok(0);
+        };
+        Ok(ids
+            .iter()
+            .filter(|id| consumer_group.pending.remove(id).is_some())
+            .count())
+    }
+
+    /// Hands pending entries idle for at least `min_idle_ms` to `consumer`, returning the
+    /// claimed entries. FORCE also claims stream entries that were never delivered.
+    pub async fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle_ms: u64,
+        ids: &[StreamID],
+        options: &ClaimOptions,
+    ) -> io::Result<StreamEntries> {
+        let mut map = self.keyspace.write().await;
+        let Some(entry) = map.get_mut(key).filter(|e| !e.is_expired()) else {
+            return Err(no_group(key, group));
+        };
+        let RedisValue::Stream(stream) = &mut entry.value else {
+            return Err(wrong_type());
+        };
+        // Take the group out so it can be updated while reading the stream's entries.
+        let Some(mut consumer_group) = stream.groups.remove(group) else {
+            return Err(no_group(key, group));
+        };
+        let claimed = consumer_group.claim(stream, consumer, min_idle_ms, ids, options);
+        stream.groups.insert(group.to_string(), consumer_group);
+        Ok(claimed)
+    }
+
+    /// A snapshot of the group's PEL in ID order.
+    pub async fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> io::Result<Vec<(StreamID, PendingEntry)>> {
+        let map = self.keyspace.read().await;
+        let Some(entry) = map.get(key).filter(|e| !e.is_expired()) else {
+            return Err(no_group(key, group));
+        };
+        let RedisValue::Stream(stream) = &entry.value else {
+            return Err(wrong_type());
+        };
+        let consumer_group = stream.groups.get(group).ok_or_else(|| no_group(key, group))?;
+        Ok(consumer_group
+            .pending
+            .iter()
+            .map(|(id, pending)| (*id, pending.clone()))
+            .collect())
+    }
+}
@@ -32,8 +32,12 @@ impl StreamID {
                 let ms = ms_str
                     .parse::<u64>()
                     .map_err(|_| invalid_data_err("Invalid ms"))?;
+                // Nothing under an older millisecond, or past a full one, can follow `previous`.
                 let mut seq = match previous {
-                    Some(prev) if prev.ms == ms => prev.seq + 1,
+                    Some(prev) if prev.ms > ms => return Err(not_after_top()),
+                    Some(prev) if prev.ms == ms => {
+                        prev.seq.checked_add(1).ok_or_else(not_after_top)?
+                    }
                     _ => 0,
                 };
                 if ms == 0 && seq == 0 {
@@ -63,6 +67,11 @@ impl StreamID {
     }
 }
 
+/// XADD's reply when an id would not sort after the stream's last entry.
+pub(crate) fn not_after_top() -> io::Error {
+    invalid_data_err("ERR The ID specified in XADD is equal or smaller than the target stream top item")
+}
+
 impl TryFrom<&str> for StreamID {
     type Error = io::Error;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
@@ -91,11 +100,13 @@ fn parse_stream_id(id: &str) -> io::Result<(u64, u64)> {
         .parse::<u64>()
         .map_err(|_| invalid_data_err("Invalid milliseconds in stream ID"))?;
 
+    // A bare millisecond time means the first entry at that time, as in Redis.
     let seq = parts
         .next()
-        .ok_or_else(|| invalid_data_err("Invalid ID format"))?
-        .parse::<u64>()
-        .map_err(|_| invalid_data_err("Invalid sequence in stream ID"))?;
+        .map(|seq| seq.parse::<u64>())
+        .transpose()
+        .map_err(|_| invalid_data_err("Invalid sequence in stream ID"))?
+        .unwrap_or(0);
 
     Ok((ms, seq))
 }
@@ -2,7 +2,10 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use ordered_float::OrderedFloat;
 
-use crate::shared_store::shared_store::{Entry, RedisValue, Store};
+use crate::command::{LexBound, ZaddOptions};
+use crate::error_helpers::invalid_data_err;
+use crate::shared_store::keyspace_events::{GENERIC, ZSET};
+use crate::shared_store::shared_store::{wrong_type, Entry, RedisValue, Store};
 
 #[derive(Debug, Clone)]
 pub struct Zrank {
@@ -17,51 +20,132 @@ impl Zrank {
             reverse_map: HashMap::new(),
         }
     }
+
+    /// Places `member` at `score`, moving it out of its previous bucket if needed.
+    /// Returns true when the member was not present before.
+    fn upsert(&mut self, member: String, score: f64) -> bool {
+        let previous = self.reverse_map.insert(member.clone(), score);
+        if let Some(old_score) = previous {
+            if let Some(bucket) = self.data.get_mut(&OrderedFloat(old_score)) {
+                bucket.remove(&member);
+                if bucket.is_empty() {
+                    self.data.remove(&OrderedFloat(old_score));
+                }
+            }
+        }
+        self.data.entry(OrderedFloat(score)).or_default().insert(member);
+        previous.is_none()
+    }
+}
+
+/// Applies the NX/XX/GT/LT rules to a candidate score, returning whether it may be written.
+fn zadd_allowed(options: &ZaddOptions, current: Option<f64>, score: f64) -> bool {
+    match current {
+        None => !options.xx,
+        Some(old) => {
+            if options.nx {
+                return false;
+            }
+            !(options.gt && score <= old || options.lt && score >= old)
+        }
+    }
 }
 
 impl Store {
     pub async fn zadd(&self, key: String, rank: f64, value: String) -> anyhow::Result<i64> {
+        self.zadd_members(key, &ZaddOptions::default(), vec![(rank, value)])
+            .await
+    }
+
+    pub async fn zadd_members(
+        &self,
+        key: String,
+        options: &ZaddOptions,
+        members: Vec<(f64, String)>,
+    ) -> anyhow::Result<i64> {
         let mut keyspace = self.keyspace.write().await;
-        let mut old_value = false;
-        if let Some(entry) = keyspace.get_mut(&key) {
-            match &mut entry.value {
-                RedisValue::ZRank(zrank) => {
-                    if let Some(old_rank) = zrank.reverse_map.get(&value) {
-                        if old_rank != &rank {
-                            old_value = true;
-                            zrank
-                                .data
-                                .get_mut(&OrderedFloat(*old_rank))
-                                .unwrap()
-                                .remove(&value);
-                        }
-                    }
-                    zrank.reverse_map.insert(value.clone(), rank);
-                    let data = zrank.data.entry(OrderedFloat(rank)).or_default();
-                    let added = data.insert(value.clone());
-                    if added && !old_value {
-                        Ok(1)
-                    } else {
-                        Ok(0)
-                    }
-                }
-                _ => Ok(0),
+        if keyspace.get(&key).is_some_and(|entry| entry.is_expired()) {
+            keyspace.remove(&key);
+        }
+        // An existing set is updated in place so it keeps its TTL.
+        let mut created = None;
+        let zrank = match keyspace.get_mut(&key) {
+            Some(entry) => match &mut entry.value {
+                RedisValue::ZRank(zrank) => zrank,
+                _ => return Err(wrong_type().into()),
+            },
+            None => created.insert(Zrank::new()),
+        };
+
+        let mut added = 0;
+        let mut changed = 0;
+        for (score, member) in members {
+            let current = zrank.reverse_map.get(&member).copied();
+            if !zadd_allowed(options, current, score) || current == Some(score) {
+                continue;
             }
+            if zrank.upsert(member, score) {
+                added += 1;
+            } else {
+                changed += 1;
+            }
+        }
+
+        if let Some(zrank) = created.filter(|zrank| !zrank.reverse_map.is_empty()) {
+            keyspace.insert(key.clone(), Entry::new(RedisValue::ZRank(zrank), None));
+        }
+        drop(keyspace);
+        if added + changed > 0 {
+            self.notify_keyspace_event(ZSET, "zadd", &key).await;
+        }
+        if options.ch {
+            Ok(added + changed)
         } else {
-            let mut zrank = Zrank::new();
-            zrank.reverse_map.insert(value.clone(), rank);
-            let mut set = BTreeSet::new();
-            set.insert(value);
-            zrank.data.insert(OrderedFloat(rank), set);
-            let entry: Entry = Entry::new(RedisValue::ZRank(zrank), None);
-            keyspace.insert(key, entry);
-            Ok(1)
+            Ok(added)
+        }
+    }
+
+    /// ZADD ... INCR: the increment is only committed when the resulting score passes
+    /// the NX/XX/GT/LT conditions, otherwise nothing changes and `None` is returned.
+    pub async fn zadd_incr(
+        &self,
+        key: String,
+        options: &ZaddOptions,
+        increment: f64,
+        member: String,
+    ) -> anyhow::Result<Option<f64>> {
+        let mut keyspace = self.keyspace.write().await;
+        if keyspace.get(&key).is_some_and(|entry| entry.is_expired()) {
+            keyspace.remove(&key);
+        }
+        let current = match keyspace.get(&key) {
+            Some(entry) => match &entry.value {
+                RedisValue::ZRank(zrank) => zrank.reverse_map.get(&member).copied(),
+                _ => return Err(wrong_type().into()),
+            },
+            None => None,
+        };
+
+        let score = current.unwrap_or(0.0) + increment;
+        if score.is_nan() {
+            return Err(invalid_data_err("ERR resulting score is not a number (NaN)").into());
         }
+        if !zadd_allowed(options, current, score) {
+            return Ok(None);
+        }
+
+        let entry = keyspace
+            .entry(key)
+            .or_insert_with(|| Entry::new(RedisValue::ZRank(Zrank::new()), None));
+        if let RedisValue::ZRank(zrank) = &mut entry.value {
+            zrank.upsert(member, score);
+        }
+        Ok(Some(score))
     }
 
     pub async fn zrank_command(&self, key: String, value: String) -> anyhow::Result<Option<usize>> {
         let keyspace = self.keyspace.read().await;
-        if let Some(entry) = keyspace.get(&key) {
+        if let Some(entry) = keyspace.get(&key).filter(|entry| !entry.is_expired()) {
             match &entry.value {
                 RedisValue::ZRank(zrank) => {
                     if !zrank.reverse_map.contains_key(&value) {
@@ -86,7 +170,7 @@ impl Store {
 
                     return Ok(None);
                 }
-                _ => return Ok(None),
+                _ => return Err(wrong_type().into()),
             }
         }
         Ok(None)
@@ -94,40 +178,49 @@ impl Store {
 
     pub async fn zrange(&self, key: String, start: i64, stop: i64) -> anyhow::Result<Vec<String>> {
         let keyspace = self.keyspace.read().await;
-        if let Some(entry) = keyspace.get(&key) {
+        if let Some(entry) = keyspace.get(&key).filter(|entry| !entry.is_expired()) {
             match &entry.value {
                 RedisValue::ZRank(zrank) => {
                     let members: Vec<String> = zrank
                         .data
-                        .iter()
-                        .flat_map(|(_, set)| {
+                        .values()
+                        .flat_map(|set| {
                             let mut v: Vec<String> = set.iter().cloned().collect();
                             v.sort(); // sort members with same score lexicographically
                             v
                         })
                         .collect();
-                    let len: usize = members.len();
-                    if len == 0 {
+                    let Some((start, stop)) = rank_range(start, stop, members.len()) else {
                         return Ok(vec![]);
-                    }
-
-                    let start = normalize_index(start, len);
-                    let mut stop = normalize_index(stop, len);
-
-                    if start > stop {
-                        return Ok(vec![]);
-                    }
-
-                    if stop >= len {
-                        stop = len - 1;
-                    }
-
-                    // Grab the slice and map to just the member strings
-                    let range = members[start..=stop].to_vec();
+                    };
+                    return Ok(members[start..=stop].to_vec());
+                }
+                _ => return Err(wrong_type().into()),
+            }
+        }
+        Ok(vec![])
+    }
 
-                    return Ok(range);
+    pub async fn zrangebylex(
+        &self,
+        key: String,
+        min: LexBound,
+        max: LexBound,
+    ) -> anyhow::Result<Vec<String>> {
+        let keyspace = self.keyspace.read().await;
+        if let Some(entry) = keyspace.get(&key).filter(|entry| !entry.is_expired()) {
+            match &entry.value {
+                RedisValue::ZRank(zrank) => {
+                    let members = zrank
+                        .data
+                        .values()
+                        .flatten()
+                        .filter(|member| above_min(member, &min) && below_max(member, &max))
+                        .cloned()
+                        .collect();
+                    return Ok(members);
                 }
-                _ => return Ok(vec![]),
+                _ => return Err(wrong_type().into()),
             }
         }
         Ok(vec![])
@@ -135,7 +228,7 @@ impl Store {
 
     pub async fn zcard(&self, key: String) -> anyhow::Result<i64> {
         let keyspace = self.keyspace.read().await;
-        if let Some(entry) = keyspace.get(&key) {
+        if let Some(entry) = keyspace.get(&key).filter(|entry| !entry.is_expired()) {
             match &entry.value {
                 RedisValue::ZRank(zrank) => {
                     let mut ret = 0;
@@ -144,7 +237,7 @@ impl Store {
                     }
                     return Ok(ret as i64);
                 }
-                _ => return Ok(0),
+                _ => return Err(wrong_type().into()),
             }
         }
         Ok(0)
@@ -152,13 +245,13 @@ impl Store {
 
     pub async fn zscore(&self, key: String, value: String) -> anyhow::Result<Option<f64>> {
         let keyspace = self.keyspace.read().await;
-        if let Some(entry) = keyspace.get(&key) {
+        if let Some(entry) = keyspace.get(&key).filter(|entry| !entry.is_expired()) {
             match &entry.value {
                 RedisValue::ZRank(zrank) => {
                     let ret = zrank.reverse_map.get(&value).copied();
                     return Ok(ret);
                 }
-                _ => return Ok(None),
+                _ => return Err(wrong_type().into()),
             }
         }
         Ok(None)
@@ -169,27 +262,89 @@ impl Store {
         if let Some(entry) = keyspace.get_mut(&key) {
             match &mut entry.value {
                 RedisValue::ZRank(zrank) => {
-                    if let Some(rank) = zrank.reverse_map.get(&value) {
-                        let data = zrank.data.get_mut(&OrderedFloat(*rank)).unwrap();
-                        data.remove(&value);
-                        zrank.reverse_map.remove(&value);
+                    if let Some(rank) = zrank.reverse_map.remove(&value) {
+                        if let Some(bucket) = zrank.data.get_mut(&OrderedFloat(rank)) {
+                            bucket.remove(&value);
+                            if bucket.is_empty() {
+                                zrank.data.remove(&OrderedFloat(rank));
+                            }
+                        }
+                        // Like Redis, a sorted set that loses its last member stops existing.
+                        let emptied = zrank.reverse_map.is_empty();
+                        if emptied {
+                            keyspace.remove(&key);
+                        }
+                        drop(keyspace);
+                        self.notify_keyspace_event(ZSET, "zrem", &key).await;
+                        if emptied {
+                            self.notify_keyspace_event(GENERIC, "del", &key).await;
+                        }
                         return Ok(Some(1i64));
                     }
                 }
-                _ => return Ok(None),
+                _ => return Err(wrong_type().into()),
             }
         }
         Ok(None)
     }
 }
 
-fn normalize_index(idx: i64, len: usize) -> usize {
-    if idx < 0 {
-        let abs = (-idx) as usize;
-        len.saturating_sub(abs)
-    } else if idx as usize >= len {
-        len
-    } else {
-        idx as usize
+fn above_min(member: &str, min: &LexBound) -> bool {
+    match min {
+        LexBound::NegativeInfinity => true,
+        LexBound::PositiveInfinity => false,
+        LexBound::Inclusive(bound) => member >= bound.as_str(),
+        LexBound::Exclusive(bound) => member > bound.as_str(),
+    }
+}
+
+fn below_max(member: &str, max: &LexBound) -> bool {
+    match max {
+        LexBound::NegativeInfinity => false,
+        LexBound::PositiveInfinity => true,
+        LexBound::Inclusive(bound) => member <= bound.as_str(),
+        LexBound::Exclusive(bound) => member < bound.as_str(),
+    }
+}
+
+/// Resolves ZRANGE-style indexes (negative counts from the end) into an inclusive
+/// slice range, or `None` when the window is empty. Mirrors Redis: negative indexes are
+/// offset by `len` first, then `start` is floored at 0 and `stop` capped at `len - 1`.
+fn rank_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { start + len } else { start }.max(0);
+    let stop = if stop < 0 { stop + len } else { stop }.min(len - 1);
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rank_range;
+
+    #[test]
+    fn resolves_in_range_and_negative_indexes() {
+        assert_eq!(rank_range(0, -1, 5), Some((0, 4)));
+        assert_eq!(rank_range(0, 0, 5), Some((0, 0)));
+        assert_eq!(rank_range(1, 3, 5), Some((1, 3)));
+        assert_eq!(rank_range(-2, -1, 5), Some((3, 4)));
+        assert_eq!(rank_range(-5, 1, 5), Some((0, 1)));
+    }
+
+    #[test]
+    fn clamps_out_of_range_bounds() {
+        assert_eq!(rank_range(-100, 100, 5), Some((0, 4)));
+        assert_eq!(rank_range(3, 100, 5), Some((3, 4)));
+        assert_eq!(rank_range(i64::MIN, i64::MAX, 5), Some((0, 4)));
+    }
+
+    #[test]
+    fn empty_windows_are_none() {
+        assert_eq!(rank_range(2, 1, 5), None);
+        assert_eq!(rank_range(5, 10, 5), None);
+        assert_eq!(rank_range(-10, -6, 5), None);
+        assert_eq!(rank_range(0, -1, 0), None);
     }
 }